@@ -1,3 +1,5 @@
+use anyhow::Result;
+use std::io::Cursor;
 use std::ops::*;
 
 pub fn align_up<T: Copy + Add<Output = T> + Sub<Output = T> + Rem<Output = T>>(
@@ -6,3 +8,11 @@ pub fn align_up<T: Copy + Add<Output = T> + Sub<Output = T> + Rem<Output = T>>(
 ) -> T {
     value + (align - value % align) % align
 }
+
+/// Pads the buffer with zeros to a multiple of 16 bytes, and moves the cursor to the end
+pub fn pad16(buf: &mut Cursor<Vec<u8>>) -> Result<()> {
+    let len = align_up(buf.get_ref().len() as u64, 16);
+    buf.get_mut().resize(usize::try_from(len)?, 0);
+    buf.set_position(len);
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use crate::gui::{FieldValue, Gui, PlayObject};
+use crate::magic::FileKind;
+use crate::mesh::Mesh;
+use crate::pak::{PakFileIndex, SyncPakReader};
+use crate::pfb::Pfb;
+use crate::rcol::Rcol;
+use crate::rsz::Rsz;
+use crate::scn::Scn;
+use crate::suffix::SUFFIX_MAP;
+use crate::user::User;
+use crate::uvs::Uvs;
+use anyhow::Result;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Cursor;
+
+/// Paths referenced by the content of a file
+#[derive(Debug, Default)]
+struct FileRefs {
+    paths: Vec<String>,
+    /// Only for MESH. Resolved against the folder of the mesh once the mesh is named
+    material_names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PathDiscovery {
+    /// Newly named entries, sorted by path
+    pub found: Vec<(String, PakFileIndex)>,
+    pub unnamed: Vec<PakFileIndex>,
+    /// Number of resolving passes until no new path was found
+    pub rounds: usize,
+    /// Entries that failed to be read or parsed, and were not scanned for paths
+    pub failed: usize,
+}
+
+fn looks_like_path(s: &str) -> bool {
+    let s = s.strip_prefix('@').unwrap_or(s);
+    let Some(dot) = s.rfind('.') else {
+        return false;
+    };
+    dot != 0
+        && SUFFIX_MAP.contains_key(&s[dot + 1..])
+        && s.bytes()
+            .all(|c| (c == b' ' || c.is_ascii_graphic()) && !br#""*\:<>?|"#.contains(&c))
+}
+
+fn collect_json_strings(value: &serde_json::Value, output: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => output.push(s.clone()),
+        serde_json::Value::Array(array) => {
+            for v in array {
+                collect_json_strings(v, output)
+            }
+        }
+        serde_json::Value::Object(object) => {
+            for v in object.values() {
+                collect_json_strings(v, output)
+            }
+        }
+        _ => (),
+    }
+}
+
+fn collect_rsz(rsz: &Rsz, output: &mut Vec<String>) {
+    output.extend(rsz.extern_slots.values().map(|slot| slot.path.clone()));
+    // Unregistered types are common, in which case only extern slots are used
+    if let Ok(nodes) = rsz.deserialize(None) {
+        for node in nodes {
+            if let Ok(json) = node.to_json() {
+                if let Ok(value) = serde_json::from_str(&json) {
+                    collect_json_strings(&value, output)
+                }
+            }
+        }
+    }
+}
+
+fn collect_gui_object(object: &PlayObject, output: &mut Vec<String>) {
+    for field in object.properties.iter().chain(&object.variables) {
+        match &field.value {
+            FieldValue::Texture(s) | FieldValue::String(s) => output.push(s.clone()),
+            _ => (),
+        }
+    }
+}
+
+fn collect_refs(data: &[u8]) -> Result<FileRefs> {
+    let mut refs = FileRefs::default();
+    let Some(kind) = FileKind::detect(data) else {
+        return Ok(refs);
+    };
+    let file = Cursor::new(data);
+    let paths = &mut refs.paths;
+    match kind {
+        FileKind::User => {
+            let user = User::new(file)?;
+            paths.extend(user.resource_names);
+            paths.extend(user.children.into_iter().map(|child| child.name));
+            collect_rsz(&user.rsz, paths);
+        }
+        FileKind::Pfb => {
+            let pfb = Pfb::new(file)?;
+            paths.extend(pfb.resource_names);
+            paths.extend(pfb.children.into_iter().map(|child| child.name));
+            collect_rsz(&pfb.rsz, paths);
+        }
+        FileKind::Scn => {
+            let scn = Scn::new(file)?;
+            paths.extend(scn.resource_names);
+            paths.extend(scn.prefab_paths);
+            paths.extend(scn.children.into_iter().map(|child| child.name));
+            collect_rsz(&scn.rsz, paths);
+        }
+        FileKind::Rcol => collect_rsz(&Rcol::new(file, false)?.rsz, paths),
+        FileKind::Gui => {
+            let gui = Gui::new(file)?;
+            collect_gui_object(&gui.root, paths);
+            for control in &gui.controls {
+                for object in &control.play_objects {
+                    collect_gui_object(object, paths)
+                }
+            }
+        }
+        FileKind::Uvs => {
+            paths.extend(Uvs::new(file)?.textures.into_iter().map(|t| t.path));
+        }
+        FileKind::Mesh => refs.material_names = Mesh::new(file)?.material_names,
+        FileKind::Tex | FileKind::Msg => (),
+    }
+    refs.paths.retain(|path| looks_like_path(path));
+    Ok(refs)
+}
+
+struct Resolver<'a> {
+    pak: &'a SyncPakReader,
+    /// Base path of each named entry, without language suffix
+    names: HashMap<PakFileIndex, String>,
+    found: BTreeMap<String, PakFileIndex>,
+    tried: HashSet<String>,
+}
+
+impl Resolver<'_> {
+    /// Names entries matching the paths, and returns the ones not named before
+    fn resolve(&mut self, paths: Vec<String>, report: bool) -> Result<Vec<PakFileIndex>> {
+        let mut newly_named = vec![];
+        for path in paths {
+            let path = path.strip_prefix('@').unwrap_or(&path).to_owned();
+            if !self.tried.insert(path.clone()) {
+                continue;
+            }
+            let streaming_path = "streaming/".to_owned() + &path;
+            for path in [path, streaming_path] {
+                for i18n_index in self.pak.find_file_i18n(&path)? {
+                    let index = i18n_index.index;
+                    if self.names.contains_key(&index) {
+                        continue;
+                    }
+                    self.names.insert(index, path.clone());
+                    newly_named.push(index);
+                    if report {
+                        let name = if i18n_index.language.is_empty() {
+                            path.clone()
+                        } else {
+                            format!("{}.{}", path, i18n_index.language)
+                        };
+                        self.found.insert(name, index);
+                    }
+                }
+            }
+        }
+        Ok(newly_named)
+    }
+}
+
+/// Discovers paths from the parsed content of all files in the PAK.
+/// Paths in `known` are only used to name entries, and are not reported as found.
+pub fn discover_paths(pak: &SyncPakReader, known: &[String]) -> Result<PathDiscovery> {
+    let indexs = pak.all_file_indexs();
+    let refs: Vec<Option<FileRefs>> = indexs
+        .par_iter()
+        .map(|&index| collect_refs(&pak.read_file(index).ok()?).ok())
+        .collect();
+    let failed = refs.iter().filter(|refs| refs.is_none()).count();
+    let refs: HashMap<PakFileIndex, FileRefs> = indexs
+        .iter()
+        .cloned()
+        .zip(refs)
+        .filter_map(|(index, refs)| Some((index, refs?)))
+        .collect();
+
+    let mut resolver = Resolver {
+        pak,
+        names: HashMap::new(),
+        found: BTreeMap::new(),
+        tried: HashSet::new(),
+    };
+
+    let known = known
+        .iter()
+        .filter(|path| looks_like_path(path))
+        .cloned()
+        .collect();
+    let mut newly_named = resolver.resolve(known, false)?;
+
+    let mut pending: Vec<String> = refs
+        .values()
+        .flat_map(|refs| refs.paths.iter().cloned())
+        .collect();
+    let mut rounds = 0;
+    loop {
+        // Paths derived from newly named files, such as materials next to a mesh
+        for index in &newly_named {
+            let Some(refs) = refs.get(index) else {
+                continue;
+            };
+            let path = &resolver.names[index];
+            if let Some(stem) = path.strip_suffix(".mesh") {
+                pending.push(format!("{stem}.mdf2"));
+                let folder = stem.rsplit_once('/').map_or("", |(folder, _)| folder);
+                for material in &refs.material_names {
+                    pending.push(format!("{folder}/{material}.mdf2"));
+                }
+            }
+        }
+
+        if pending.is_empty() {
+            break;
+        }
+        rounds += 1;
+        newly_named = resolver.resolve(std::mem::take(&mut pending), true)?;
+        if newly_named.is_empty() {
+            break;
+        }
+    }
+
+    let mut unnamed: Vec<PakFileIndex> = indexs
+        .into_iter()
+        .filter(|index| !resolver.names.contains_key(index))
+        .collect();
+    unnamed.sort();
+
+    Ok(PathDiscovery {
+        found: resolver.found.into_iter().collect(),
+        unnamed,
+        rounds,
+        failed,
+    })
+}
@@ -5,7 +5,7 @@ use crate::gpu::*;
 use crate::gui::*;
 use crate::mesh::*;
 use crate::msg::*;
-use crate::pak::PakReader;
+use crate::pak::{PakFileIndex, SyncPakReader};
 use crate::pfb::Pfb;
 use crate::rcol::Rcol;
 use crate::rsz::*;
@@ -20,7 +20,7 @@ use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
-use std::io::{Cursor, Read, Seek, Write};
+use std::io::{Cursor, Write};
 use std::ops::Deref;
 
 pub static ITEM_ICON_SPECIAL_COLOR: [i32; 7] = [93, 115, 121, 123, 178, 179, 189];
@@ -257,7 +257,7 @@ pub fn gen_collider_mapping(rcol: Rcol) -> Result<ColliderMapping> {
 
 #[allow(clippy::too_many_arguments)]
 pub fn gen_monsters(
-    pak: &mut PakReader<impl Read + Seek>,
+    pak: &SyncPakReader,
     pfb_path_gen: fn(u32, u32) -> String,
     boss_init_path_gen: fn(u32, u32) -> Option<String>,
     collider_path_gen: fn(u32, u32) -> String,
@@ -267,10 +267,8 @@ pub fn gen_monsters(
     is_large: bool,
     version_hint: Option<u32>,
 ) -> Result<Vec<Monster>> {
-    let mut monsters = vec![];
-
     fn sub_file<T: FromRsz + 'static>(
-        pak: &mut PakReader<impl Read + Seek>,
+        pak: &SyncPakReader,
         pfb: &Pfb,
         version_hint: Option<u32>,
     ) -> Result<T> {
@@ -287,14 +285,19 @@ pub fn gen_monsters(
             .context(path.clone())
     }
 
-    for id in 0..1000 {
-        for sub_id in 0..10 {
+    // Monsters are independent from each other, so their files are read in parallel
+    let found: Vec<(u32, u32, String, PakFileIndex)> = (0..1000)
+        .flat_map(|id| (0..10).map(move |sub_id| (id, sub_id)))
+        .filter_map(|(id, sub_id)| {
             let main_pfb_path = pfb_path_gen(id, sub_id);
-            let main_pfb_index = if let Ok(index) = pak.find_file(&main_pfb_path) {
-                index
-            } else {
-                continue;
-            };
+            let main_pfb_index = pak.find_file(&main_pfb_path).ok()?;
+            Some((id, sub_id, main_pfb_path, main_pfb_index))
+        })
+        .collect();
+
+    found
+        .into_par_iter()
+        .map(|(id, sub_id, main_pfb_path, main_pfb_index)| {
             let main_pfb = Pfb::new(Cursor::new(pak.read_file(main_pfb_index)?))?;
 
             let data_base = sub_file(pak, &main_pfb, version_hint).context("data_base")?;
@@ -434,7 +437,7 @@ pub fn gen_monsters(
                 None
             };
 
-            monsters.push(Monster {
+            Ok(Monster {
                 id,
                 sub_id,
                 enemy_type,
@@ -455,19 +458,17 @@ pub fn gen_monsters(
                 unique_mystery,
                 unique_over_mystery,
             })
-        }
-    }
-
-    Ok(monsters)
+        })
+        .collect()
 }
 
-fn get_msg(pak: &mut PakReader<impl Read + Seek>, path: &str) -> Result<Msg> {
+fn get_msg(pak: &SyncPakReader, path: &str) -> Result<Msg> {
     let index = pak.find_file(path)?;
     Msg::new(Cursor::new(pak.read_file(index)?))
 }
 
-fn get_user<T: 'static>(
-    pak: &mut PakReader<impl Read + Seek>,
+fn get_user<T: 'static + Send + Sync>(
+    pak: &SyncPakReader,
     path: &str,
     version_hint: Option<u32>,
 ) -> Result<T> {
@@ -478,8 +479,8 @@ fn get_user<T: 'static>(
         .with_context(|| path.to_string())
 }
 
-fn get_user_opt<T: 'static>(
-    pak: &mut PakReader<impl Read + Seek>,
+fn get_user_opt<T: 'static + Send + Sync>(
+    pak: &SyncPakReader,
     path: &str,
     version_hint: Option<u32>,
 ) -> Result<Option<T>> {
@@ -497,14 +498,14 @@ fn get_user_opt<T: 'static>(
 }
 
 fn get_singleton<T: 'static + SingletonUser>(
-    pak: &mut PakReader<impl Read + Seek>,
+    pak: &SyncPakReader,
     version_hint: Option<u32>,
 ) -> Result<T> {
     Ok(T::from_rsz(get_user(pak, T::PATH, version_hint)?))
 }
 
 fn get_singleton_opt<T: 'static + SingletonUser>(
-    pak: &mut PakReader<impl Read + Seek>,
+    pak: &SyncPakReader,
     version_hint: Option<u32>,
 ) -> Result<Option<T>> {
     if let Some(user) = get_user_opt(pak, T::PATH, version_hint)? {
@@ -514,73 +515,66 @@ fn get_singleton_opt<T: 'static + SingletonUser>(
     }
 }
 
-fn get_weapon_list<BaseData: 'static>(
-    pak: &mut PakReader<impl Read + Seek>,
+// Loads each value as its own task, since they have different types.
+// Each variable is then bound to its value, or the first error in listed order is returned
+macro_rules! load_all {
+    ($($name:ident = $load:expr;)*) => {
+        $(let mut $name = None;)*
+        rayon::scope(|s| {
+            $(s.spawn(|_| $name = Some($load));)*
+        });
+        $(let $name = $name.unwrap()?;)*
+    };
+}
+
+fn get_weapon_list<BaseData: 'static + Send + Sync>(
+    pak: &SyncPakReader,
     weapon_class: &str,
     version_hint: Option<u32>,
 ) -> Result<WeaponList<BaseData>> {
+    let path =
+        |file: &str| format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}{file}");
+    load_all! {
+        base_data = get_user(pak, &path("BaseData.user"), version_hint);
+        product = get_user(pak, &path("ProductData.user"), version_hint);
+        change = get_user(pak, &path("ChangeData.user"), version_hint);
+        process = get_user(pak, &path("ProcessData.user"), version_hint);
+        tree = get_user(pak, &path("UpdateTreeData.user"), version_hint);
+        overwear = get_user_opt(pak, &path("OverwearBaseData.user"), version_hint);
+        overwear_product = get_user_opt(pak, &path("OverwearProductData.user"), version_hint);
+        name = get_msg(pak, &path("_Name.msg"));
+        explain = get_msg(pak, &path("_Explain.msg"));
+        name_mr = get_msg(pak, &path("_Name_MR.msg"));
+        explain_mr = get_msg(pak, &path("_Explain_MR.msg"));
+    }
     Ok(WeaponList {
-        base_data: get_user(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}BaseData.user"),
-            version_hint,
-        )?,
-        product: get_user(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}ProductData.user"),
-            version_hint,
-        )?,
-        change: get_user(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}ChangeData.user"),
-            version_hint,
-        )?,
-        process: get_user(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}ProcessData.user"),
-            version_hint,
-        )?,
-        tree: get_user(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}UpdateTreeData.user"),
-            version_hint,
-        )?,
-        overwear: get_user_opt(
-            pak,
-            &format!(
-                "data/Define/Player/Weapon/{weapon_class}/{weapon_class}OverwearBaseData.user"
-            ),
-            version_hint,
-        )?,
-        overwear_product: get_user_opt(
-            pak,
-            &format!(
-                "data/Define/Player/Weapon/{weapon_class}/{weapon_class}OverwearProductData.user"
-            ),
-            version_hint,
-        )?,
-        name: get_msg(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}_Name.msg"),
-        )?,
-        explain: get_msg(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}_Explain.msg"),
-        )?,
-        name_mr: get_msg(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}_Name_MR.msg"),
-        )?,
-        explain_mr: get_msg(
-            pak,
-            &format!("data/Define/Player/Weapon/{weapon_class}/{weapon_class}_Explain_MR.msg"),
-        )?,
+        base_data,
+        product,
+        change,
+        process,
+        tree,
+        overwear,
+        overwear_product,
+        name,
+        explain,
+        name_mr,
+        explain_mr,
     })
 }
 
-fn get_version_hint<T: 'static + SingletonUser, U: FromRsz>(
-    pak: &mut PakReader<impl Read + Seek>,
-) -> Result<u32> {
+fn get_random_mystery_difficulty(
+    pak: &SyncPakReader,
+    version_hint: Option<u32>,
+) -> Result<Option<RandomMysteryDifficultyRateListData>> {
+    let mut random_mystery_difficulty: Option<RandomMysteryDifficultyRateListData> =
+        get_singleton_opt(pak, version_hint)?;
+    if let Some(rmd) = &mut random_mystery_difficulty {
+        ExternResolver::new(pak, version_hint).resolve(rmd)?;
+    }
+    Ok(random_mystery_difficulty)
+}
+
+fn get_version_hint<T: 'static + SingletonUser, U: FromRsz>(pak: &SyncPakReader) -> Result<u32> {
     let index = pak.find_file(T::PATH)?;
     let user = User::new(Cursor::new(pak.read_file(index)?))?;
     for td in &user.rsz.type_descriptors {
@@ -595,7 +589,7 @@ fn get_version_hint<T: 'static + SingletonUser, U: FromRsz>(
     bail!("Type not found for version hint")
 }
 
-pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
+pub fn gen_pedia(pak: &SyncPakReader) -> Result<Pedia> {
     let version_hint = Some(get_version_hint::<MonsterListBossData, BossMonsterData>(
         pak,
     )?);
@@ -612,368 +606,437 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         ))
     }
 
-    let monsters = gen_monsters(
-        pak,
-        |id, sub_id| format!("enemy/em{id:03}/{sub_id:02}/prefab/em{id:03}_{sub_id:02}.pfb"),
-        boss_init_set_path,
-        gen_em_collider_path,
-        |id, sub_id| {
-            format!("enemy/em{id:03}/{sub_id:02}/user_data/em{id:03}_{sub_id:02}_datatune.user")
-        },
-        gen_em_atk_collider_path,
-        gen_em_shell_collider_path,
-        true,
-        version_hint,
-    )
-    .context("Generating large monsters")?;
-
-    let small_monsters = gen_monsters(
-        pak,
-        |id, sub_id| format!("enemy/ems{id:03}/{sub_id:02}/prefab/ems{id:03}_{sub_id:02}.pfb"),
-        |_, _| None,
-        gen_ems_collider_path,
-        |id, sub_id| {
-            format!("enemy/ems{id:03}/{sub_id:02}/user_data/ems{id:03}_{sub_id:02}_datatune.user")
-        },
-        gen_ems_atk_collider_path,
-        gen_ems_shell_collider_path,
-        false,
-        version_hint,
-    )
-    .context("Generating small monsters")?;
-
-    let monster_names = get_msg(pak, "Message/Tag/Tag_EM_Name.msg")?;
-    let monster_aliases = get_msg(pak, "Message/Tag/Tag_EM_Name_Alias.msg")?;
-    let monster_explains = get_msg(pak, "Message/HunterNote/HN_MonsterListMsg.msg")?;
-
-    let monster_names_mr = get_msg(pak, "Message/Tag_MR/Tag_EM_Name_MR.msg")?;
-    let monster_aliases_mr = get_msg(pak, "Message/Tag_MR/Tag_EM_Name_Alias_MR.msg")?;
-    let monster_explains_mr = get_msg(pak, "Message/HunterNote_MR/HN_MonsterListMsg_MR.msg")?;
-
-    let condition_preset: EnemyConditionPresetData = get_singleton(pak, version_hint)?;
-    condition_preset.verify()?;
-
-    let hunter_note_msg = get_msg(pak, "Message/HunterNote/HN_Hunternote_Menu.msg")?;
-    let hunter_note_msg_mr = get_msg(pak, "Message/HunterNote_MR/HN_Hunternote_Menu_MR.msg")?;
-
-    let quest_hall_msg = get_msg(pak, "Message/Quest/QuestData_Hall.msg")?;
-    let quest_hall_msg_mr = get_msg(pak, "Message/Quest/QuestData_Hall_MR.msg")?;
-    let quest_hall_msg_mr2 = get_msg(pak, "Message/Quest/QuestData_Hall2_MR.msg")?;
-    let quest_village_msg = get_msg(pak, "Message/Quest/QuestData_Village.msg")?;
-    let quest_village_msg_mr = get_msg(pak, "Message/Quest/QuestData_Village_MR.msg")?;
-    let quest_tutorial_msg = get_msg(pak, "Message/Quest/QuestData_Tutorial.msg")?;
-    let quest_arena_msg = get_msg(pak, "Message/Quest/QuestData_Arena.msg")?;
-    let quest_dlc_msg = get_msg(pak, "Message/Quest/QuestData_Dlc.msg")?;
-    let npc_mission_msg = get_msg(pak, "Message/Quest/QuestData_NpcMission.msg")?;
-    let npc_mission_msg_mr = get_msg(pak, "Message/Quest/QuestData_NpcMission_MR.msg")?;
-
-    let armor_head_name_msg = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Name.msg")?;
-    let armor_chest_name_msg = get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Name.msg")?;
-    let armor_arm_name_msg = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Name.msg")?;
-    let armor_waist_name_msg = get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Name.msg")?;
-    let armor_leg_name_msg = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Name.msg")?;
-    let armor_head_explain_msg = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Explain.msg")?;
-    let armor_chest_explain_msg =
-        get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Explain.msg")?;
-    let armor_arm_explain_msg = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Explain.msg")?;
-    let armor_waist_explain_msg =
-        get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Explain.msg")?;
-    let armor_leg_explain_msg = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Explain.msg")?;
-    let armor_series_name_msg =
-        get_msg(pak, "data/Define/Player/Armor/ArmorSeries_Hunter_Name.msg")?;
-
-    let armor_head_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Name_MR.msg")?;
-    let armor_chest_name_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Name_MR.msg")?;
-    let armor_arm_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Name_MR.msg")?;
-    let armor_waist_name_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Name_MR.msg")?;
-    let armor_leg_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Name_MR.msg")?;
-    let armor_head_explain_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Explain_MR.msg")?;
-    let armor_chest_explain_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Explain_MR.msg")?;
-    let armor_arm_explain_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Explain_MR.msg")?;
-    let armor_waist_explain_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Explain_MR.msg")?;
-    let armor_leg_explain_msg_mr =
-        get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Explain_MR.msg")?;
-    let armor_series_name_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Armor/ArmorSeries_Hunter_Name_MR.msg",
-    )?;
-
-    let player_skill_detail_msg = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Detail.msg",
-    )?;
-    let player_skill_explain_msg = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Explain.msg",
-    )?;
-    let player_skill_name_msg = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Name.msg",
-    )?;
-    let player_skill_detail_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Detail_MR.msg",
-    )?;
-    let player_skill_explain_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Explain_MR.msg",
-    )?;
-    let player_skill_name_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Name_MR.msg",
-    )?;
-
-    let hyakuryu_skill_name_msg = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Name.msg",
-    )?;
-    let hyakuryu_skill_explain_msg = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Explain.msg",
-    )?;
-
-    let hyakuryu_skill_name_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Name_MR.msg",
-    )?;
-    let hyakuryu_skill_explain_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Explain_MR.msg",
-    )?;
-
-    let decorations_name_msg = get_msg(
-        pak,
-        "data/Define/Player/Equip/Decorations/Decorations_Name.msg",
-    )?;
-    let decorations_name_msg_mr = get_msg(
-        pak,
-        "data/Define/Player/Equip/Decorations/Decorations_Name_MR.msg",
-    )?;
-    let hyakuryu_decos_name_msg = get_msg(
-        pak,
-        "data/Define/Player/Equip/HyakuryuDeco/HyakuryuDeco_Name_MR.msg",
-    )?;
-
-    let items_name_msg = get_msg(pak, "data/System/ContentsIdSystem/Item/Normal/ItemName.msg")?;
-    let items_explain_msg = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/Item/Normal/ItemExplain.msg",
-    )?;
-    let items_name_msg_mr = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/Item/Normal/ItemName_MR.msg",
-    )?;
-    let items_explain_msg_mr = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/Item/Normal/ItemExplain_MR.msg",
-    )?;
-    let material_category_msg = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/Common/ItemCategoryType_Name.msg",
-    )?;
-    let material_category_msg_mr = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/Common/ItemCategoryType_Name_MR.msg",
-    )?;
-
-    let great_sword = get_weapon_list(pak, "GreatSword", version_hint)?;
-    let short_sword = get_weapon_list(pak, "ShortSword", version_hint)?;
-    let hammer = get_weapon_list(pak, "Hammer", version_hint)?;
-    let lance = get_weapon_list(pak, "Lance", version_hint)?;
-    let long_sword = get_weapon_list(pak, "LongSword", version_hint)?;
-    let slash_axe = get_weapon_list(pak, "SlashAxe", version_hint)?;
-    let gun_lance = get_weapon_list(pak, "GunLance", version_hint)?;
-    let dual_blades = get_weapon_list(pak, "DualBlades", version_hint)?;
-    let horn = get_weapon_list(pak, "Horn", version_hint)?;
-    let insect_glaive = get_weapon_list(pak, "InsectGlaive", version_hint)?;
-    let charge_axe = get_weapon_list(pak, "ChargeAxe", version_hint)?;
-    let light_bowgun = get_weapon_list(pak, "LightBowgun", version_hint)?;
-    let heavy_bowgun = get_weapon_list(pak, "HeavyBowgun", version_hint)?;
-    let bow = get_weapon_list(pak, "Bow", version_hint)?;
-
-    let horn_melody = get_msg(pak, "data/Define/Player/Weapon/Horn/Horn_UniqueParam.msg")?;
-    let horn_melody_mr = get_msg(
-        pak,
-        "data/Define/Player/Weapon/Horn/Horn_UniqueParam_MR.msg",
-    )?;
-    let weapon_series = get_msg(
-        pak,
-        "data/Define/Player/Weapon/WeaponSeries_Hunter_Name.msg",
-    )?;
-    let weapon_series_mr = get_msg(
-        pak,
-        "data/Define/Player/Weapon/WeaponSeries_Hunter_Name_MR.msg",
-    )?;
-
-    let maps = prepare_maps(pak)?;
-    let map_name = get_msg(pak, "Message/Common_Msg/Stage_Name.msg")?;
-    let map_name_mr = get_msg(pak, "Message/Common_Msg_MR/Stage_Name_MR.msg")?;
-
-    let airou_armor_head_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Name.msg",
-    )?;
-    let airou_armor_head_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Explain.msg",
-    )?;
-    let airou_armor_chest_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Name.msg",
-    )?;
-    let airou_armor_chest_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Explain.msg",
-    )?;
-    let dog_armor_head_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Name.msg",
-    )?;
-    let dog_armor_head_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Explain.msg",
-    )?;
-    let dog_armor_chest_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Name.msg",
-    )?;
-    let dog_armor_chest_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Explain.msg",
-    )?;
-    let airou_weapon_name = get_msg(pak, "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Name.msg")?;
-    let airou_weapon_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Explain.msg",
-    )?;
-    let dog_weapon_name = get_msg(pak, "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Name.msg")?;
-    let dog_weapon_explain = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Explain.msg",
-    )?;
-    let airou_series_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/ArmorSeries_OtAirou_Name.msg",
-    )?;
-    let dog_series_name = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/ArmorSeries_OtDog_Name.msg",
-    )?;
-
-    let airou_armor_head_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Name_MR.msg",
-    )?;
-    let airou_armor_head_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Explain_MR.msg",
-    )?;
-    let airou_armor_chest_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Name_MR.msg",
-    )?;
-    let airou_armor_chest_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Explain_MR.msg",
-    )?;
-    let dog_armor_head_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Name_MR.msg",
-    )?;
-    let dog_armor_head_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Explain_MR.msg",
-    )?;
-    let dog_armor_chest_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Name_MR.msg",
-    )?;
-    let dog_armor_chest_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Explain_MR.msg",
-    )?;
-    let airou_weapon_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Name_MR.msg",
-    )?;
-    let airou_weapon_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Explain_MR.msg",
-    )?;
-    let dog_weapon_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Name_MR.msg",
-    )?;
-    let dog_weapon_explain_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Explain_MR.msg",
-    )?;
-    let airou_series_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/ArmorSeries_OtAirou_Name_MR.msg",
-    )?;
-    let dog_series_name_mr = get_msg(
-        pak,
-        "data/Define/Otomo/Equip/Armor/ArmorSeries_OtDog_Name_MR.msg",
-    )?;
-
-    let servant_profile = get_msg(pak, "Message/Servant/ServantProfile_MR.msg")?;
-
-    let mut random_mystery_difficulty: Option<RandomMysteryDifficultyRateListData> =
-        get_singleton_opt(pak, version_hint)?;
-
-    if let Some(rmd) = &mut random_mystery_difficulty {
-        for nand_data in &mut rmd.nand_data {
-            for nand_kinds_data in &mut nand_data.nand_kinds_data {
-                nand_kinds_data.nando_ref_table.load(pak, version_hint)?;
-            }
-        }
+    load_all! {
+        monsters = gen_monsters(
+            pak,
+            |id, sub_id| format!("enemy/em{id:03}/{sub_id:02}/prefab/em{id:03}_{sub_id:02}.pfb"),
+            boss_init_set_path,
+            gen_em_collider_path,
+            |id, sub_id| {
+                format!("enemy/em{id:03}/{sub_id:02}/user_data/em{id:03}_{sub_id:02}_datatune.user")
+            },
+            gen_em_atk_collider_path,
+            gen_em_shell_collider_path,
+            true,
+            version_hint,
+        )
+        .context("Generating large monsters");
+        small_monsters = gen_monsters(
+            pak,
+            |id, sub_id| format!("enemy/ems{id:03}/{sub_id:02}/prefab/ems{id:03}_{sub_id:02}.pfb"),
+            |_, _| None,
+            gen_ems_collider_path,
+            |id, sub_id| {
+                format!(
+                    "enemy/ems{id:03}/{sub_id:02}/user_data/ems{id:03}_{sub_id:02}_datatune.user"
+                )
+            },
+            gen_ems_atk_collider_path,
+            gen_ems_shell_collider_path,
+            false,
+            version_hint,
+        )
+        .context("Generating small monsters");
+        monster_names = get_msg(pak, "Message/Tag/Tag_EM_Name.msg");
+        monster_aliases = get_msg(pak, "Message/Tag/Tag_EM_Name_Alias.msg");
+        monster_explains = get_msg(pak, "Message/HunterNote/HN_MonsterListMsg.msg");
+        monster_names_mr = get_msg(pak, "Message/Tag_MR/Tag_EM_Name_MR.msg");
+        monster_aliases_mr = get_msg(pak, "Message/Tag_MR/Tag_EM_Name_Alias_MR.msg");
+        monster_explains_mr = get_msg(pak, "Message/HunterNote_MR/HN_MonsterListMsg_MR.msg");
+        condition_preset = get_singleton::<EnemyConditionPresetData>(pak, version_hint);
+        hunter_note_msg = get_msg(pak, "Message/HunterNote/HN_Hunternote_Menu.msg");
+        hunter_note_msg_mr = get_msg(pak, "Message/HunterNote_MR/HN_Hunternote_Menu_MR.msg");
+        quest_hall_msg = get_msg(pak, "Message/Quest/QuestData_Hall.msg");
+        quest_hall_msg_mr = get_msg(pak, "Message/Quest/QuestData_Hall_MR.msg");
+        quest_hall_msg_mr2 = get_msg(pak, "Message/Quest/QuestData_Hall2_MR.msg");
+        quest_village_msg = get_msg(pak, "Message/Quest/QuestData_Village.msg");
+        quest_village_msg_mr = get_msg(pak, "Message/Quest/QuestData_Village_MR.msg");
+        quest_tutorial_msg = get_msg(pak, "Message/Quest/QuestData_Tutorial.msg");
+        quest_arena_msg = get_msg(pak, "Message/Quest/QuestData_Arena.msg");
+        quest_dlc_msg = get_msg(pak, "Message/Quest/QuestData_Dlc.msg");
+        npc_mission_msg = get_msg(pak, "Message/Quest/QuestData_NpcMission.msg");
+        npc_mission_msg_mr = get_msg(pak, "Message/Quest/QuestData_NpcMission_MR.msg");
+        armor_head_name_msg = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Name.msg");
+        armor_chest_name_msg = get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Name.msg");
+        armor_arm_name_msg = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Name.msg");
+        armor_waist_name_msg = get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Name.msg");
+        armor_leg_name_msg = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Name.msg");
+        armor_head_explain_msg = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Explain.msg");
+        armor_chest_explain_msg =
+            get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Explain.msg");
+        armor_arm_explain_msg = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Explain.msg");
+        armor_waist_explain_msg =
+            get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Explain.msg");
+        armor_leg_explain_msg = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Explain.msg");
+        armor_series_name_msg =
+            get_msg(pak, "data/Define/Player/Armor/ArmorSeries_Hunter_Name.msg");
+        armor_head_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Name_MR.msg");
+        armor_chest_name_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Name_MR.msg");
+        armor_arm_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Name_MR.msg");
+        armor_waist_name_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Name_MR.msg");
+        armor_leg_name_msg_mr = get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Name_MR.msg");
+        armor_head_explain_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Head/A_Head_Explain_MR.msg");
+        armor_chest_explain_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Chest/A_Chest_Explain_MR.msg");
+        armor_arm_explain_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Arm/A_Arm_Explain_MR.msg");
+        armor_waist_explain_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Waist/A_Waist_Explain_MR.msg");
+        armor_leg_explain_msg_mr =
+            get_msg(pak, "data/Define/Player/Armor/Leg/A_Leg_Explain_MR.msg");
+        armor_series_name_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Armor/ArmorSeries_Hunter_Name_MR.msg",
+        );
+        player_skill_detail_msg = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Detail.msg",
+        );
+        player_skill_explain_msg = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Explain.msg",
+        );
+        player_skill_name_msg = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Name.msg",
+        );
+        player_skill_detail_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Detail_MR.msg",
+        );
+        player_skill_explain_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Explain_MR.msg",
+        );
+        player_skill_name_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlEquipSkill/PlayerSkill_Name_MR.msg",
+        );
+        hyakuryu_skill_name_msg = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Name.msg",
+        );
+        hyakuryu_skill_explain_msg = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Explain.msg",
+        );
+        hyakuryu_skill_name_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Name_MR.msg",
+        );
+        hyakuryu_skill_explain_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlHyakuryuSkill/HyakuryuSkill_Explain_MR.msg",
+        );
+        decorations_name_msg = get_msg(
+            pak,
+            "data/Define/Player/Equip/Decorations/Decorations_Name.msg",
+        );
+        decorations_name_msg_mr = get_msg(
+            pak,
+            "data/Define/Player/Equip/Decorations/Decorations_Name_MR.msg",
+        );
+        hyakuryu_decos_name_msg = get_msg(
+            pak,
+            "data/Define/Player/Equip/HyakuryuDeco/HyakuryuDeco_Name_MR.msg",
+        );
+        items_name_msg = get_msg(pak, "data/System/ContentsIdSystem/Item/Normal/ItemName.msg");
+        items_explain_msg = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/Item/Normal/ItemExplain.msg",
+        );
+        items_name_msg_mr = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/Item/Normal/ItemName_MR.msg",
+        );
+        items_explain_msg_mr = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/Item/Normal/ItemExplain_MR.msg",
+        );
+        material_category_msg = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/Common/ItemCategoryType_Name.msg",
+        );
+        material_category_msg_mr = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/Common/ItemCategoryType_Name_MR.msg",
+        );
+        great_sword = get_weapon_list(pak, "GreatSword", version_hint);
+        short_sword = get_weapon_list(pak, "ShortSword", version_hint);
+        hammer = get_weapon_list(pak, "Hammer", version_hint);
+        lance = get_weapon_list(pak, "Lance", version_hint);
+        long_sword = get_weapon_list(pak, "LongSword", version_hint);
+        slash_axe = get_weapon_list(pak, "SlashAxe", version_hint);
+        gun_lance = get_weapon_list(pak, "GunLance", version_hint);
+        dual_blades = get_weapon_list(pak, "DualBlades", version_hint);
+        horn = get_weapon_list(pak, "Horn", version_hint);
+        insect_glaive = get_weapon_list(pak, "InsectGlaive", version_hint);
+        charge_axe = get_weapon_list(pak, "ChargeAxe", version_hint);
+        light_bowgun = get_weapon_list(pak, "LightBowgun", version_hint);
+        heavy_bowgun = get_weapon_list(pak, "HeavyBowgun", version_hint);
+        bow = get_weapon_list(pak, "Bow", version_hint);
+        horn_melody = get_msg(pak, "data/Define/Player/Weapon/Horn/Horn_UniqueParam.msg");
+        horn_melody_mr = get_msg(
+            pak,
+            "data/Define/Player/Weapon/Horn/Horn_UniqueParam_MR.msg",
+        );
+        weapon_series = get_msg(
+            pak,
+            "data/Define/Player/Weapon/WeaponSeries_Hunter_Name.msg",
+        );
+        weapon_series_mr = get_msg(
+            pak,
+            "data/Define/Player/Weapon/WeaponSeries_Hunter_Name_MR.msg",
+        );
+        maps = prepare_maps(pak);
+        map_name = get_msg(pak, "Message/Common_Msg/Stage_Name.msg");
+        map_name_mr = get_msg(pak, "Message/Common_Msg_MR/Stage_Name_MR.msg");
+        airou_armor_head_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Name.msg",
+        );
+        airou_armor_head_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Explain.msg",
+        );
+        airou_armor_chest_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Name.msg",
+        );
+        airou_armor_chest_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Explain.msg",
+        );
+        dog_armor_head_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Name.msg",
+        );
+        dog_armor_head_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Explain.msg",
+        );
+        dog_armor_chest_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Name.msg",
+        );
+        dog_armor_chest_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Explain.msg",
+        );
+        airou_weapon_name = get_msg(pak, "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Name.msg");
+        airou_weapon_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Explain.msg",
+        );
+        dog_weapon_name = get_msg(pak, "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Name.msg");
+        dog_weapon_explain = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Explain.msg",
+        );
+        airou_series_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/ArmorSeries_OtAirou_Name.msg",
+        );
+        dog_series_name = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/ArmorSeries_OtDog_Name.msg",
+        );
+        airou_armor_head_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Name_MR.msg",
+        );
+        airou_armor_head_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Head_Explain_MR.msg",
+        );
+        airou_armor_chest_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Name_MR.msg",
+        );
+        airou_armor_chest_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtAirouArmor_Chest_Explain_MR.msg",
+        );
+        dog_armor_head_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Name_MR.msg",
+        );
+        dog_armor_head_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Head_Explain_MR.msg",
+        );
+        dog_armor_chest_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Name_MR.msg",
+        );
+        dog_armor_chest_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/OtDogArmor_Chest_Explain_MR.msg",
+        );
+        airou_weapon_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Name_MR.msg",
+        );
+        airou_weapon_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtAirouWeapon_Explain_MR.msg",
+        );
+        dog_weapon_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Name_MR.msg",
+        );
+        dog_weapon_explain_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Weapon/OtDogWeapon_Explain_MR.msg",
+        );
+        airou_series_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/ArmorSeries_OtAirou_Name_MR.msg",
+        );
+        dog_series_name_mr = get_msg(
+            pak,
+            "data/Define/Otomo/Equip/Armor/ArmorSeries_OtDog_Name_MR.msg",
+        );
+        servant_profile = get_msg(pak, "Message/Servant/ServantProfile_MR.msg");
+        random_mystery_difficulty = get_random_mystery_difficulty(pak, version_hint);
+        switch_action_name = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlSwitchAction/PlayerSwitchAction_Name.msg",
+        );
+        switch_action_name_mr = get_msg(
+            pak,
+            "data/Define/Player/Skill/PlSwitchAction/PlayerSwitchAction_Name_MR.msg",
+        );
+        weapon_control = get_msg(pak, "Message/HunterNote/HN_WeaponControlsMsg.msg");
+        weapon_control_mr = get_msg(pak, "Message/HunterNote_MR/HN_WeaponControlsMsg_MR.msg");
+        buff_cage_name = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/LvBuffCage/Normal/LvBuffCage_Name.msg",
+        );
+        buff_cage_explain = get_msg(
+            pak,
+            "data/System/ContentsIdSystem/LvBuffCage/Normal/LvBuffCage_Explain.msg",
+        );
+        dlc_name = get_msg(pak, "Message/DLC/DLC_Name.msg");
+        dlc_name_mr = get_msg(pak, "Message/DLC/DLC_Name_MR.msg");
+        dlc_explain = get_msg(pak, "Message/DLC/DLC_Explain.msg");
+        dlc_explain_mr = get_msg(pak, "Message/DLC/DLC_Explain_MR.msg");
+        award_name = get_msg(pak, "Message/GuildCard/GC_Award_Name.msg");
+        award_name_mr = get_msg(pak, "Message/GuildCard/GC_Award_Name_MR.msg");
+        award_explain = get_msg(pak, "Message/GuildCard/GC_Award_Explain.msg");
+        award_explain_mr = get_msg(pak, "Message/GuildCard/GC_Award_Explain_MR.msg");
+        achievement_name = get_msg(pak, "Message/GuildCard/GC_Achievement_Name.msg");
+        achievement_name_mr = get_msg(pak, "Message/GuildCard/GC_Achievement_Name_MR.msg");
+        achievement_explain = get_msg(pak, "Message/GuildCard/GC_Achievement_Explain.msg");
+        achievement_explain_mr = get_msg(pak, "Message/GuildCard/GC_Achievement_Explain_MR.msg");
+        monster_list = get_singleton(pak, version_hint);
+        monster_lot = get_singleton(pak, version_hint);
+        monster_lot_mr = get_singleton(pak, version_hint);
+        parts_type = get_singleton(pak, version_hint);
+        normal_quest_data = get_singleton(pak, version_hint);
+        normal_quest_data_mr = get_singleton(pak, version_hint);
+        normal_quest_data_for_enemy = get_singleton(pak, version_hint);
+        normal_quest_data_for_enemy_mr = get_singleton(pak, version_hint);
+        dl_quest_data = get_singleton(pak, version_hint);
+        dl_quest_data_for_enemy = get_singleton(pak, version_hint);
+        dl_quest_data_mr = get_singleton_opt(pak, version_hint);
+        dl_quest_data_for_enemy_mr = get_singleton_opt(pak, version_hint);
+        difficulty_rate = get_singleton(pak, version_hint);
+        difficulty_rate_anomaly = get_singleton_opt(pak, version_hint);
+        random_scale = get_singleton(pak, version_hint);
+        size_list = get_singleton(pak, version_hint);
+        discover_em_set_data = get_singleton(pak, version_hint);
+        quest_data_for_reward = get_singleton(pak, version_hint);
+        quest_data_for_reward_mr = get_singleton(pak, version_hint);
+        reward_id_lot_table = get_singleton(pak, version_hint);
+        reward_id_lot_table_mr = get_singleton(pak, version_hint);
+        main_target_reward_lot_num = get_singleton(pak, version_hint);
+        fixed_hyakuryu_quest = get_singleton(pak, version_hint);
+        mystery_reward_item = get_singleton(pak, version_hint);
+        quest_servant = get_singleton(pak, version_hint);
+        supply_data = get_singleton(pak, version_hint);
+        supply_data_mr = get_singleton(pak, version_hint);
+        arena_quest = get_singleton(pak, version_hint);
+        quest_unlock = get_singleton(pak, version_hint);
+        time_attack_reward = get_singleton(pak, version_hint);
+        talk_condition_quest_list = get_singleton(pak, version_hint);
+        npc_mission = get_singleton(pak, version_hint);
+        npc_mission_mr = get_singleton(pak, version_hint);
+        armor = get_singleton(pak, version_hint);
+        armor_series = get_singleton(pak, version_hint);
+        armor_product = get_singleton(pak, version_hint);
+        overwear = get_singleton(pak, version_hint);
+        overwear_product = get_singleton(pak, version_hint);
+        armor_buildup = get_singleton(pak, version_hint);
+        armor_pair = get_singleton(pak, version_hint);
+        equip_skill = get_singleton(pak, version_hint);
+        hyakuryu_skill = get_singleton(pak, version_hint);
+        hyakuryu_skill_recipe = get_singleton(pak, version_hint);
+        decorations = get_singleton(pak, version_hint);
+        decorations_product = get_singleton(pak, version_hint);
+        hyakuryu_decos = get_singleton(pak, version_hint);
+        hyakuryu_decos_product = get_singleton(pak, version_hint);
+        alchemy_pl_skill = get_singleton(pak, version_hint);
+        items = get_singleton(pak, version_hint);
+        hyakuryu_weapon_buildup = get_singleton(pak, version_hint);
+        weapon_chaos_critical = get_singleton_opt(pak, version_hint);
+        item_pop_lot = get_singleton(pak, version_hint);
+        airou_armor = get_singleton(pak, version_hint);
+        airou_armor_product = get_singleton(pak, version_hint);
+        dog_armor = get_singleton(pak, version_hint);
+        dog_armor_product = get_singleton(pak, version_hint);
+        airou_weapon = get_singleton(pak, version_hint);
+        airou_weapon_product = get_singleton(pak, version_hint);
+        dog_weapon = get_singleton(pak, version_hint);
+        dog_weapon_product = get_singleton(pak, version_hint);
+        ot_equip_series = get_singleton(pak, version_hint);
+        airou_overwear = get_singleton(pak, version_hint);
+        dog_overwear = get_singleton(pak, version_hint);
+        ot_overwear_recipe = get_singleton(pak, version_hint);
+        custom_buildup_base = get_singleton_opt(pak, version_hint);
+        custom_buildup_armor_open = get_singleton_opt(pak, version_hint);
+        custom_buildup_weapon_open = get_singleton_opt(pak, version_hint);
+        custom_buildup_armor_material = get_singleton_opt(pak, version_hint);
+        custom_buildup_weapon_material = get_singleton_opt(pak, version_hint);
+        custom_buildup_armor_lot = get_singleton_opt(pak, version_hint);
+        custom_buildup_armor_category_lot = get_singleton_opt(pak, version_hint);
+        custom_buildup_equip_skill_detail = get_singleton_opt(pak, version_hint);
+        custom_buildup_wep_table = get_singleton_opt(pak, version_hint);
+        custom_buildup_slot_bonus = get_singleton_opt(pak, version_hint);
+        random_mystery_enemy = get_singleton_opt(pak, version_hint);
+        random_mystery_rank_release = get_singleton_opt(pak, version_hint);
+        random_mystery_reward_base = get_singleton_opt(pak, version_hint);
+        random_mystery_reward_subtarget = get_singleton_opt(pak, version_hint);
+        progress = get_singleton(pak, version_hint);
+        enemy_rank = get_singleton(pak, version_hint);
+        species = get_singleton(pak, version_hint);
+        buff_cage = get_singleton(pak, version_hint);
+        item_shop = get_singleton(pak, version_hint);
+        item_shop_lot = get_singleton(pak, version_hint);
+        fukudama = get_singleton(pak, version_hint);
+        mystery_labo_trade_item = get_singleton_opt(pak, version_hint);
+        item_mix = get_singleton(pak, version_hint);
+        bbq = get_singleton(pak, version_hint);
+        exchange_item = get_singleton(pak, version_hint);
+        trade_dust = get_singleton(pak, version_hint);
+        trade_feature = get_singleton(pak, version_hint);
+        trade_rare = get_singleton(pak, version_hint);
+        trade = get_singleton(pak, version_hint);
+        spy = get_singleton(pak, version_hint);
+        offcut_convert = get_singleton(pak, version_hint);
+        dlc = get_singleton(pak, version_hint);
+        dlc_add = get_singleton(pak, version_hint);
+        item_pack = get_singleton(pak, version_hint);
+        slc_item_pack = get_singleton(pak, version_hint);
+        system_mystery = get_singleton(pak, version_hint);
+        system_mario = get_singleton(pak, version_hint);
+        award = get_singleton(pak, version_hint);
+        achievement = get_singleton(pak, version_hint);
     }
-
-    let switch_action_name = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlSwitchAction/PlayerSwitchAction_Name.msg",
-    )?;
-
-    let switch_action_name_mr = get_msg(
-        pak,
-        "data/Define/Player/Skill/PlSwitchAction/PlayerSwitchAction_Name_MR.msg",
-    )?;
-
-    let weapon_control = get_msg(pak, "Message/HunterNote/HN_WeaponControlsMsg.msg")?;
-
-    let weapon_control_mr = get_msg(pak, "Message/HunterNote_MR/HN_WeaponControlsMsg_MR.msg")?;
-
-    let buff_cage_name = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/LvBuffCage/Normal/LvBuffCage_Name.msg",
-    )?;
-
-    let buff_cage_explain = get_msg(
-        pak,
-        "data/System/ContentsIdSystem/LvBuffCage/Normal/LvBuffCage_Explain.msg",
-    )?;
-
-    let dlc_name = get_msg(pak, "Message/DLC/DLC_Name.msg")?;
-    let dlc_name_mr = get_msg(pak, "Message/DLC/DLC_Name_MR.msg")?;
-    let dlc_explain = get_msg(pak, "Message/DLC/DLC_Explain.msg")?;
-    let dlc_explain_mr = get_msg(pak, "Message/DLC/DLC_Explain_MR.msg")?;
-
-    let award_name = get_msg(pak, "Message/GuildCard/GC_Award_Name.msg")?;
-    let award_name_mr = get_msg(pak, "Message/GuildCard/GC_Award_Name_MR.msg")?;
-    let award_explain = get_msg(pak, "Message/GuildCard/GC_Award_Explain.msg")?;
-    let award_explain_mr = get_msg(pak, "Message/GuildCard/GC_Award_Explain_MR.msg")?;
-
-    let achievement_name = get_msg(pak, "Message/GuildCard/GC_Achievement_Name.msg")?;
-    let achievement_name_mr = get_msg(pak, "Message/GuildCard/GC_Achievement_Name_MR.msg")?;
-    let achievement_explain = get_msg(pak, "Message/GuildCard/GC_Achievement_Explain.msg")?;
-    let achievement_explain_mr = get_msg(pak, "Message/GuildCard/GC_Achievement_Explain_MR.msg")?;
+    condition_preset.verify()?;
 
     Ok(Pedia {
         monsters,
@@ -985,42 +1048,42 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         monster_aliases_mr,
         monster_explains_mr,
         condition_preset,
-        monster_list: get_singleton(pak, version_hint)?,
+        monster_list,
         hunter_note_msg,
         hunter_note_msg_mr,
         material_category_msg_mr,
-        monster_lot: get_singleton(pak, version_hint)?,
-        monster_lot_mr: get_singleton(pak, version_hint)?,
-        parts_type: get_singleton(pak, version_hint)?,
-        normal_quest_data: get_singleton(pak, version_hint)?,
-        normal_quest_data_mr: get_singleton(pak, version_hint)?,
-        normal_quest_data_for_enemy: get_singleton(pak, version_hint)?,
-        normal_quest_data_for_enemy_mr: get_singleton(pak, version_hint)?,
-        dl_quest_data: get_singleton(pak, version_hint)?,
-        dl_quest_data_for_enemy: get_singleton(pak, version_hint)?,
-        dl_quest_data_mr: get_singleton_opt(pak, version_hint)?,
-        dl_quest_data_for_enemy_mr: get_singleton_opt(pak, version_hint)?,
-        difficulty_rate: get_singleton(pak, version_hint)?,
-        difficulty_rate_anomaly: get_singleton_opt(pak, version_hint)?,
-        random_scale: get_singleton(pak, version_hint)?,
-        size_list: get_singleton(pak, version_hint)?,
-        discover_em_set_data: get_singleton(pak, version_hint)?,
-        quest_data_for_reward: get_singleton(pak, version_hint)?,
-        quest_data_for_reward_mr: get_singleton(pak, version_hint)?,
-        reward_id_lot_table: get_singleton(pak, version_hint)?,
-        reward_id_lot_table_mr: get_singleton(pak, version_hint)?,
-        main_target_reward_lot_num: get_singleton(pak, version_hint)?,
-        fixed_hyakuryu_quest: get_singleton(pak, version_hint)?,
-        mystery_reward_item: get_singleton(pak, version_hint)?,
-        quest_servant: get_singleton(pak, version_hint)?,
-        supply_data: get_singleton(pak, version_hint)?,
-        supply_data_mr: get_singleton(pak, version_hint)?,
-        arena_quest: get_singleton(pak, version_hint)?,
-        quest_unlock: get_singleton(pak, version_hint)?,
-        time_attack_reward: get_singleton(pak, version_hint)?,
-        talk_condition_quest_list: get_singleton(pak, version_hint)?,
-        npc_mission: get_singleton(pak, version_hint)?,
-        npc_mission_mr: get_singleton(pak, version_hint)?,
+        monster_lot,
+        monster_lot_mr,
+        parts_type,
+        normal_quest_data,
+        normal_quest_data_mr,
+        normal_quest_data_for_enemy,
+        normal_quest_data_for_enemy_mr,
+        dl_quest_data,
+        dl_quest_data_for_enemy,
+        dl_quest_data_mr,
+        dl_quest_data_for_enemy_mr,
+        difficulty_rate,
+        difficulty_rate_anomaly,
+        random_scale,
+        size_list,
+        discover_em_set_data,
+        quest_data_for_reward,
+        quest_data_for_reward_mr,
+        reward_id_lot_table,
+        reward_id_lot_table_mr,
+        main_target_reward_lot_num,
+        fixed_hyakuryu_quest,
+        mystery_reward_item,
+        quest_servant,
+        supply_data,
+        supply_data_mr,
+        arena_quest,
+        quest_unlock,
+        time_attack_reward,
+        talk_condition_quest_list,
+        npc_mission,
+        npc_mission_mr,
         quest_hall_msg,
         quest_hall_msg_mr,
         quest_hall_msg_mr2,
@@ -1031,13 +1094,13 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         quest_dlc_msg,
         npc_mission_msg,
         npc_mission_msg_mr,
-        armor: get_singleton(pak, version_hint)?,
-        armor_series: get_singleton(pak, version_hint)?,
-        armor_product: get_singleton(pak, version_hint)?,
-        overwear: get_singleton(pak, version_hint)?,
-        overwear_product: get_singleton(pak, version_hint)?,
-        armor_buildup: get_singleton(pak, version_hint)?,
-        armor_pair: get_singleton(pak, version_hint)?,
+        armor,
+        armor_series,
+        armor_product,
+        overwear,
+        overwear_product,
+        armor_buildup,
+        armor_pair,
         armor_head_name_msg,
         armor_chest_name_msg,
         armor_arm_name_msg,
@@ -1060,35 +1123,35 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         armor_waist_explain_msg_mr,
         armor_leg_explain_msg_mr,
         armor_series_name_msg_mr,
-        equip_skill: get_singleton(pak, version_hint)?,
+        equip_skill,
         player_skill_detail_msg,
         player_skill_explain_msg,
         player_skill_name_msg,
         player_skill_detail_msg_mr,
         player_skill_explain_msg_mr,
         player_skill_name_msg_mr,
-        hyakuryu_skill: get_singleton(pak, version_hint)?,
-        hyakuryu_skill_recipe: get_singleton(pak, version_hint)?,
+        hyakuryu_skill,
+        hyakuryu_skill_recipe,
         hyakuryu_skill_name_msg,
         hyakuryu_skill_explain_msg,
         hyakuryu_skill_name_msg_mr,
         hyakuryu_skill_explain_msg_mr,
-        decorations: get_singleton(pak, version_hint)?,
-        decorations_product: get_singleton(pak, version_hint)?,
+        decorations,
+        decorations_product,
         decorations_name_msg,
         decorations_name_msg_mr,
-        hyakuryu_decos: get_singleton(pak, version_hint)?,
-        hyakuryu_decos_product: get_singleton(pak, version_hint)?,
+        hyakuryu_decos,
+        hyakuryu_decos_product,
         hyakuryu_decos_name_msg,
         //alchemy_pattern: get_singleton(pak,version_hint)?,
-        alchemy_pl_skill: get_singleton(pak, version_hint)?,
+        alchemy_pl_skill,
         /*alchemy_grade_worth: get_singleton(pak,version_hint)?,
         alchemy_rare_type: get_singleton(pak,version_hint)?,
         alchemy_second_skill_lot: get_singleton(pak,version_hint)?,
         alchemy_skill_grade_lot: get_singleton(pak,version_hint)?,
         alchemy_slot_num: get_singleton(pak,version_hint)?,
         alchemy_slot_worth: get_singleton(pak,version_hint)?,*/
-        items: get_singleton(pak, version_hint)?,
+        items,
         items_name_msg,
         items_explain_msg,
         items_name_msg_mr,
@@ -1110,26 +1173,26 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         bow,
         horn_melody,
         horn_melody_mr,
-        hyakuryu_weapon_buildup: get_singleton(pak, version_hint)?,
-        weapon_chaos_critical: get_singleton_opt(pak, version_hint)?,
+        hyakuryu_weapon_buildup,
+        weapon_chaos_critical,
         weapon_series,
         weapon_series_mr,
         maps,
         map_name,
         map_name_mr,
-        item_pop_lot: get_singleton(pak, version_hint)?,
-        airou_armor: get_singleton(pak, version_hint)?,
-        airou_armor_product: get_singleton(pak, version_hint)?,
-        dog_armor: get_singleton(pak, version_hint)?,
-        dog_armor_product: get_singleton(pak, version_hint)?,
-        airou_weapon: get_singleton(pak, version_hint)?,
-        airou_weapon_product: get_singleton(pak, version_hint)?,
-        dog_weapon: get_singleton(pak, version_hint)?,
-        dog_weapon_product: get_singleton(pak, version_hint)?,
-        ot_equip_series: get_singleton(pak, version_hint)?,
-        airou_overwear: get_singleton(pak, version_hint)?,
-        dog_overwear: get_singleton(pak, version_hint)?,
-        ot_overwear_recipe: get_singleton(pak, version_hint)?,
+        item_pop_lot,
+        airou_armor,
+        airou_armor_product,
+        dog_armor,
+        dog_armor_product,
+        airou_weapon,
+        airou_weapon_product,
+        dog_weapon,
+        dog_weapon_product,
+        ot_equip_series,
+        airou_overwear,
+        dog_overwear,
+        ot_overwear_recipe,
         airou_armor_head_name,
         airou_armor_head_explain,
         airou_armor_chest_name,
@@ -1159,64 +1222,64 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
         airou_series_name_mr,
         dog_series_name_mr,
         servant_profile,
-        custom_buildup_base: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_armor_open: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_weapon_open: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_armor_material: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_weapon_material: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_armor_lot: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_armor_category_lot: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_equip_skill_detail: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_wep_table: get_singleton_opt(pak, version_hint)?,
-        custom_buildup_slot_bonus: get_singleton_opt(pak, version_hint)?,
+        custom_buildup_base,
+        custom_buildup_armor_open,
+        custom_buildup_weapon_open,
+        custom_buildup_armor_material,
+        custom_buildup_weapon_material,
+        custom_buildup_armor_lot,
+        custom_buildup_armor_category_lot,
+        custom_buildup_equip_skill_detail,
+        custom_buildup_wep_table,
+        custom_buildup_slot_bonus,
         random_mystery_difficulty,
-        random_mystery_enemy: get_singleton_opt(pak, version_hint)?,
-        random_mystery_rank_release: get_singleton_opt(pak, version_hint)?,
-        random_mystery_reward_base: get_singleton_opt(pak, version_hint)?,
-        random_mystery_reward_subtarget: get_singleton_opt(pak, version_hint)?,
-        progress: get_singleton(pak, version_hint)?,
-        enemy_rank: get_singleton(pak, version_hint)?,
-        species: get_singleton(pak, version_hint)?,
+        random_mystery_enemy,
+        random_mystery_rank_release,
+        random_mystery_reward_base,
+        random_mystery_reward_subtarget,
+        progress,
+        enemy_rank,
+        species,
         switch_action_name,
         switch_action_name_mr,
         weapon_control,
         weapon_control_mr,
-        buff_cage: get_singleton(pak, version_hint)?,
+        buff_cage,
         buff_cage_name,
         buff_cage_explain,
-        item_shop: get_singleton(pak, version_hint)?,
-        item_shop_lot: get_singleton(pak, version_hint)?,
-        fukudama: get_singleton(pak, version_hint)?,
-        mystery_labo_trade_item: get_singleton_opt(pak, version_hint)?,
-        item_mix: get_singleton(pak, version_hint)?,
-        bbq: get_singleton(pak, version_hint)?,
-        exchange_item: get_singleton(pak, version_hint)?,
-        trade_dust: get_singleton(pak, version_hint)?,
-        trade_feature: get_singleton(pak, version_hint)?,
-        trade_rare: get_singleton(pak, version_hint)?,
-        trade: get_singleton(pak, version_hint)?,
-        spy: get_singleton(pak, version_hint)?,
-        offcut_convert: get_singleton(pak, version_hint)?,
-
-        dlc: get_singleton(pak, version_hint)?,
-        dlc_add: get_singleton(pak, version_hint)?,
-        item_pack: get_singleton(pak, version_hint)?,
-        slc_item_pack: get_singleton(pak, version_hint)?,
+        item_shop,
+        item_shop_lot,
+        fukudama,
+        mystery_labo_trade_item,
+        item_mix,
+        bbq,
+        exchange_item,
+        trade_dust,
+        trade_feature,
+        trade_rare,
+        trade,
+        spy,
+        offcut_convert,
+
+        dlc,
+        dlc_add,
+        item_pack,
+        slc_item_pack,
         dlc_name,
         dlc_name_mr,
         dlc_explain,
         dlc_explain_mr,
 
-        system_mystery: get_singleton(pak, version_hint)?,
-        system_mario: get_singleton(pak, version_hint)?,
+        system_mystery,
+        system_mario,
 
-        award: get_singleton(pak, version_hint)?,
+        award,
         award_name,
         award_name_mr,
         award_explain,
         award_explain_mr,
 
-        achievement: get_singleton(pak, version_hint)?,
+        achievement,
         achievement_name,
         achievement_name_mr,
         achievement_explain,
@@ -1225,7 +1288,7 @@ pub fn gen_pedia(pak: &mut PakReader<impl Read + Seek>) -> Result<Pedia> {
 }
 
 fn gen_monster_hitzones(
-    pak: &mut PakReader<impl Read + Seek>,
+    pak: &SyncPakReader,
     output: &impl Sink,
     collider_path_gen: fn(u32, u32) -> String,
     mesh_path_gen: fn(u32, u32) -> String,
@@ -1335,7 +1398,7 @@ fn save_spriter(
     Ok(())
 }
 
-pub fn gen_resources(pak: &mut PakReader<impl Read + Seek>, output: &impl Sink) -> Result<()> {
+pub fn gen_resources(pak: &SyncPakReader, output: &impl Sink) -> Result<()> {
     let mesh_path_gen = |id, mut sub_id| {
         if id == 99 && sub_id == 5 {
             sub_id = 0;
@@ -1687,7 +1750,7 @@ pub fn gen_resources(pak: &mut PakReader<impl Read + Seek>, output: &impl Sink)
 }
 
 fn gen_gui_colors(
-    pak: &mut PakReader<impl Read + Seek>,
+    pak: &SyncPakReader,
     mut file: impl Write,
     gui: &str,
     control_name: &str,
@@ -1744,7 +1807,7 @@ fn gen_gui_colors(
     Ok(())
 }
 
-fn gen_item_colors(pak: &mut PakReader<impl Read + Seek>, mut output: impl Write) -> Result<()> {
+fn gen_item_colors(pak: &SyncPakReader, mut output: impl Write) -> Result<()> {
     gen_gui_colors(
         pak,
         &mut output,
@@ -1760,7 +1823,7 @@ fn gen_item_colors(pak: &mut PakReader<impl Read + Seek>, mut output: impl Write
     Ok(())
 }
 
-fn gen_rarity_colors(pak: &mut PakReader<impl Read + Seek>, output: impl Write) -> Result<()> {
+fn gen_rarity_colors(pak: &SyncPakReader, output: impl Write) -> Result<()> {
     gen_gui_colors(
         pak,
         output,
@@ -3580,7 +3643,7 @@ fn prepare_monsters<'a>(
                 let Some(reward) = reward_lot.get(i) else {
                     // crapcom: v13 chaotic gore
                     eprintln!("Additional quest reward {i} not found for {mystery_reward:?}");
-                    return Ok(None)
+                    return Ok(None);
                 };
                 Ok(Some(reward))
             })
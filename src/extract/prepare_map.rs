@@ -6,12 +6,11 @@ use crate::scn::*;
 use crate::tex::*;
 use crate::user::*;
 use anyhow::{anyhow, bail, Context, Result};
-use nalgebra::*;
 use nalgebra_glm::*;
 use serde::*;
 use std::collections::BTreeMap;
-use std::io::{Cursor, Read, Seek};
-use std::rc::*;
+use std::io::Cursor;
+use std::sync::Arc;
 
 struct MapFiles {
     tex_files: &'static [&'static str],
@@ -165,7 +164,15 @@ pub struct GameMap {
     pub pops: Vec<MapPop>,
 }
 
-fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<Option<GameMap>> {
+/// Position of the object in the world, with the map plane in x and y
+fn world_position(object: &GameObject, world: &Mat4) -> Result<Vec3> {
+    object
+        .get_component::<rsz::Transform>()
+        .context("Lack of transform")?;
+    Ok(world.column(3).xyz().xzy())
+}
+
+fn get_map(pak: &SyncPakReader, files: &MapFiles) -> Result<Option<GameMap>> {
     if pak.find_file(files.scene_file).is_err() {
         return Ok(None);
     }
@@ -177,31 +184,30 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
 
     let scale: rsz::GuiMapScaleDefineData = if scale.symbol() == rsz::GuiMapScaleDefineData::SYMBOL
     {
-        Rc::try_unwrap(scale.downcast().unwrap()).map_err(|_| anyhow!("Shared node"))?
+        Arc::try_unwrap(scale.downcast().unwrap()).map_err(|_| anyhow!("Shared node"))?
     } else if scale.symbol() == rsz::GuiMap07DefineData::SYMBOL {
         let scale: rsz::GuiMap07DefineData =
-            Rc::try_unwrap(scale.downcast().unwrap()).map_err(|_| anyhow!("Shared node"))?;
+            Arc::try_unwrap(scale.downcast().unwrap()).map_err(|_| anyhow!("Shared node"))?;
         scale.base.0
     } else {
         bail!("Unknown map scale type {}", scale.symbol())
     };
 
     let scene = Scene::new(pak, files.scene_file)?;
+    scene.print_rsz_reports(files.scene_file);
 
     let mut pops = vec![];
+    // Fishing points share their spawn data
+    let mut resolver = rsz::ExternResolver::new(pak, None);
 
-    scene.for_each_object(&mut |object: &GameObject| {
+    scene.for_each_object_world(&mut |object: &GameObject, world: &Mat4| {
         if object
             .get_component::<rsz::M31IsletArrivalChecker>()
             .is_ok()
         {
             return Ok(true);
         } else if let Ok(behavior) = object.get_component::<rsz::ItemPopBehavior>() {
-            let transform = object
-                .get_component::<rsz::Transform>()
-                .context("Lack of transform")?;
-
-            let position = transform.position.xzy();
+            let position = world_position(object, world)?;
             let relic = object.get_component::<rsz::RelicNoteUnlock>().ok();
             let kind = MapPopKind::Item {
                 behavior: behavior.clone(),
@@ -210,16 +216,9 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
 
             pops.push(MapPop { position, kind });
         } else if let Ok(behavior) = object.get_component::<rsz::WireLongJumpUnlock>() {
-            let transform = object
-                .get_component::<rsz::Transform>()
-                .context("Lack of transform")?;
+            let position = world_position(object, world)?;
 
-            let position = transform.position.xzy();
-
-            let mat = geometry::UnitQuaternion::new_normalize(Quat::from(transform.rotation))
-                .to_rotation_matrix();
-            let tester = make_vec3(&[1.0, 0.0, 0.0]);
-            let rotated = mat * tester;
+            let rotated = world * vec4(1.0, 0.0, 0.0, 0.0);
             let angle = f32::atan2(rotated.x, rotated.z);
 
             let kind = MapPopKind::WireLongJump {
@@ -229,22 +228,15 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
 
             pops.push(MapPop { position, kind });
         } else if let Ok(behavior) = object.get_component::<rsz::FishingPoint>() {
-            let transform = object
-                .get_component::<rsz::Transform>()
-                .context("Lack of transform")?;
-
-            let position = transform.position.xzy();
+            let position = world_position(object, world)?;
             let mut behavior = behavior.clone();
-            behavior.fish_spawn_data.load(pak, None)?;
+            behavior.fish_spawn_data.load(&mut resolver)?;
 
             let kind = MapPopKind::FishingPoint { behavior };
 
             pops.push(MapPop { position, kind });
         } else if let Ok(behavior) = object.get_component::<rsz::OtomoReconSpot>() {
-            let transform = object
-                .get_component::<rsz::Transform>()
-                .context("Lack of transform")?;
-            let position = transform.position.xzy();
+            let position = world_position(object, world)?;
             pops.push(MapPop {
                 position,
                 kind: MapPopKind::Recon {
@@ -252,10 +244,7 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
                 },
             });
         } else if let Ok(behavior) = object.get_component::<rsz::TentBehavior>() {
-            let transform = object
-                .get_component::<rsz::Transform>()
-                .context("Lack of transform")?;
-            let position = transform.position.xzy();
+            let position = world_position(object, world)?;
             pops.push(MapPop {
                 position,
                 kind: MapPopKind::Camp {
@@ -263,23 +252,12 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
                 },
             });
         } else {
-            for child in &object.children {
-                if let Ok(behavior) = child.get_component::<rsz::TentBehavior>() {
-                    let transform = object
-                        .get_component::<rsz::Transform>()
-                        .context("Lack of transform")?;
-                    let sub_transform = child
-                        .get_component::<rsz::Transform>()
-                        .context("Lack of transform")?;
-                    let position = transform.position.xzy() + sub_transform.position.xzy();
-                    pops.push(MapPop {
-                        position,
-                        kind: MapPopKind::Camp {
-                            behavior: behavior.clone(),
-                        },
-                    });
-                }
-            }
+            // Pops placed by a prefab are under the instancing object
+            let has_tent = object
+                .children
+                .iter()
+                .any(|child| child.get_component::<rsz::TentBehavior>().is_ok());
+            return Ok(object.prefab.is_some() || has_tent);
         }
 
         Ok(false)
@@ -294,7 +272,7 @@ fn get_map<F: Read + Seek>(pak: &mut PakReader<F>, files: &MapFiles) -> Result<O
     }))
 }
 
-pub fn prepare_maps(pak: &mut PakReader<impl Read + Seek>) -> Result<BTreeMap<i32, GameMap>> {
+pub fn prepare_maps(pak: &SyncPakReader) -> Result<BTreeMap<i32, GameMap>> {
     MAP_FILES
         .iter()
         .enumerate()
@@ -309,7 +287,7 @@ pub fn prepare_maps(pak: &mut PakReader<impl Read + Seek>) -> Result<BTreeMap<i3
         .collect()
 }
 
-pub fn gen_map_resource(pak: &mut PakReader<impl Read + Seek>, output: &impl Sink) -> Result<()> {
+pub fn gen_map_resource(pak: &SyncPakReader, output: &impl Sink) -> Result<()> {
     for (i, f) in MAP_FILES.iter().enumerate() {
         if let Some(f) = f {
             for (j, &name) in f.tex_files.iter().enumerate() {
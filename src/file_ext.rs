@@ -2,7 +2,7 @@ use crate::align::*;
 use anyhow::{bail, Result};
 use nalgebra_glm::*;
 use std::convert::TryInto;
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 pub trait ReadExt {
     fn read_bool(&mut self) -> Result<bool>;
     fn read_u8(&mut self) -> Result<u8>;
@@ -24,6 +24,21 @@ pub trait ReadExt {
     fn read_f32m4x4(&mut self) -> Result<Mat4x4>;
 }
 
+pub trait WriteExt {
+    fn write_u8(&mut self, v: u8) -> Result<()>;
+    fn write_u16(&mut self, v: u16) -> Result<()>;
+    fn write_u32(&mut self, v: u32) -> Result<()>;
+    fn write_u64(&mut self, v: u64) -> Result<()>;
+    fn write_i8(&mut self, v: i8) -> Result<()>;
+    fn write_i16(&mut self, v: i16) -> Result<()>;
+    fn write_i32(&mut self, v: i32) -> Result<()>;
+    fn write_i64(&mut self, v: i64) -> Result<()>;
+    fn write_f32(&mut self, v: f32) -> Result<()>;
+    fn write_f64(&mut self, v: f64) -> Result<()>;
+    fn write_magic(&mut self, magic: &[u8; 4]) -> Result<()>;
+    fn write_u16str(&mut self, v: &str) -> Result<()>;
+}
+
 pub trait SeekExt {
     fn seek_noop(&mut self, from_start: u64) -> Result<u64>;
     fn seek_assert_align_up(&mut self, from_start: u64, align: u64) -> Result<u64>;
@@ -141,6 +156,59 @@ impl<T: Read + ?Sized> ReadExt for T {
     }
 }
 
+impl<T: Write + ?Sized> WriteExt for T {
+    fn write_u8(&mut self, v: u8) -> Result<()> {
+        self.write_all(&[v])?;
+        Ok(())
+    }
+    fn write_u16(&mut self, v: u16) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_u32(&mut self, v: u32) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_u64(&mut self, v: u64) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_i8(&mut self, v: i8) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_i16(&mut self, v: i16) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_i32(&mut self, v: i32) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_i64(&mut self, v: i64) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_f32(&mut self, v: f32) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_f64(&mut self, v: f64) -> Result<()> {
+        self.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_magic(&mut self, magic: &[u8; 4]) -> Result<()> {
+        self.write_all(magic)?;
+        Ok(())
+    }
+    fn write_u16str(&mut self, v: &str) -> Result<()> {
+        for c in v.encode_utf16() {
+            self.write_u16(c)?;
+        }
+        self.write_u16(0)
+    }
+}
+
 impl<T: Seek + Read + ?Sized> SeekExt for T {
     fn seek_noop(&mut self, from_start: u64) -> Result<u64> {
         let pos = self.stream_position()?;
@@ -0,0 +1,194 @@
+use crate::gui::Gui;
+use crate::mesh::Mesh;
+use crate::msg::Msg;
+use crate::pfb::Pfb;
+use crate::rcol::Rcol;
+use crate::rsz::Rsz;
+use crate::scn::Scn;
+use crate::tex::Tex;
+use crate::user::User;
+use crate::uvs::Uvs;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+/// File formats this crate can parse, recognized by their magic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileKind {
+    User,
+    Pfb,
+    Scn,
+    Rcol,
+    Mesh,
+    Tex,
+    Msg,
+    Gui,
+    Uvs,
+}
+
+impl FileKind {
+    pub fn detect(data: &[u8]) -> Option<FileKind> {
+        let magic = data.get(0..4)?;
+        let kind = match magic {
+            b"USR\0" => FileKind::User,
+            b"PFB\0" => FileKind::Pfb,
+            b"SCN\0" => FileKind::Scn,
+            b"RCOL" => FileKind::Rcol,
+            b"MESH" => FileKind::Mesh,
+            b"TEX\0" => FileKind::Tex,
+            b".SVU" => FileKind::Uvs,
+            _ => match data.get(4..8)? {
+                b"GMSG" => FileKind::Msg,
+                b"GUIR" => FileKind::Gui,
+                _ => return None,
+            },
+        };
+        Some(kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileKind::User => "user",
+            FileKind::Pfb => "pfb",
+            FileKind::Scn => "scn",
+            FileKind::Rcol => "rcol",
+            FileKind::Mesh => "mesh",
+            FileKind::Tex => "tex",
+            FileKind::Msg => "msg",
+            FileKind::Gui => "gui",
+            FileKind::Uvs => "uvs",
+        }
+    }
+
+    pub fn has_rsz(self) -> bool {
+        matches!(
+            self,
+            FileKind::User | FileKind::Pfb | FileKind::Scn | FileKind::Rcol
+        )
+    }
+
+    /// Parses the file and returns its RSZ block, if the format has one
+    pub fn parse_rsz(self, data: &[u8]) -> Result<Option<Rsz>> {
+        let file = Cursor::new(data);
+        Ok(match self {
+            FileKind::User => Some(User::new(file)?.rsz),
+            FileKind::Pfb => Some(Pfb::new(file)?.rsz),
+            FileKind::Scn => Some(Scn::new(file)?.rsz),
+            FileKind::Rcol => Some(Rcol::new(file, false)?.rsz),
+            _ => None,
+        })
+    }
+
+    /// Parses the file and throws away the result.
+    /// For files containing RSZ, also returns the result of deserializing the RSZ.
+    pub fn try_parse(self, data: &[u8]) -> Result<Option<Result<()>>> {
+        let file = Cursor::new(data);
+        let rsz: Option<Rsz> = match self {
+            FileKind::User => Some(User::new(file)?.rsz),
+            FileKind::Pfb => Some(Pfb::new(file)?.rsz),
+            FileKind::Scn => Some(Scn::new(file)?.rsz),
+            FileKind::Rcol => Some(Rcol::new(file, false)?.rsz),
+            FileKind::Mesh => {
+                Mesh::new(file)?;
+                None
+            }
+            FileKind::Tex => {
+                Tex::new(file)?;
+                None
+            }
+            FileKind::Msg => {
+                Msg::new(file)?;
+                None
+            }
+            FileKind::Gui => {
+                Gui::new(file)?;
+                None
+            }
+            FileKind::Uvs => {
+                Uvs::new(file)?;
+                None
+            }
+        };
+        Ok(rsz.map(|rsz| rsz.deserialize(None).map(|_| ())))
+    }
+}
+
+/// Printable form of the first four bytes of a file.
+/// Non-alphanumeric bytes are written as `_xx`.
+pub fn magic_name(data: &[u8]) -> String {
+    let Some(magic) = data.get(0..4) else {
+        return "short".to_owned();
+    };
+    let mut name = String::new();
+    for c in magic {
+        if c.is_ascii_alphanumeric() {
+            name.push(*c as char);
+        } else {
+            name += &format!("_{c:02x}");
+        }
+    }
+    name
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CensusStat {
+    pub count: usize,
+    pub size: u64,
+    pub parsed: usize,
+    /// Only counted for formats containing RSZ
+    pub deserialized: usize,
+}
+
+impl CensusStat {
+    fn merge(&mut self, other: CensusStat) {
+        self.count += other.count;
+        self.size += other.size;
+        self.parsed += other.parsed;
+        self.deserialized += other.deserialized;
+    }
+}
+
+/// Entry counts and parse results of a set of files, grouped by magic
+#[derive(Debug, Default, Clone)]
+pub struct Census {
+    pub known: BTreeMap<FileKind, CensusStat>,
+    /// Files not recognized by any parser, keyed by `magic_name`
+    pub unknown: BTreeMap<String, CensusStat>,
+    /// Files that failed to be read at all
+    pub unreadable: usize,
+}
+
+impl Census {
+    pub fn add(&mut self, data: &[u8]) {
+        let Some(kind) = FileKind::detect(data) else {
+            let stat = self.unknown.entry(magic_name(data)).or_default();
+            stat.count += 1;
+            stat.size += data.len() as u64;
+            return;
+        };
+        let stat = self.known.entry(kind).or_default();
+        stat.count += 1;
+        stat.size += data.len() as u64;
+        match kind.try_parse(data) {
+            Ok(None) => stat.parsed += 1,
+            Ok(Some(rsz)) => {
+                stat.parsed += 1;
+                if rsz.is_ok() {
+                    stat.deserialized += 1;
+                }
+            }
+            Err(_) => (),
+        }
+    }
+
+    pub fn merge(mut self, other: Census) -> Census {
+        for (kind, stat) in other.known {
+            self.known.entry(kind).or_default().merge(stat);
+        }
+        for (magic, stat) in other.unknown {
+            self.unknown.entry(magic).or_default().merge(stat);
+        }
+        self.unreadable += other.unreadable;
+        self
+    }
+}
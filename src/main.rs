@@ -1,24 +1,26 @@
 #![recursion_limit = "4096"]
 
 use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::*;
 use clap::*;
 use minidump::*;
 use rayon::prelude::*;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::*;
-use std::sync::Mutex;
 
 mod align;
 mod bitfield;
 mod collada;
+mod discover;
 mod extract;
 mod file_ext;
 mod gpu;
 mod gui;
 mod hash;
+mod magic;
 mod mesh;
 mod msg;
 mod pak;
@@ -26,6 +28,8 @@ mod part_color;
 mod pfb;
 mod rcol;
 mod rsz;
+mod rsz_diff;
+mod scene_query;
 mod scn;
 mod suffix;
 mod tdb;
@@ -37,6 +41,7 @@ use extract::hash_store::*;
 use extract::sink::*;
 use file_ext::*;
 use gui::*;
+use magic::*;
 use mesh::*;
 use msg::*;
 use pak::*;
@@ -89,6 +94,140 @@ pub struct TdbOptions {
 }
 
 #[derive(clap::Parser)]
+pub struct PakKeyOptions {
+    /// Base64 of the RSA modulus that decrypts the key in encrypted PAK files
+    #[clap(long, global = true, env = "MHRICE_PAK_KEY_MOD")]
+    pub pak_key_mod: Option<String>,
+
+    /// Base64 of an already decrypted 32-byte key, used for every encrypted PAK file
+    #[clap(long, global = true, env = "MHRICE_PAK_KEY")]
+    pub pak_key: Option<String>,
+
+    /// File containing either the modulus or the decrypted key in base64.
+    /// A 32-byte value is taken as the key.
+    #[clap(long, global = true, env = "MHRICE_PAK_KEY_FILE")]
+    pub pak_key_file: Option<String>,
+
+    /// Folder to cache guessed keys in, so that later runs skip guessing
+    #[clap(long, global = true, env = "MHRICE_PAK_KEY_CACHE")]
+    pub pak_key_cache: Option<String>,
+}
+
+impl PakKeyOptions {
+    fn into_config(self) -> Result<PakKeyConfig> {
+        fn decode_key(key: &str) -> Result<[u8; 0x20]> {
+            BASE64_STANDARD
+                .decode(key.trim())?
+                .try_into()
+                .map_err(|_| anyhow!("PAK key must be 32 bytes"))
+        }
+
+        let mut config = PakKeyConfig {
+            cache_dir: self.pak_key_cache.map(PathBuf::from),
+            ..Default::default()
+        };
+        if let Some(file) = self.pak_key_file {
+            let content = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read key file {file}"))?;
+            let content = BASE64_STANDARD.decode(content.trim())?;
+            if content.len() == 0x20 {
+                config.key = Some(content.try_into().unwrap());
+            } else {
+                config.modulus = Some(content);
+            }
+        }
+        if let Some(modulus) = self.pak_key_mod {
+            config.modulus = Some(BASE64_STANDARD.decode(modulus.trim())?);
+        }
+        if let Some(key) = self.pak_key {
+            config.key = Some(decode_key(&key)?);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MeshFormat {
+    Obj,
+    Dae,
+}
+
+#[derive(clap::Parser)]
+pub struct SceneQueryOptions {
+    /// Glob on the object name. `*` and `?` match any characters
+    #[clap(long)]
+    pub object: Option<String>,
+
+    /// Glob on the hierarchy path of folder and object names, such as `Gimmick/**/Pop*`.
+    /// `*` stays within a path segment, and `**` spans segments
+    #[clap(long)]
+    pub path: Option<String>,
+
+    /// Symbol of a component the object must have, such as snow.stage.pop.ItemPopBehavior
+    #[clap(short, long)]
+    pub component: Vec<String>,
+
+    /// Condition on a component field, such as `pop_id=5` or `snow.stage.pop.ItemPopBehavior:pop_id>=100`.
+    /// Operators are = != < <= > >= and ~ for globs
+    #[clap(short = 'w', long = "where")]
+    pub predicate: Vec<String>,
+}
+
+#[derive(clap::Parser)]
+pub struct SceneEditOptions {
+    /// Path of the game object, made of the names of folders and objects joined by `/`
+    #[clap(long)]
+    pub object: String,
+
+    /// Create the object, with a transform, instead of editing an existing one
+    #[clap(long)]
+    pub create: bool,
+
+    /// GUID of the created object, required for SCN files. Objects in PFB files have none
+    #[clap(long, requires = "create")]
+    pub guid: Option<String>,
+
+    /// New local position of the object, as `x,y,z`
+    #[clap(long)]
+    pub position: Option<String>,
+
+    /// Move the object under this folder or object, keeping its local transform. `/` for the root
+    #[clap(long)]
+    pub parent: Option<String>,
+
+    /// Symbol of a component to remove
+    #[clap(long)]
+    pub remove_component: Vec<String>,
+
+    /// JSON of a component to add, in the form printed by read-user
+    #[clap(long)]
+    pub add_component: Vec<String>,
+
+    /// Remove the object and its children
+    #[clap(long, conflicts_with_all = ["create", "position", "parent", "remove_component", "add_component"])]
+    pub remove: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum QueryFormat {
+    Json,
+    Csv,
+}
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(flatten)]
+    pak_key: PakKeyOptions,
+
+    /// JSON file of RSZ type layouts, used to read types without a built-in definition
+    #[clap(long, global = true, env = "MHRICE_RSZ_SCHEMA")]
+    rsz_schema: Option<String>,
+
+    #[clap(subcommand)]
+    command: Mhrice,
+}
+
+#[derive(clap::Subcommand)]
 enum Mhrice {
     /// Dump a sub-file with specific name from the PAK file
     Dump {
@@ -118,6 +257,20 @@ enum Mhrice {
         output: String,
     },
 
+    /// Pack a folder of sub-files into a new PAK file
+    PackPak {
+        /// Input directory. Paths relative to it are used as sub-file names,
+        /// so it should contain the "natives" folder
+        #[clap(short, long)]
+        input: String,
+        /// Output PAK file
+        #[clap(short, long)]
+        output: String,
+        /// Compression format for sub-files
+        #[clap(short, long, value_enum, default_value_t = PakCompression::Zstd)]
+        compression: PakCompression,
+    },
+
     /// Scan the PAK file to verify known file formats that contains RSZ
     ///
     /// This will verify the files conform the format,
@@ -129,6 +282,48 @@ enum Mhrice {
         /// Print all gathered CRC instead of mismatched ones
         #[clap(short, long)]
         crc: bool,
+        /// Also write back every USER file and compare with the original
+        #[clap(long)]
+        round_trip: bool,
+        /// Version of the game for --round-trip, for the CRC of each type. Defaults to the latest one
+        #[clap(short, long)]
+        version: Option<u32>,
+        /// Write the mismatched CRCs with the files they appear in as JSON, for recheck-rsz
+        #[clap(long)]
+        report: Option<String>,
+    },
+
+    /// Deserialize again the files in a scan-rsz report, reading each mismatched CRC
+    /// as a candidate version, to find the types whose layout really changed
+    RecheckRsz {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+        /// Path to the report written by scan-rsz --report
+        #[clap(short, long)]
+        report: String,
+        /// Candidate version for all new CRCs. Defaults to the latest version of each type
+        #[clap(short, long)]
+        version: Option<u32>,
+    },
+
+    /// Generate rsz_struct! and rsz_enum! definitions for RSZ types
+    ///
+    /// Struct layouts are taken from the RSZ schema given by --rsz-schema,
+    /// and enum values from the TDB.
+    GenRsz {
+        /// Full name of a type, or a namespace such as "snow.data" for all types in it
+        #[clap(short, long)]
+        name: String,
+        /// TDB dumped as JSON by read-tdb --json
+        #[clap(short, long)]
+        tdb: Option<String>,
+        /// Version for the CRC in the schema, such as 150000 for 15_00_00
+        #[clap(short, long, default_value_t = 0)]
+        version: u32,
+        /// Output file. Otherwise, print to stdout
+        #[clap(short, long)]
+        output: Option<String>,
     },
 
     /// Generate JSON file of game information from the PAK file
@@ -212,6 +407,17 @@ enum Mhrice {
         dmp: Vec<String>,
     },
 
+    /// Search for paths referenced by the parsed content of all files in the PAK
+    DiscoverPath {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+
+        /// Path to the list of already known paths, which are not reported again
+        #[clap(short, long)]
+        list: Option<String>,
+    },
+
     /// Dump all sub-files from the PAK file
     DumpTree {
         /// Path to the PAK file
@@ -225,6 +431,58 @@ enum Mhrice {
         output: String,
     },
 
+    /// List sub-files in the PAK file by name
+    Ls {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+        /// File name list, can be the output from search-path command
+        #[clap(short, long)]
+        list: String,
+    },
+
+    /// Report sub-files that are replaced by later PAK files, such as patch PAK files
+    Overrides {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+        /// Optional file name list, can be the output from search-path command
+        #[clap(short, long)]
+        list: Option<String>,
+    },
+
+    /// Verify that every sub-file in the PAK file can be decoded
+    VerifyPak {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+    },
+
+    /// Convert a file of any supported format to a readable form, detected by its magic
+    Convert {
+        /// Path to the input file, or to the folder when converting recursively.
+        /// If PAK files are given, this is the path of the sub-file in them
+        input: String,
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+        /// Output path without extension. A folder when converting recursively
+        #[clap(short, long)]
+        output: String,
+        /// Convert all files in the input folder, such as one produced by dump-tree
+        #[clap(short, long)]
+        recursive: bool,
+        #[clap(long, value_enum, default_value_t = MeshFormat::Obj)]
+        mesh_format: MeshFormat,
+    },
+
+    /// Classify all files in the PAK by magic and report parse success rates
+    Census {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+    },
+
     /// Scan the PAK file and verify the format of all MESH files
     ScanMesh {
         /// Path to the PAK file
@@ -349,6 +607,61 @@ enum Mhrice {
         /// Version of the game, optional
         #[clap(short, long)]
         version: Option<u32>,
+        /// Load the USER files it references from the PAK, and write them in place of the paths
+        #[clap(long)]
+        resolve_externs: bool,
+        /// Path to the PAK file, for --resolve-externs
+        #[clap(short, long)]
+        pak: Vec<String>,
+    },
+
+    /// Write a USER file from the JSON printed by read-user
+    WriteUser {
+        /// Path to the JSON file, containing one or more root instances
+        #[clap(short, long)]
+        json: String,
+        /// Path to the output USER file
+        #[clap(short, long)]
+        output: String,
+        /// Version of the game, for the CRC of each type. Defaults to the latest one
+        #[clap(short, long)]
+        version: Option<u32>,
+        /// The original USER file, providing the CRCs, resource list and referenced USER files
+        #[clap(short, long)]
+        template: Option<String>,
+    },
+
+    /// Compare the RSZ data of a USER, PFB or SCN file between two versions of the game
+    DiffUser {
+        /// Path to the PAK files of the old version
+        #[clap(long)]
+        old: Vec<String>,
+        /// Path to the PAK files of the new version
+        #[clap(long)]
+        new: Vec<String>,
+        /// Path of the file in the PAK, such as data/Define/Quest/System/QuestData/NormalQuestData.user
+        #[clap(short, long)]
+        file: String,
+        /// Field matching array elements between versions, tried before `id` and fields ending with `_id` or `_no`
+        #[clap(short, long)]
+        key: Vec<String>,
+        /// Print the differences as JSON
+        #[clap(short, long)]
+        json: bool,
+    },
+
+    /// Compare the types of two TDBs: added and removed types,
+    /// and changed fields, method signatures and constants
+    DiffTdb {
+        /// Path to the old TDB, a binary that contains one, or a full minidump (DMP file)
+        #[clap(long)]
+        old: String,
+        /// Path to the new TDB, a binary that contains one, or a full minidump (DMP file)
+        #[clap(long)]
+        new: String,
+        /// Print the differences as JSON
+        #[clap(short, long)]
+        json: bool,
     },
 
     /// Find TDB in the a full minidump (DMP file) and print the converted TDB
@@ -383,6 +696,35 @@ enum Mhrice {
         name: String,
     },
 
+    /// Find game objects in a scene and print their world transform
+    SceneQuery {
+        /// Path to the PAK file
+        #[clap(short, long)]
+        pak: Vec<String>,
+        /// The name of the root SCN file
+        #[clap(short, long)]
+        name: String,
+        #[clap(flatten)]
+        options: SceneQueryOptions,
+        #[clap(long, value_enum, default_value_t = QueryFormat::Json)]
+        format: QueryFormat,
+    },
+
+    /// Edit a game object of a SCN or PFB file, and write the file back
+    EditScene {
+        /// Path to the SCN or PFB file
+        #[clap(short, long)]
+        input: String,
+        /// Path to the output file
+        #[clap(short, long)]
+        output: String,
+        /// Version of the game, for the CRC of types not in the file. Defaults to the latest one
+        #[clap(short, long)]
+        version: Option<u32>,
+        #[clap(flatten)]
+        options: SceneEditOptions,
+    },
+
     /// Print runtime information of a type
     TypeInfo {
         /// Path to the full minidump (DMP file)
@@ -414,7 +756,7 @@ enum Mhrice {
     },
 }
 
-fn open_pak_files(mut pak: Vec<String>) -> Result<Vec<File>> {
+fn list_pak_files(mut pak: Vec<String>) -> Result<Vec<String>> {
     if pak.len() == 1 && Path::new(&pak[0]).is_dir() {
         eprintln!("Listing all PAK files in the folder...");
         let dir = pak.pop().unwrap();
@@ -436,23 +778,53 @@ fn open_pak_files(mut pak: Vec<String>) -> Result<Vec<File>> {
             eprintln!("Found PAK file: {path}");
         }
     }
+    Ok(pak)
+}
 
-    pak.into_iter().map(|path| Ok(File::open(path)?)).collect()
+fn open_pak_files(pak: Vec<String>) -> Result<Vec<File>> {
+    list_pak_files(pak)?
+        .into_iter()
+        .map(|path| Ok(File::open(path)?))
+        .collect()
+}
+
+fn read_path_list(list: String) -> Result<Vec<String>> {
+    let list = File::open(list)?;
+    let mut origin_paths = BufReader::new(list)
+        .lines()
+        .map(|line| {
+            let line = line?;
+            let origin_path = line.split(" $ ").next().context("Empty line")?;
+            Ok(origin_path
+                .strip_prefix('@')
+                .unwrap_or(origin_path)
+                .to_owned())
+        })
+        .collect::<Result<Vec<String>>>()?;
+    origin_paths.sort();
+    origin_paths.dedup();
+    Ok(origin_paths)
 }
 
 fn dump(pak: Vec<String>, name: String, output: String) -> Result<()> {
     let mut pak = PakReader::new(open_pak_files(pak)?)?;
     let index = pak.find_file(&name).context("Cannot find subfile")?;
     println!("Index {index:?}");
-    let content = pak.read_file(index)?;
-    std::fs::write(output, content)?;
+    pak.copy_file_to(index, File::create(output)?)?;
     Ok(())
 }
 
 fn dump_index(pak: Vec<String>, version: usize, index: usize, output: String) -> Result<()> {
     let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let content = pak.read_file_at(version, index)?;
-    std::fs::write(output, content)?;
+    let index = pak.index_at(version, index)?;
+    pak.copy_file_to(index, File::create(output)?)?;
+    Ok(())
+}
+
+fn pack_pak(input: String, output: String, compression: PakCompression) -> Result<()> {
+    let mut writer = PakWriter::new();
+    writer.add_dir(input, compression)?;
+    writer.write(std::io::BufWriter::new(File::create(output)?))?;
     Ok(())
 }
 
@@ -483,52 +855,313 @@ fn visit_tree(nodes: &mut [TreeNode], current: usize, depth: i32) {
     nodes[current].visited = true;
 }*/
 
-fn scan_rsz(pak: Vec<String>, print_all: bool) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
+#[derive(Default)]
+struct RoundTripReport {
+    identical: usize,
+    undeserializable: usize,
+    failures: Vec<(PakFileIndex, String)>,
+}
 
-    let mut crc_mismatches = BTreeMap::new();
+impl RoundTripReport {
+    fn merge(mut self, other: RoundTripReport) -> RoundTripReport {
+        self.identical += other.identical;
+        self.undeserializable += other.undeserializable;
+        self.failures.extend(other.failures);
+        self
+    }
+}
 
-    for index in pak.all_file_indexs() {
-        let content = pak
-            .read_file(index)
-            .context(format!("Failed to open file at {index:?}"))?;
-        if content.len() < 4 {
-            continue;
+/// Deserializes the USER file and writes it back as write-user would without a template,
+/// expecting identical bytes
+fn round_trip_user(
+    content: &[u8],
+    index: PakFileIndex,
+    version: Option<u32>,
+    report: &mut RoundTripReport,
+) {
+    let user = match User::new(Cursor::new(content)) {
+        Ok(user) => user,
+        Err(e) => {
+            report.failures.push((index, format!("{e:#}")));
+            return;
         }
-
-        if &content[0..3] == b"USR" {
-            User::new(Cursor::new(&content))
-                .context(format!("Failed to open USER at {index:?}"))?
-                .rsz
-                .verify_crc(&mut crc_mismatches, print_all);
-        } else if &content[0..3] == b"PFB" {
-            Pfb::new(Cursor::new(&content))
-                .context(format!("Failed to open PFB at {index:?}"))?
-                .rsz
-                .verify_crc(&mut crc_mismatches, print_all);
-        } else if &content[0..3] == b"SCN" {
-            Scn::new(Cursor::new(&content))
-                .context(format!("Failed to open SCN at {index:?}"))?
-                .rsz
-                .verify_crc(&mut crc_mismatches, print_all);
-        } else if &content[0..4] == b"RCOL" {
-            Rcol::new(Cursor::new(&content), false)
-                .context(format!("Failed to open RCOL at {index:?}"))?
-                .rsz
-                .verify_crc(&mut crc_mismatches, print_all);
+    };
+    let Ok(nodes) = user.rsz.deserialize(version) else {
+        report.undeserializable += 1;
+        return;
+    };
+    let result = (|| -> Result<()> {
+        let rsz = rsz::Rsz::serialize(&nodes, version.unwrap_or(u32::MAX), &HashMap::new())?;
+        let mut output = vec![];
+        User {
+            resource_names: user.resource_names,
+            children: UserChild::from_extern_slots(&rsz),
+            rsz,
         }
+        .write(&mut output)?;
+        if let Some(pos) = output.iter().zip(content).position(|(a, b)| a != b) {
+            bail!("Output differs at {pos:08X}")
+        }
+        if output.len() != content.len() {
+            bail!(
+                "Output size {:08X} differs from {:08X}",
+                output.len(),
+                content.len()
+            )
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => report.identical += 1,
+        Err(e) => report.failures.push((index, format!("{e:#}"))),
     }
+}
+
+fn scan_rsz(
+    pak: Vec<String>,
+    print_all: bool,
+    round_trip: bool,
+    version: Option<u32>,
+    report: Option<String>,
+) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let write_report = report.is_some();
+
+    let (crc_mismatches, crc_report, round_trip_report) = pak
+        .all_file_indexs()
+        .into_par_iter()
+        .map(
+            |index| -> Result<(BTreeMap<&str, u32>, rsz::CrcReport, RoundTripReport)> {
+                let mut crc_mismatches = BTreeMap::new();
+                let mut crc_report = rsz::CrcReport::default();
+                let mut round_trip_report = RoundTripReport::default();
+                let content = pak
+                    .read_file(index)
+                    .context(format!("Failed to open file at {index:?}"))?;
+                if content.len() < 4 {
+                    return Ok((crc_mismatches, crc_report, round_trip_report));
+                }
+
+                let rsz = if &content[0..3] == b"USR" {
+                    if round_trip {
+                        round_trip_user(&content, index, version, &mut round_trip_report);
+                    }
+                    User::new(Cursor::new(&content))
+                        .context(format!("Failed to open USER at {index:?}"))?
+                        .rsz
+                } else if &content[0..3] == b"PFB" {
+                    Pfb::new(Cursor::new(&content))
+                        .context(format!("Failed to open PFB at {index:?}"))?
+                        .rsz
+                } else if &content[0..3] == b"SCN" {
+                    Scn::new(Cursor::new(&content))
+                        .context(format!("Failed to open SCN at {index:?}"))?
+                        .rsz
+                } else if &content[0..4] == b"RCOL" {
+                    Rcol::new(Cursor::new(&content), false)
+                        .context(format!("Failed to open RCOL at {index:?}"))?
+                        .rsz
+                } else {
+                    return Ok((crc_mismatches, crc_report, round_trip_report));
+                };
+                rsz.verify_crc(&mut crc_mismatches, print_all);
+                if write_report {
+                    crc_report.add(&rsz, &format!("{:016X}", pak.path_hash_of(index)));
+                }
+                Ok((crc_mismatches, crc_report, round_trip_report))
+            },
+        )
+        .try_reduce(
+            || {
+                (
+                    BTreeMap::new(),
+                    rsz::CrcReport::default(),
+                    RoundTripReport::default(),
+                )
+            },
+            |mut a, b| {
+                a.0.extend(b.0);
+                let crc_report = if write_report { a.1.merge(b.1) } else { a.1 };
+                Ok((a.0, crc_report, a.2.merge(b.2)))
+            },
+        )?;
 
     for (symbol, crc) in crc_mismatches {
         println!("Mismatch CRC {crc:08X} for {symbol}")
     }
 
+    if let Some(report) = report {
+        serde_json::to_writer_pretty(File::create(report)?, &crc_report)?;
+    }
+
+    if round_trip {
+        let mut failures = round_trip_report.failures;
+        failures.sort();
+        for (index, error) in &failures {
+            println!("Round trip failed at {}: {error}", index.short_string());
+        }
+        println!(
+            "Round trip: {} identical, {} failed, {} not deserializable",
+            round_trip_report.identical,
+            failures.len(),
+            round_trip_report.undeserializable
+        );
+    }
+
+    Ok(())
+}
+
+/// Outcome of reading a mismatched CRC as the candidate version
+#[derive(Default)]
+struct CrcRecheck {
+    passed: usize,
+    /// Files failing because of this type, with the reason
+    failures: Vec<(String, String)>,
+    /// Files failing with other new CRCs that can't be ruled out, with the reason
+    undetermined: Vec<(String, String)>,
+}
+
+fn recheck_rsz(pak: Vec<String>, report: String, version: Option<u32>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let report: rsz::CrcReport = serde_json::from_reader(File::open(report)?)?;
+    let candidates: HashMap<(u32, u32), u32> = report
+        .mismatches
+        .iter()
+        .map(|m| ((m.hash, m.crc), version.unwrap_or(m.latest_version)))
+        .collect();
+    rsz::install_crc_candidates(candidates.clone())?;
+
+    // The mismatched types listed for each file
+    let mut files: BTreeMap<&str, Vec<(u32, u32)>> = BTreeMap::new();
+    for mismatch in &report.mismatches {
+        for file in &mismatch.files {
+            files
+                .entry(file)
+                .or_default()
+                .push((mismatch.hash, mismatch.crc));
+        }
+    }
+    let files: Vec<(&str, Vec<(u32, u32)>)> = files.into_iter().collect();
+
+    // Whether each file is read without any error
+    let results = files
+        .par_iter()
+        .map(|(file, types)| -> Result<Result<(), String>> {
+            let hash = u64::from_str_radix(file, 16)
+                .with_context(|| format!("Bad path hash {file} in the report"))?;
+            let index = pak
+                .find_path_hash(hash)
+                .context("The report is not for these PAK files")?;
+            let content = pak.read_file(index)?;
+            let rsz = FileKind::detect(&content)
+                .context("Unknown file format")?
+                .parse_rsz(&content)?
+                .context("File without RSZ")?;
+            for &(hash, crc) in types {
+                if !rsz
+                    .type_descriptors
+                    .iter()
+                    .any(|td| td.hash == hash && td.crc == crc)
+                {
+                    bail!("The report is not for these PAK files: {file} has no type {hash:08X} with CRC {crc:08X}")
+                }
+            }
+            Ok(rsz.deserialize(None).map(|_| ()).map_err(|e| format!("{e:#}")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // A file read without error clears all its new CRCs. A failing one is blamed on its only
+    // new CRC, or on the only one not cleared by other files. Otherwise it is undetermined.
+    let mut rechecks: HashMap<(u32, u32), CrcRecheck> = HashMap::new();
+    for ((_, types), result) in files.iter().zip(&results) {
+        if result.is_ok() {
+            for key in types {
+                rechecks.entry(*key).or_default().passed += 1;
+            }
+        }
+    }
+    for ((file, types), result) in files.iter().zip(&results) {
+        let Err(reason) = result else {
+            continue;
+        };
+        let suspects: Vec<(u32, u32)> = match types[..] {
+            [key] => vec![key],
+            _ => types
+                .iter()
+                .copied()
+                .filter(|key| rechecks.get(key).map_or(0, |r| r.passed) == 0)
+                .collect(),
+        };
+        let failure = (file.to_string(), reason.clone());
+        match suspects[..] {
+            [key] => rechecks.entry(key).or_default().failures.push(failure),
+            _ => {
+                for key in types {
+                    rechecks
+                        .entry(*key)
+                        .or_default()
+                        .undetermined
+                        .push(failure.clone())
+                }
+            }
+        }
+    }
+
+    for mismatch in &report.mismatches {
+        let version = candidates[&(mismatch.hash, mismatch.crc)];
+        let recheck = rechecks
+            .remove(&(mismatch.hash, mismatch.crc))
+            .unwrap_or_default();
+        let total = recheck.passed + recheck.failures.len() + recheck.undetermined.len();
+        if let Some((file, reason)) = recheck.failures.first() {
+            println!(
+                "Still failing {} CRC {:08X} as version {} in {} of {} files, such as {}: {}",
+                mismatch.symbol,
+                mismatch.crc,
+                version,
+                recheck.failures.len(),
+                total,
+                file,
+                reason
+            );
+        } else if let Some((file, reason)) = recheck.undetermined.first() {
+            println!(
+                "Undetermined {} CRC {:08X} as version {}: {} of {} files fail along with other new CRCs, such as {}: {}",
+                mismatch.symbol,
+                mismatch.crc,
+                version,
+                recheck.undetermined.len(),
+                total,
+                file,
+                reason
+            );
+        } else {
+            println!(
+                "Layout unchanged for {} in {} files: 0x{:08X} = {},",
+                mismatch.symbol, recheck.passed, mismatch.crc, version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn gen_rsz(name: String, tdb: Option<String>, version: u32, output: Option<String>) -> Result<()> {
+    let enums = match tdb {
+        Some(tdb) => rsz::load_tdb_enums(tdb)?,
+        None => HashMap::new(),
+    };
+    let source = rsz::generate_rsz_source(&name, rsz::RszSchema::installed(), &enums, version)?;
+    match output {
+        Some(output) => std::fs::write(output, source)?,
+        None => print!("{source}"),
+    }
     Ok(())
 }
 
 fn gen_json(pak: Vec<String>) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let pedia = extract::gen_pedia(&mut pak)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let pedia = extract::gen_pedia(&pak)?;
     let json = serde_json::to_string_pretty(&pedia)?;
     println!("{json}");
     Ok(())
@@ -539,14 +1172,14 @@ fn gen_website_to_sink(
     sink: impl Sink,
     config: extract::WebsiteConfig,
 ) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let pedia = extract::gen_pedia(&mut pak)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let pedia = extract::gen_pedia(&pak)?;
     let pedia_ex = extract::gen_pedia_ex(&pedia)?;
     sink.create("mhrice.json")?
         .write_all(serde_json::to_string_pretty(&pedia)?.as_bytes())?;
     let mut hash_store = HashStore::new();
     extract::gen_website(&mut hash_store, &pedia, &pedia_ex, &config, &sink)?;
-    extract::gen_resources(&mut pak, &sink.sub_sink("resources")?)?;
+    extract::gen_resources(&pak, &sink.sub_sink("resources")?)?;
     sink.finalize()?;
     Ok(())
 }
@@ -602,8 +1235,9 @@ impl<F: Seek> Seek for OffsetFile<F> {
     }
 }
 
-fn read_tdb(tdb: String, options: TdbOptions) -> Result<()> {
-    let mut file = BufReader::new(File::open(tdb)?);
+/// Opens the file from the start of the TDB it contains
+fn open_tdb_file(path: &str) -> Result<OffsetFile<BufReader<File>>> {
+    let mut file = BufReader::new(File::open(path)?);
     let offset = loop {
         let mut magic = vec![0; TDB_ANCHOR.len()];
         file.read_exact(&mut magic)?;
@@ -613,8 +1247,11 @@ fn read_tdb(tdb: String, options: TdbOptions) -> Result<()> {
             file.seek(SeekFrom::Current(-(TDB_ANCHOR.len() as i64) + 1))?;
         }
     };
+    OffsetFile::new(file, offset)
+}
 
-    tdb::print(OffsetFile::new(file, offset)?, 0, options)?;
+fn read_tdb(tdb: String, options: TdbOptions) -> Result<()> {
+    tdb::print(open_tdb_file(&tdb)?, 0, options)?;
     Ok(())
 }
 
@@ -687,22 +1324,55 @@ fn read_dmp_tdb(dmp: String, address: Option<String>, options: TdbOptions) -> Re
         return Ok(());
     }
 
+    if let Some(base) = find_dmp_tdb(&memory)? {
+        eprintln!("Found at address 0x{base:016X}");
+        let file = MinidumpReader::new(&memory);
+
+        tdb::print(file, base, options)?;
+    }
+
+    Ok(())
+}
+
+/// Address of the first TDB found in the memory of a minidump
+fn find_dmp_tdb(memory: &MinidumpMemory64List) -> Result<Option<u64>> {
     for block in memory.iter() {
         if let Some(pos) = block
             .bytes
             .windows(TDB_ANCHOR.len())
             .position(|w| w == TDB_ANCHOR)
         {
-            let base = block.base_address + u64::try_from(pos)?;
-            eprintln!("Found at address 0x{base:016X}");
-            let file = MinidumpReader::new(&memory);
-
-            tdb::print(file, base, options)?;
-
-            break;
+            return Ok(Some(block.base_address + u64::try_from(pos)?));
         }
     }
+    Ok(None)
+}
+
+/// Reads the TDB of a full minidump, or of any other file containing one
+fn load_tdb(path: &str) -> Result<tdb::Tdb> {
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    if &magic != b"MDMP" {
+        return tdb::Tdb::new(open_tdb_file(path)?, 0);
+    }
+    let dmp = Minidump::read_path(path).map_err(|e| anyhow!(e))?;
+    let memory = dmp
+        .get_stream::<MinidumpMemory64List>()
+        .map_err(|e| anyhow!(e))
+        .context("No full dump memory found")?;
+    let base = find_dmp_tdb(&memory)?.context("No TDB found in the minidump")?;
+    tdb::Tdb::new(MinidumpReader::new(&memory), base)
+}
 
+fn diff_tdb(old: String, new: String, json: bool) -> Result<()> {
+    let old = load_tdb(&old).context("Failed to read the old TDB")?;
+    let new = load_tdb(&new).context("Failed to read the new TDB")?;
+    let diff = tdb::TdbDiff::new(&old, &new);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        print!("{diff}");
+    }
     Ok(())
 }
 
@@ -788,80 +1458,288 @@ fn scan_msg(pak: Vec<String>, output: String) -> Result<()> {
 }
 
 fn grep_msg(pak: Vec<String>, pattern: String) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
     use regex::*;
     let regex = RegexBuilder::new(&pattern).build()?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if file.len() < 8 || file[4..8] != b"GMSG"[..] {
-            continue;
-        }
-        let msg = Msg::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
-        for entry in &msg.entries {
-            for text in &entry.content {
-                if regex.is_match(text) {
-                    println!("Found @ {i:?}");
+    let mut found = pak
+        .all_file_indexs()
+        .into_par_iter()
+        .map(|i| -> Result<Vec<PakFileIndex>> {
+            let file = pak.read_file(i)?;
+            if file.len() < 8 || file[4..8] != b"GMSG"[..] {
+                return Ok(vec![]);
+            }
+            let msg = Msg::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+            let mut found = vec![];
+            for entry in &msg.entries {
+                for text in &entry.content {
+                    if regex.is_match(text) {
+                        found.push(i);
+                    }
                 }
             }
-        }
+            Ok(found)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    found.sort();
+    for i in found.into_iter().flatten() {
+        println!("Found @ {i:?}");
     }
     Ok(())
 }
 
-fn scan_mesh(pak: Vec<String>) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if file.len() < 4 || file[0..4] != b"MESH"[..] {
-            continue;
+/// Converts the file content to `output` plus an extension based on the format.
+/// Returns `None` if the format is not recognized.
+fn convert_file(data: &[u8], output: &Path, mesh_format: MeshFormat) -> Result<Option<FileKind>> {
+    let Some(kind) = FileKind::detect(data) else {
+        return Ok(None);
+    };
+    let with_extension = |extension: &str| {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    };
+    let file = Cursor::new(data);
+    let rsz = match kind {
+        FileKind::User => Some(User::new(file)?.rsz),
+        FileKind::Pfb => Some(Pfb::new(file)?.rsz),
+        FileKind::Scn => Some(Scn::new(file)?.rsz),
+        FileKind::Rcol => Some(Rcol::new(file, false)?.rsz),
+        FileKind::Msg => {
+            let msg = Msg::new(file)?;
+            std::fs::write(with_extension("json"), serde_json::to_string_pretty(&msg)?)?;
+            None
         }
-        let _ = Mesh::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
-    }
-    Ok(())
-}
+        FileKind::Gui => {
+            let gui = Gui::new(file)?;
+            std::fs::write(with_extension("json"), serde_json::to_string_pretty(&gui)?)?;
+            None
+        }
+        FileKind::Uvs => {
+            let uvs = Uvs::new(file)?;
+            std::fs::write(with_extension("json"), serde_json::to_string_pretty(&uvs)?)?;
+            None
+        }
+        FileKind::Mesh => {
+            let mesh = Mesh::new(file)?;
+            let path = match mesh_format {
+                MeshFormat::Obj => with_extension("obj"),
+                MeshFormat::Dae => with_extension("dae"),
+            };
+            let path = path.to_str().context("Bad path")?.to_owned();
+            match mesh_format {
+                MeshFormat::Obj => mesh.dump(path)?,
+                MeshFormat::Dae => mesh.dump_dae(path)?,
+            }
+            None
+        }
+        FileKind::Tex => {
+            let tex = Tex::new(file)?;
+            for index in 0..tex.texture_count() {
+                for mipmap in 0..tex.mipmap_count() {
+                    let path = with_extension(&format!("{index}.{mipmap}.png"));
+                    tex.save_png(index, mipmap, File::create(path)?)?;
+                }
+            }
+            None
+        }
+    };
 
-fn scan_tex(pak: Vec<String>) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if file.len() < 4 || file[0..4] != b"TEX\0"[..] {
-            continue;
+    if let Some(rsz) = rsz {
+        // Instances of unknown types are written as their raw bytes
+        let (nodes, skip_report) = rsz.deserialize_lenient(None)?;
+        if !skip_report.is_empty() {
+            eprint!("{} is partially read:\n{skip_report}", output.display());
         }
-        let _ = Tex::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+        let nodes = rsz::AnyRsz::to_json_all(&nodes)?
+            .iter()
+            .map(|node| Ok(serde_json::from_str(node)?))
+            .collect::<Result<Vec<serde_json::Value>>>()?;
+        std::fs::write(
+            with_extension("json"),
+            serde_json::to_string_pretty(&nodes)?,
+        )?;
     }
 
-    Ok(())
+    Ok(Some(kind))
 }
 
-fn scan_gui(pak: Vec<String>) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if file.len() < 8 || file[4..8] != b"GUIR"[..] {
-            continue;
-        }
-        let _ = Gui::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+fn convert(
+    input: String,
+    pak: Vec<String>,
+    output: String,
+    recursive: bool,
+    mesh_format: MeshFormat,
+) -> Result<()> {
+    if !recursive {
+        let data = if pak.is_empty() {
+            std::fs::read(&input)?
+        } else {
+            let mut pak = PakReader::new(open_pak_files(pak)?)?;
+            let index = pak.find_file(&input)?;
+            pak.read_file(index)?
+        };
+        let kind = convert_file(&data, Path::new(&output), mesh_format)?
+            .with_context(|| format!("Unknown format {}", magic_name(&data)))?;
+        eprintln!("Converted {} file", kind.name());
+        return Ok(());
     }
 
+    if !pak.is_empty() {
+        bail!("Recursive conversion only works on a dumped folder")
+    }
+
+    let input = Path::new(&input);
+    let mut files = vec![];
+    let mut pending = vec![input.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+
+    let results = files
+        .par_iter()
+        .map(|path| -> Result<Option<FileKind>> {
+            let output = Path::new(&output).join(path.strip_prefix(input)?);
+            std::fs::create_dir_all(output.parent().context("no parent")?)?;
+            convert_file(&std::fs::read(path)?, &output, mesh_format)
+        })
+        .collect::<Vec<_>>();
+
+    let mut converted: BTreeMap<FileKind, usize> = BTreeMap::new();
+    let mut skipped = 0;
+    let mut failed = 0;
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(Some(kind)) => *converted.entry(kind).or_default() += 1,
+            Ok(None) => skipped += 1,
+            Err(e) => {
+                eprintln!("Failed to convert {}: {e:#}", path.display());
+                failed += 1;
+            }
+        }
+    }
+    for (kind, count) in converted {
+        println!("{}: {count}", kind.name());
+    }
+    println!("Skipped unknown formats: {skipped}");
+    if failed != 0 {
+        bail!("{failed} files failed to convert")
+    }
     Ok(())
 }
 
-fn scan_uvs(pak: Vec<String>) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if file.len() < 4 || file[0..4] != b".SVU"[..] {
-            continue;
-        }
-        let _ = Uvs::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+fn census(pak: Vec<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let census = pak
+        .all_file_indexs()
+        .into_par_iter()
+        .fold(Census::default, |mut census, index| {
+            match pak.read_file(index) {
+                Ok(data) => census.add(&data),
+                Err(_) => census.unreadable += 1,
+            }
+            census
+        })
+        .reduce(Census::default, Census::merge);
+
+    println!("format\tcount\tsize\tparsed\tdeserialized");
+    for (kind, stat) in &census.known {
+        let percent = |n: usize| n as f64 / stat.count as f64 * 100.0;
+        let deserialized = if kind.has_rsz() {
+            format!("{:.2}%", percent(stat.deserialized))
+        } else {
+            "-".to_owned()
+        };
+        println!(
+            "{}\t{}\t{}\t{:.2}%\t{}",
+            kind.name(),
+            stat.count,
+            stat.size,
+            percent(stat.parsed),
+            deserialized
+        );
     }
 
+    let mut unknown: Vec<_> = census.unknown.iter().collect();
+    unknown.sort_by_key(|(_, stat)| std::cmp::Reverse(stat.count));
+    println!("Unknown magics:");
+    for (magic, stat) in unknown {
+        println!("{magic}\t{}\t{}", stat.count, stat.size);
+    }
+    if census.unreadable != 0 {
+        println!("Unreadable entries: {}", census.unreadable);
+    }
     Ok(())
 }
 
+fn scan_mesh(pak: Vec<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    pak.all_file_indexs()
+        .into_par_iter()
+        .try_for_each(|i| -> Result<()> {
+            let file = pak.read_file(i)?;
+            if file.len() < 4 || file[0..4] != b"MESH"[..] {
+                return Ok(());
+            }
+            let _ = Mesh::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+            Ok(())
+        })
+}
+
+fn scan_tex(pak: Vec<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    pak.all_file_indexs()
+        .into_par_iter()
+        .try_for_each(|i| -> Result<()> {
+            let file = pak.read_file(i)?;
+            if file.len() < 4 || file[0..4] != b"TEX\0"[..] {
+                return Ok(());
+            }
+            let _ = Tex::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+            Ok(())
+        })
+}
+
+fn scan_gui(pak: Vec<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    pak.all_file_indexs()
+        .into_par_iter()
+        .try_for_each(|i| -> Result<()> {
+            let file = pak.read_file(i)?;
+            if file.len() < 8 || file[4..8] != b"GUIR"[..] {
+                return Ok(());
+            }
+            let _ = Gui::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+            Ok(())
+        })
+}
+
+fn scan_uvs(pak: Vec<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    pak.all_file_indexs()
+        .into_par_iter()
+        .try_for_each(|i| -> Result<()> {
+            let file = pak.read_file(i)?;
+            if file.len() < 4 || file[0..4] != b".SVU"[..] {
+                return Ok(());
+            }
+            let _ = Uvs::new(Cursor::new(&file)).context(format!("at {i:?}"))?;
+            Ok(())
+        })
+}
+
 fn grep(pak: Vec<String>, utf16: bool, mut pattern: String) -> Result<()> {
     use regex::bytes::*;
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
     if utf16 {
         pattern = pattern
             .encode_utf16()
@@ -873,18 +1751,24 @@ fn grep(pak: Vec<String>, utf16: bool, mut pattern: String) -> Result<()> {
     }
     println!("Searching for patterns \"{}\"", &pattern);
     let re = RegexBuilder::new(&pattern).unicode(false).build()?;
-    for i in pak.all_file_indexs() {
-        let file = pak.read_file(i)?;
-        if re.is_match(&file) {
-            println!("Matched @ {i:?}");
-        }
+    let mut matched = pak
+        .all_file_indexs()
+        .into_par_iter()
+        .filter_map(|i| match pak.read_file(i) {
+            Ok(file) => re.is_match(&file).then_some(Ok(i)),
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    matched.sort();
+    for i in matched {
+        println!("Matched @ {i:?}");
     }
     Ok(())
 }
 
 fn search_path(pak: Vec<String>, dmp: Vec<String>) -> Result<()> {
-    let pak = Mutex::new(PakReader::new(open_pak_files(pak)?)?);
-    let indexs = pak.lock().unwrap().all_file_indexs();
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let indexs = pak.all_file_indexs();
     let counter = std::sync::atomic::AtomicU32::new(0);
 
     let mut paths: Vec<(String, Vec<I18nPakFileIndex>)> = vec![];
@@ -934,7 +1818,7 @@ fn search_path(pak: Vec<String>, dmp: Vec<String>) -> Result<()> {
                 for pos in (begin..end).step_by(2) {
                     path.push(char::from(memory[pos]));
                 }
-                let index = pak.lock().unwrap().find_file_i18n(&path)?;
+                let index = pak.find_file_i18n(&path)?;
                 paths.push((path, index));
             }
         }
@@ -995,7 +1879,7 @@ fn search_path(pak: Vec<String>, dmp: Vec<String>) -> Result<()> {
         indexs
             .into_par_iter()
             .map(|index| {
-                let file = pak.lock().unwrap().read_file(index)?;
+                let file = pak.read_file(index)?;
                 search_memory(&file)
             })
             .flat_map_iter(|paths: Result<_>| paths.unwrap()),
@@ -1011,66 +1895,206 @@ fn search_path(pak: Vec<String>, dmp: Vec<String>) -> Result<()> {
     Ok(())
 }
 
+fn discover_path(pak: Vec<String>, list: Option<String>) -> Result<()> {
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let known = list.map(read_path_list).transpose()?.unwrap_or_default();
+    let discovery = discover::discover_paths(&pak, &known)?;
+
+    for (path, index) in &discovery.found {
+        println!("{path} $ {}", index.short_string());
+    }
+    // Keep stdout usable as a path list
+    eprintln!("Unnamed entries:");
+    for index in &discovery.unnamed {
+        eprintln!("{}", index.short_string());
+    }
+    eprintln!(
+        "Found {} new paths in {} rounds. {} entries remain unnamed. {} entries failed to parse",
+        discovery.found.len(),
+        discovery.rounds,
+        discovery.unnamed.len(),
+        discovery.failed
+    );
+    Ok(())
+}
+
 fn dump_tree(pak: Vec<String>, list: String, output: String) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let list = File::open(list)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let origin_paths = read_path_list(list)?;
+
+    let visited = origin_paths
+        .par_iter()
+        .map(|origin_path| -> Result<Vec<PakFileIndex>> {
+            let mut visited = vec![];
+            let streaming_path = "streaming/".to_owned() + origin_path;
+            let paths = [origin_path, &streaming_path];
+
+            for path in paths {
+                for i18n_index in pak.find_file_i18n(path)? {
+                    let index = i18n_index.index;
+                    let path_i18n = if i18n_index.language.is_empty() {
+                        path.to_owned()
+                    } else {
+                        format!("{}.{}", path, i18n_index.language)
+                    };
+
+                    let mut path = PathBuf::from(&output);
+                    for component in path_i18n.split('/') {
+                        path.push(component);
+                    }
+
+                    std::fs::create_dir_all(path.parent().context("no parent")?)?;
+                    pak.copy_file_to(index, File::create(path)?)?;
+                    visited.push(index);
+                }
+            }
+            Ok(visited)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     let mut unvisited: std::collections::HashSet<_> = pak.all_file_indexs().into_iter().collect();
-    for line in BufReader::new(list).lines() {
-        let line = line?;
-        let mut origin_path = line.split(" $ ").next().context("Empty line")?;
-        if let Some(new_path) = origin_path.strip_prefix('@') {
-            origin_path = new_path;
-        }
+    for index in visited.into_iter().flatten() {
+        unvisited.remove(&index);
+    }
 
-        let streaming_path = "streaming/".to_owned() + origin_path;
-        let paths = [origin_path, &streaming_path];
+    unvisited
+        .into_par_iter()
+        .try_for_each(|index| -> Result<()> {
+            let mut reader = pak.open_file(index)?;
+            let mut magic = vec![];
+            reader.by_ref().take(4).read_to_end(&mut magic)?;
+            let format = magic_name(&magic);
+
+            let mut path = PathBuf::from(&output);
+            path.push("_unknown");
+            path.push(&format);
+            std::fs::create_dir_all(&path)?;
+            path.push(index.short_string());
+            let mut file = File::create(path)?;
+            file.write_all(&magic)?;
+            std::io::copy(&mut reader, &mut file)?;
+            Ok(())
+        })
+}
 
-        for path in paths {
-            for i18n_index in pak.find_file_i18n(path)? {
-                let index = i18n_index.index;
-                let path_i18n = if i18n_index.language.is_empty() {
-                    path.to_owned()
-                } else {
-                    format!("{}.{}", path, i18n_index.language)
-                };
+fn ls(pak: Vec<String>, list: String) -> Result<()> {
+    let pak_paths = list_pak_files(pak)?;
+    let pak = PakReader::new(open_pak_files(pak_paths.clone())?)?;
+    let listing = pak.list_files(&read_path_list(list)?)?;
+
+    let print_info = |name: &str, info: &PakEntryInfo| {
+        println!(
+            "{name}\t{}\t{}\t{}\t{}\t{}",
+            pak_paths[info.index.version()],
+            info.len_compressed,
+            info.len,
+            info.format_name(),
+            if info.encryption != 0 {
+                "encrypted"
+            } else {
+                "plain"
+            }
+        )
+    };
 
-                let mut path = PathBuf::from(&output);
-                for component in path_i18n.split('/') {
-                    path.push(component);
-                }
+    for (name, info) in &listing.named {
+        print_info(name, info);
+    }
+
+    println!();
+    println!("Unnamed entries:");
+    for info in &listing.unnamed {
+        print_info(&info.index.short_string(), info);
+    }
+
+    println!();
+    println!(
+        "Named {} of {} entries ({:.2}%)",
+        listing.named.len(),
+        listing.named.len() + listing.unnamed.len(),
+        listing.coverage()
+    );
+
+    Ok(())
+}
+
+fn overrides(pak: Vec<String>, list: Option<String>) -> Result<()> {
+    let pak_paths = list_pak_files(pak)?;
+    let pak = SyncPakReader::new(open_pak_files(pak_paths.clone())?)?;
+    let names: HashMap<PakFileIndex, String> = if let Some(list) = list {
+        pak.list_files(&read_path_list(list)?)?
+            .named
+            .into_iter()
+            .map(|(name, info)| (info.index, name))
+            .collect()
+    } else {
+        HashMap::new()
+    };
 
-                std::fs::create_dir_all(path.parent().context("no parent")?)?;
-                std::fs::write(path, pak.read_file(index)?)?;
-                unvisited.remove(&index);
+    let overrides = pak.overrides()?;
+    let mut replaced = vec![(0, 0); pak_paths.len()];
+    for o in &overrides {
+        let winner = o.winner();
+        if let Some(name) = names.get(&winner) {
+            println!("{name} [{:016X}]", o.hash);
+        } else {
+            println!("[{:016X}]", o.hash);
+        }
+        for layer in &o.layers {
+            let mark = if layer.index == winner { "*" } else { " " };
+            println!(
+                "  {mark} {} {} (size {})",
+                layer.index.short_string(),
+                pak_paths[layer.index.version()],
+                layer.len
+            );
+        }
+        println!(
+            "  {}",
+            if o.content_differs {
+                "changed"
+            } else {
+                "identical"
             }
+        );
+
+        let count = &mut replaced[winner.version()];
+        count.0 += 1;
+        if o.content_differs {
+            count.1 += 1;
         }
     }
 
-    for index in unvisited {
-        let data = pak.read_file(index)?;
-        let format = if let Some(magic) = data.get(0..4) {
-            let mut format = String::new();
-            for c in magic {
-                if c.is_ascii_alphanumeric() {
-                    format.push(*c as char);
-                } else {
-                    use std::fmt::Write as _;
-                    write!(format, "_{c:02x}")?;
-                }
-            }
-            format
-        } else {
-            "short".to_owned()
-        };
+    println!();
+    for (path, (count, changed)) in pak_paths.iter().zip(replaced) {
+        println!("{path}: replaced {count} entries, {changed} of them changed");
+    }
+
+    Ok(())
+}
+
+fn verify_pak(pak: Vec<String>) -> Result<()> {
+    let pak_paths = list_pak_files(pak)?;
+    let pak = SyncPakReader::new(open_pak_files(pak_paths.clone())?)?;
+    let report = pak.verify()?;
 
-        let mut path = PathBuf::from(&output);
-        path.push("_unknown");
-        path.push(&format);
-        std::fs::create_dir_all(&path)?;
-        path.push(index.short_string());
-        std::fs::write(path, &data)?;
+    println!("Entries: {}", report.entry_count);
+    println!("Formats:");
+    for (&format, count) in &report.format_counts {
+        println!("  {}: {count}", format_name(format));
+    }
+    println!("Encryptions:");
+    for (&encryption, count) in &report.encryption_counts {
+        println!("  {}: {count}", encryption_name(encryption));
+    }
+    println!("Failures: {}", report.failures.len());
+    for (index, error) in &report.failures {
+        println!("  {index:?} ({}): {error}", pak_paths[index.version()]);
     }
 
+    if !report.failures.is_empty() {
+        bail!("{} entries failed verification", report.failures.len())
+    }
     Ok(())
 }
 
@@ -1190,10 +2214,10 @@ fn gen_meat(pak: Vec<String>, index: u32, output: impl Write) -> Result<()> {
 }
 
 fn gen_resources(pak: Vec<String>, output: String) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
 
     let sink = DiskSink::init(Path::new(&output))?;
-    extract::gen_resources(&mut pak, &sink)?;
+    extract::gen_resources(&pak, &sink)?;
 
     Ok(())
 }
@@ -1206,16 +2230,112 @@ fn hash(input: String, utf16: bool) {
     }
 }
 
-fn read_user(user: String, version_hint: Option<u32>) -> Result<()> {
-    let nodes = User::new(File::open(user)?)?
+fn read_user(
+    user: String,
+    version_hint: Option<u32>,
+    resolve_externs: bool,
+    pak: Vec<String>,
+) -> Result<()> {
+    let mut nodes = User::new(File::open(user)?)?
         .rsz
         .deserialize(version_hint)?;
+    if resolve_externs {
+        if pak.is_empty() {
+            bail!("--resolve-externs needs the PAK file")
+        }
+        let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+        let mut resolver = rsz::ExternResolver::new(&pak, version_hint);
+        for node in &mut nodes {
+            resolver.resolve_any(node)?;
+        }
+    }
     for node in nodes {
         println!("{}", node.to_json()?);
     }
     Ok(())
 }
 
+fn write_user(
+    json: String,
+    output: String,
+    version: Option<u32>,
+    template: Option<String>,
+) -> Result<()> {
+    let version = version.unwrap_or(u32::MAX);
+    let template = template.map(|t| User::new(File::open(t)?)).transpose()?;
+    let mut crc_hint = HashMap::new();
+    let mut extern_hint = HashMap::new();
+    if let Some(template) = &template {
+        let rsz = &template.rsz;
+        crc_hint.extend(rsz.type_descriptors.iter().map(|td| (td.hash, td.crc)));
+        for (&index, slot) in &rsz.extern_slots {
+            let crc = rsz.type_descriptors[usize::try_from(index)?].crc;
+            extern_hint.insert(slot.path.clone(), (slot.hash, crc));
+        }
+    }
+
+    let json = std::fs::read_to_string(json)?;
+    let values = serde_json::Deserializer::from_str(&json)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut reader = rsz::RszJsonReader::new(version, &crc_hint, &extern_hint);
+    let roots = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let name = if values.len() == 1 {
+                "$".to_owned()
+            } else {
+                format!("$root{i}")
+            };
+            reader.read_root(value, &name)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let rsz = rsz::Rsz::serialize(&roots, version, &crc_hint)?;
+    let (resource_names, children) = match template {
+        Some(template) => (template.resource_names, template.children),
+        None => (vec![], UserChild::from_extern_slots(&rsz)),
+    };
+    User {
+        resource_names,
+        children,
+        rsz,
+    }
+    .write(File::create(output)?)
+}
+
+fn diff_user(
+    old: Vec<String>,
+    new: Vec<String>,
+    file: String,
+    key: Vec<String>,
+    json: bool,
+) -> Result<()> {
+    // A file missing from one version is reported as a whole
+    let read_rsz = |pak: Vec<String>| -> Result<serde_json::Value> {
+        let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+        match pak.find_file_i18n(&file)?.first() {
+            Some(index) => rsz_diff::rsz_file_json(&pak.read_file(index.index)?),
+            None => Ok(serde_json::Value::Null),
+        }
+    };
+    let old = read_rsz(old).context("Failed to read the old version")?;
+    let new = read_rsz(new).context("Failed to read the new version")?;
+    if old.is_null() && new.is_null() {
+        bail!("{file} is in neither version")
+    }
+    let diff = rsz_diff::diff_json(&old, &new, &key);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        for entry in &diff {
+            println!("{entry}");
+        }
+    }
+    Ok(())
+}
+
 fn dump_scn(scn: String) -> Result<()> {
     let scn = Scn::new(File::open(scn)?)?;
     scn.dump();
@@ -1298,26 +2418,83 @@ fn scene_print_scene(scene: &Scene, level: usize) {
 }
 
 fn scene(pak: Vec<String>, name: String) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let scene = Scene::new(&mut pak, &name)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let scene = Scene::new(&pak, &name)?;
+    scene.print_rsz_reports(&name);
     scene_print_scene(&scene, 0);
     Ok(())
 }
 
+fn scene_query(
+    pak: Vec<String>,
+    name: String,
+    options: SceneQueryOptions,
+    format: QueryFormat,
+) -> Result<()> {
+    let query = scene_query::SceneQuery {
+        name: options.object,
+        path: options.path,
+        components: options.component,
+        predicates: options
+            .predicate
+            .iter()
+            .map(|p| p.parse())
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let scene = Scene::new(&pak, &name)?;
+    scene.print_rsz_reports(&name);
+    let matches = query.run(&scene)?;
+    match format {
+        QueryFormat::Json => println!("{}", serde_json::to_string_pretty(&matches)?),
+        QueryFormat::Csv => {
+            let quote = |s: &str| {
+                if s.contains([',', '"', '\n']) {
+                    format!("\"{}\"", s.replace('"', "\"\""))
+                } else {
+                    s.to_owned()
+                }
+            };
+            println!("path,name,x,y,z,qx,qy,qz,qw,sx,sy,sz,components");
+            for m in &matches {
+                let (p, r, s) = (m.position, m.rotation, m.scale);
+                println!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    quote(&m.path),
+                    quote(m.name.as_deref().unwrap_or_default()),
+                    p.x,
+                    p.y,
+                    p.z,
+                    r.x,
+                    r.y,
+                    r.z,
+                    r.w,
+                    s.x,
+                    s.y,
+                    s.z,
+                    quote(&m.components.join(";"))
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 fn map(pak: Vec<String>, name: String, scale: String, tex: String, output: String) -> Result<()> {
-    let mut pak = PakReader::new(open_pak_files(pak)?)?;
-    let scene = Scene::new(&mut pak, &name)?;
+    let pak = SyncPakReader::new(open_pak_files(pak)?)?;
+    let scene = Scene::new(&pak, &name)?;
+    scene.print_rsz_reports(&name);
     let scale: rsz::GuiMapScaleDefineData = User::new(File::open(scale)?)?
         .rsz
         .deserialize_single(None)?;
     let tex = Tex::new(File::open(tex)?)?;
     let mut rgba = tex.to_rgba(0, 0)?;
 
-    scene.for_each_object(&mut |object: &GameObject| {
+    scene.for_each_object_world(&mut |object: &GameObject, world: &nalgebra_glm::Mat4| {
         if let Ok(_pop) = object.get_component::<rsz::ItemPopBehavior>() {
-            let transform = object.get_component::<rsz::Transform>()?;
-            let x = (transform.position.x + scale.map_wide_min_pos) / scale.map_scale;
-            let y = (transform.position.z + scale.map_height_min_pos) / scale.map_scale;
+            let position = world.column(3);
+            let x = (position.x + scale.map_wide_min_pos) / scale.map_scale;
+            let y = (position.z + scale.map_height_min_pos) / scale.map_scale;
             let x = (x * rgba.width() as f32) as i32;
             let y = (y * rgba.height() as f32) as i32;
             if x < 0 || y < 0 || x >= rgba.width() as i32 || y >= rgba.height() as i32 {
@@ -1325,9 +2502,11 @@ fn map(pak: Vec<String>, name: String, scale: String, tex: String, output: Strin
             }
             let pixel = rgba.pixel(x as u32, y as u32);
             pixel.copy_from_slice(&[255, 0, 0, 255]);
+            return Ok(false);
         }
 
-        Ok(false)
+        // Pops placed by a prefab are under the instancing object
+        Ok(object.prefab.is_some())
     })?;
 
     rgba.save_png(File::create(output)?)?;
@@ -1335,9 +2514,108 @@ fn map(pak: Vec<String>, name: String, scale: String, tex: String, output: Strin
     Ok(())
 }
 
+fn edit_scene(
+    input: String,
+    output: String,
+    version: Option<u32>,
+    options: SceneEditOptions,
+) -> Result<()> {
+    let version = version.unwrap_or(u32::MAX);
+    let data = std::fs::read(input)?;
+    let kind = FileKind::detect(&data);
+    let mut scene = match kind {
+        Some(FileKind::Scn) => Scene::from_scn(Scn::new(Cursor::new(&data))?)?,
+        Some(FileKind::Pfb) => Scene::from_pfb(Pfb::new(Cursor::new(&data))?)?,
+        _ => bail!("Not a SCN or PFB file"),
+    };
+    if !scene.rsz_report.is_empty() {
+        bail!("The file is partially read:\n{}", scene.rsz_report)
+    }
+
+    let path = options.object.as_str();
+    if options.remove {
+        scene.remove_object(path)?;
+    } else {
+        if options.create {
+            let (parent, name) = match path.rsplit_once('/') {
+                Some((parent, name)) => (Some(parent), name),
+                None => (None, path),
+            };
+            let guid = match (&kind, options.guid) {
+                (Some(FileKind::Pfb), None) => rsz::Guid { bytes: [0; 16] },
+                (Some(FileKind::Pfb), Some(_)) => bail!("Objects in a PFB file have no GUID"),
+                (_, Some(guid)) => guid.parse()?,
+                (_, None) => bail!("--guid is required to create an object in a SCN file"),
+            };
+            let mut object = GameObject::new(
+                guid,
+                rsz::GameObject {
+                    name: Some(name.to_owned()),
+                    tag: None,
+                    update_self: true,
+                    draw_self: true,
+                    time_scale: -1.0,
+                },
+            );
+            object.add_component(rsz::AnyRsz::from_node(rsz::Transform {
+                position: nalgebra_glm::vec4(0.0, 0.0, 0.0, 0.0),
+                rotation: nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0),
+                scale: nalgebra_glm::vec4(1.0, 1.0, 1.0, 0.0),
+                zinogre: None,
+                same_joints_constraint: false,
+                absolute_scaling: false,
+                joint_segment_scale: false,
+                joint_fast_lock_scene: false,
+            })?);
+            scene.add_object(parent, object)?;
+        }
+
+        let object = scene.object_mut(path)?;
+        for symbol in &options.remove_component {
+            object.remove_component(symbol)?;
+        }
+        let (crc_hint, extern_hint) = (HashMap::new(), HashMap::new());
+        let mut reader = rsz::RszJsonReader::new(version, &crc_hint, &extern_hint);
+        for (i, json) in options.add_component.iter().enumerate() {
+            let value: serde_json::Value = serde_json::from_str(json)?;
+            object.add_component(reader.read_root(&value, &format!("$component{i}"))?);
+        }
+        if let Some(position) = &options.position {
+            let coords = position
+                .split(',')
+                .map(|c| c.trim().parse())
+                .collect::<Result<Vec<f32>, _>>()?;
+            let &[x, y, z] = coords.as_slice() else {
+                bail!("Expected x,y,z position, found {position}")
+            };
+            let transform = object.get_component_mut::<rsz::Transform>()?;
+            transform.position.x = x;
+            transform.position.y = y;
+            transform.position.z = z;
+        }
+
+        if let Some(parent) = &options.parent {
+            let parent = (parent != "/").then_some(parent.as_str());
+            scene.move_object(path, parent)?;
+        }
+    }
+
+    let output = File::create(output)?;
+    if kind == Some(FileKind::Scn) {
+        scene.to_scn(version)?.write(output)
+    } else {
+        scene.to_pfb(version)?.write(output)
+    }
+}
+
 fn main() -> Result<()> {
     gpu::gpu_init();
-    match Mhrice::parse() {
+    let cli = Cli::parse();
+    cli.pak_key.into_config()?.install()?;
+    if let Some(schema) = cli.rsz_schema {
+        rsz::RszSchema::load(schema)?.install()?;
+    }
+    match cli.command {
         Mhrice::Dump { pak, name, output } => dump(pak, name, output),
         Mhrice::DumpIndex {
             pak,
@@ -1345,7 +2623,29 @@ fn main() -> Result<()> {
             index,
             output,
         } => dump_index(pak, version, index, output),
-        Mhrice::ScanRsz { pak, crc } => scan_rsz(pak, crc),
+        Mhrice::PackPak {
+            input,
+            output,
+            compression,
+        } => pack_pak(input, output, compression),
+        Mhrice::ScanRsz {
+            pak,
+            crc,
+            round_trip,
+            version,
+            report,
+        } => scan_rsz(pak, crc, round_trip, version, report),
+        Mhrice::RecheckRsz {
+            pak,
+            report,
+            version,
+        } => recheck_rsz(pak, report, version),
+        Mhrice::GenRsz {
+            name,
+            tdb,
+            version,
+            output,
+        } => gen_rsz(name, tdb, version, output),
         Mhrice::GenJson { pak } => gen_json(pak),
         Mhrice::GenWebsite {
             pak,
@@ -1362,7 +2662,19 @@ fn main() -> Result<()> {
             pattern,
         } => grep(pak, utf16, pattern),
         Mhrice::SearchPath { pak, dmp } => search_path(pak, dmp),
+        Mhrice::DiscoverPath { pak, list } => discover_path(pak, list),
         Mhrice::DumpTree { pak, list, output } => dump_tree(pak, list, output),
+        Mhrice::Ls { pak, list } => ls(pak, list),
+        Mhrice::Overrides { pak, list } => overrides(pak, list),
+        Mhrice::VerifyPak { pak } => verify_pak(pak),
+        Mhrice::Convert {
+            input,
+            pak,
+            output,
+            recursive,
+            mesh_format,
+        } => convert(input, pak, output, recursive, mesh_format),
+        Mhrice::Census { pak } => census(pak),
         Mhrice::ScanMesh { pak } => scan_mesh(pak),
         Mhrice::ScanTex { pak } => scan_tex(pak),
         Mhrice::ScanGui { pak } => scan_gui(pak),
@@ -1385,14 +2697,45 @@ fn main() -> Result<()> {
             hash(input, utf16);
             Ok(())
         }
-        Mhrice::ReadUser { user, version } => read_user(user, version),
+        Mhrice::ReadUser {
+            user,
+            version,
+            resolve_externs,
+            pak,
+        } => read_user(user, version, resolve_externs, pak),
+        Mhrice::WriteUser {
+            json,
+            output,
+            version,
+            template,
+        } => write_user(json, output, version, template),
+        Mhrice::EditScene {
+            input,
+            output,
+            version,
+            options,
+        } => edit_scene(input, output, version, options),
+        Mhrice::DiffUser {
+            old,
+            new,
+            file,
+            key,
+            json,
+        } => diff_user(old, new, file, key, json),
         Mhrice::ReadDmpTdb {
             dmp,
             address,
             options,
         } => read_dmp_tdb(dmp, address, options),
+        Mhrice::DiffTdb { old, new, json } => diff_tdb(old, new, json),
         Mhrice::DumpScn { scn } => dump_scn(scn),
         Mhrice::Scene { pak, name } => scene(pak, name),
+        Mhrice::SceneQuery {
+            pak,
+            name,
+            options,
+            format,
+        } => scene_query(pak, name, options, format),
         Mhrice::TypeInfo { dmp, hash, crc } => type_info(dmp, hash, crc),
         Mhrice::Map {
             pak,
@@ -2,16 +2,20 @@ use crate::align::align_up;
 use crate::file_ext::*;
 use crate::hash::hash_as_utf16;
 use crate::suffix::SUFFIX_MAP;
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use base64::prelude::*;
 use compress::flate;
+use md5::{Digest, Md5};
 use num_bigint::BigUint;
-use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use once_cell::sync::{Lazy, OnceCell};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
-use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
-static PAK_MAIN_KEY_MOD: Lazy<Option<Vec<u8>>> = Lazy::new(|| None);
+static PAK_KEY_CONFIG: OnceCell<PakKeyConfig> = OnceCell::new();
 
 static PAK_SUB_KEY_MOD: Lazy<Vec<u8>> = Lazy::new(|| {
     BASE64_STANDARD
@@ -31,6 +35,78 @@ const LANGUAGE_LIST: &[&str] = &[
     "Fc", "Hi",
 ];
 
+/// Minimum confidence for a guessed entry table key to be accepted
+pub const GUESS_KEY_MIN_CONFIDENCE: f32 = 0.8;
+
+/// Where the entry table key of encrypted PAK files comes from
+#[derive(Debug, Clone, Default)]
+pub struct PakKeyConfig {
+    /// RSA modulus that decrypts the key stored in each PAK file
+    pub modulus: Option<Vec<u8>>,
+    /// Already decrypted key, used for every encrypted PAK file
+    pub key: Option<[u8; 0x20]>,
+    /// Folder to persist guessed keys in, one file per PAK file digest
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl PakKeyConfig {
+    /// Sets the config used by all PAK readers opened afterwards.
+    /// Can only be called once, before any PAK file is opened.
+    pub fn install(self) -> Result<()> {
+        PAK_KEY_CONFIG
+            .set(self)
+            .map_err(|_| anyhow!("PAK key config is already set"))
+    }
+
+    fn get() -> &'static PakKeyConfig {
+        PAK_KEY_CONFIG.get_or_init(PakKeyConfig::default)
+    }
+
+    fn cache_path(&self, digest: &[u8]) -> Option<PathBuf> {
+        let name = format!(
+            "{}.key",
+            digest
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        );
+        self.cache_dir.as_ref().map(|dir| dir.join(name))
+    }
+
+    fn load_cached_key(&self, digest: &[u8]) -> Result<Option<[u8; 0x20]>> {
+        let Some(path) = self.cache_path(digest) else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let key = BASE64_STANDARD
+            .decode(std::fs::read_to_string(&path)?.trim())
+            .with_context(|| format!("Failed to decode cached key {}", path.display()))?;
+        let key = key
+            .try_into()
+            .map_err(|_| anyhow!("Cached key {} is not 32 bytes", path.display()))?;
+        Ok(Some(key))
+    }
+
+    fn save_cached_key(&self, digest: &[u8], key: &[u8; 0x20]) -> Result<()> {
+        let Some(path) = self.cache_path(digest) else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, BASE64_STANDARD.encode(key))
+            .with_context(|| format!("Failed to cache key to {}", path.display()))
+    }
+}
+
+/// A key recovered from an encrypted entry table without the modulus
+#[derive(Debug, Clone, Copy)]
+pub struct PakKeyGuess {
+    pub key: [u8; 0x20],
+    /// The lowest rate of the key agreeing with the observed xorpads, from 0 to 1
+    pub confidence: f32,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct PakFileIndex {
     version: usize,
@@ -47,10 +123,91 @@ impl PakFileIndex {
     pub fn short_string(&self) -> String {
         format!("{:02}-{:06}", self.version, self.index)
     }
+
+    /// The position of the PAK file that owns the entry
+    pub fn version(&self) -> usize {
+        self.version
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PakEntryInfo {
+    pub index: PakFileIndex,
+    pub len_compressed: u64,
+    pub len: u64,
+    pub format: u8,
+    pub encryption: u8,
+}
+
+impl PakEntryInfo {
+    pub fn format_name(&self) -> String {
+        format_name(self.format)
+    }
+}
+
+pub fn format_name(format: u8) -> String {
+    match format {
+        0 => "stored".to_owned(),
+        1 | 0x11 => "deflate".to_owned(),
+        2 => "zstd".to_owned(),
+        x => format!("unknown({x})"),
+    }
+}
+
+pub fn encryption_name(encryption: u8) -> String {
+    match encryption {
+        0 => "none".to_owned(),
+        1 => "rsa".to_owned(),
+        x => format!("unknown({x})"),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PakVerifyReport {
+    pub entry_count: usize,
+    pub format_counts: BTreeMap<u8, usize>,
+    pub encryption_counts: BTreeMap<u8, usize>,
+    /// Sorted by index
+    pub failures: Vec<(PakFileIndex, String)>,
+}
+
+#[derive(Debug)]
+pub struct PakListing {
+    /// Entries resolved from the path list, sorted by name
+    pub named: Vec<(String, PakEntryInfo)>,
+    pub unnamed: Vec<PakEntryInfo>,
+}
+
+impl PakListing {
+    /// Percentage of entries that have a name
+    pub fn coverage(&self) -> f64 {
+        let total = self.named.len() + self.unnamed.len();
+        if total == 0 {
+            return 100.0;
+        }
+        self.named.len() as f64 / total as f64 * 100.0
+    }
+}
+
+/// An entry that exists in more than one PAK file
+#[derive(Debug)]
+pub struct PakOverride {
+    pub hash: u64,
+    /// All copies in the order of PAK files. The last one is the one that the reader uses
+    pub layers: Vec<PakEntryInfo>,
+    /// Whether any two copies differ in size or decompressed content
+    pub content_differs: bool,
+}
+
+impl PakOverride {
+    pub fn winner(&self) -> PakFileIndex {
+        self.layers.last().unwrap().index
+    }
 }
 
 #[derive(Debug)]
 struct PakEntry {
+    hash: u64,
     offset: u64,
     len_compressed: u64,
     len: u64,
@@ -60,103 +217,111 @@ struct PakEntry {
     encryption: u8,
 }
 
+/// Entry tables of a list of PAK files, shared by all reader variants
 #[derive(Debug)]
-struct PakFile<F> {
-    file: F,
-    entries: Vec<PakEntry>,
-}
-
-#[derive(Debug)]
-pub struct PakReader<F> {
-    files: Vec<PakFile<F>>,
+struct PakDirectory {
+    entries: Vec<Vec<PakEntry>>,
     hash_map: HashMap<u64, PakFileIndex>,
 }
 
-impl<F: Read + Seek> PakReader<F> {
-    pub fn new(raw_files: Vec<F>) -> Result<PakReader<F>> {
+impl PakDirectory {
+    fn new<F: Read + Seek>(raw_files: &mut [F]) -> Result<PakDirectory> {
         let mut hash_map = HashMap::new();
-        let files = raw_files
-            .into_iter()
+        let entries = raw_files
+            .iter_mut()
             .enumerate()
-            .map(|(version, mut file)| {
-                let magic = file.read_magic()?;
-                if &magic != b"KPKA" {
-                    bail!("Wrong magic for PAK file");
-                }
-                let pak_version = file.read_u16()?;
-                if pak_version != 4 {
-                    bail!("Wrong version for PAK file");
+            .map(|(version, file)| {
+                let entries = read_entry_table(file)?;
+                for (index, entry) in entries.iter().enumerate() {
+                    hash_map.insert(entry.hash, PakFileIndex { version, index });
                 }
-                let flag = file.read_u16()?;
-                if flag & 1 != 0 {
-                    bail!("Unimplemented flag 1")
-                }
-                let count = file.read_u32()?;
-                file.seek(SeekFrom::Current(4))?;
-
-                let mut entries_buffer = vec![0; count as usize * 0x30];
-                file.read_exact(&mut entries_buffer)?;
-
-                if flag & 8 != 0 {
-                    let key = if let Some(m) = &*PAK_MAIN_KEY_MOD {
-                        let mut encrypted_key = [0; 128];
-                        file.read_exact(&mut encrypted_key)?;
-
-                        let base = BigUint::from_bytes_le(&encrypted_key);
-                        let modulus = BigUint::from_bytes_le(m);
-                        let exponent = BigUint::from(0x10001u32);
-                        let power = base.modpow(&exponent, &modulus);
-                        let key_vec = power.to_bytes_le();
-                        if key_vec.len() > 32 {
-                            bail!("Key too long")
-                        }
-                        let mut key = [0; 32];
-                        key[0..key_vec.len()].copy_from_slice(&key_vec);
-                        key
+                Ok(entries)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PakDirectory { entries, hash_map })
+    }
+
+    fn entry(&self, file_index: PakFileIndex) -> &PakEntry {
+        &self.entries[file_index.version][file_index.index]
+    }
+
+    fn entry_info(&self, index: PakFileIndex) -> PakEntryInfo {
+        let entry = self.entry(index);
+        PakEntryInfo {
+            index,
+            len_compressed: entry.len_compressed,
+            len: entry.len,
+            format: entry.format,
+            encryption: entry.encryption,
+        }
+    }
+
+    fn list_files(&self, paths: &[String]) -> Result<PakListing> {
+        let mut names: HashMap<PakFileIndex, String> = HashMap::new();
+        for origin_path in paths {
+            let streaming_path = "streaming/".to_owned() + origin_path;
+            for path in [origin_path, &streaming_path] {
+                for i18n_index in self.find_file_i18n(path)? {
+                    let name = if i18n_index.language.is_empty() {
+                        path.to_owned()
                     } else {
-                        eprintln!("PAK_MAIN_KEY_MOD not provided. Going to guess the key...");
-                        guess_key(&entries_buffer)?
+                        format!("{}.{}", path, i18n_index.language)
                     };
-                    decrypt_pak_entry_table(&mut entries_buffer, &key);
+                    names.entry(i18n_index.index).or_insert(name);
                 }
+            }
+        }
 
-                let entries: Vec<PakEntry> = entries_buffer
-                    .chunks(0x30)
-                    .enumerate()
-                    .map(|(index, mut entry)| {
-                        let hash = entry.read_u64()?;
-                        hash_map.insert(hash, PakFileIndex { version, index });
-                        let offset = entry.read_u64()?;
-                        let len_compressed = entry.read_u64()?;
-                        let len = entry.read_u64()?;
-                        let format = entry.read_u8()?;
-                        let flag = entry.read_u8()?;
-                        let encryption = entry.read_u8()?;
-                        Ok(PakEntry {
-                            offset,
-                            len_compressed,
-                            len,
-                            format,
-                            flag,
-                            encryption,
-                        })
-                    })
-                    .collect::<Result<Vec<PakEntry>>>()?;
-
-                Ok(PakFile { file, entries })
-            })
-            .collect::<Result<Vec<PakFile<F>>>>()?;
+        let mut named = vec![];
+        let mut unnamed = vec![];
+        for index in self.all_file_indexs() {
+            let info = self.entry_info(index);
+            if let Some(name) = names.remove(&index) {
+                named.push((name, info));
+            } else {
+                unnamed.push(info);
+            }
+        }
+        named.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(PakListing { named, unnamed })
+    }
+
+    fn index_at(&self, version: usize, index: usize) -> Result<PakFileIndex> {
+        if version >= self.entries.len() {
+            bail!("Version out of bound")
+        }
+        if index >= self.entries[version].len() {
+            bail!("Index out of bound");
+        }
+        Ok(PakFileIndex { version, index })
+    }
 
-        Ok(PakReader { files, hash_map })
+    /// Groups entries by hash for those that appear more than once, in the order of PAK files
+    fn override_layers(&self) -> Vec<(u64, Vec<PakFileIndex>)> {
+        let mut layers: HashMap<u64, Vec<PakFileIndex>> = HashMap::new();
+        for (version, entries) in self.entries.iter().enumerate() {
+            for (index, entry) in entries.iter().enumerate() {
+                layers
+                    .entry(entry.hash)
+                    .or_default()
+                    .push(PakFileIndex { version, index });
+            }
+        }
+        let mut layers: Vec<_> = layers
+            .into_iter()
+            .filter(|(_, layers)| layers.len() > 1)
+            .collect();
+        layers.sort_by_key(|(_, layers)| *layers.last().unwrap());
+        layers
     }
 
-    fn find_file_internal(&mut self, full_path: String) -> Option<PakFileIndex> {
-        let hash: u64 = u64::from(hash_as_utf16(&full_path.to_lowercase()))
-            | (u64::from(hash_as_utf16(&full_path.to_uppercase())) << 32);
-        self.hash_map.get(&hash).cloned()
+    fn find_file_internal(&self, full_path: String) -> Option<PakFileIndex> {
+        self.hash_map.get(&path_hash(&full_path)).cloned()
     }
 
-    pub fn find_file_i18n(&mut self, mut path: &str) -> Result<Vec<I18nPakFileIndex>> {
+    fn find_file_i18n(&self, mut path: &str) -> Result<Vec<I18nPakFileIndex>> {
         if path.starts_with('@') {
             path = &path[1..];
         }
@@ -195,7 +360,7 @@ impl<F: Read + Seek> PakReader<F> {
         Ok(vec![])
     }
 
-    pub fn find_file(&mut self, path: &str) -> Result<PakFileIndex> {
+    fn find_file(&self, path: &str) -> Result<PakFileIndex> {
         Ok(self
             .find_file_i18n(path)?
             .first()
@@ -203,92 +368,617 @@ impl<F: Read + Seek> PakReader<F> {
             .index)
     }
 
-    pub fn read_file(&mut self, file_index: PakFileIndex) -> Result<Vec<u8>> {
-        let PakFile { file, entries } = &mut self.files[file_index.version];
-        let PakEntry {
-            offset,
-            len_compressed,
-            len,
-            format,
-            encryption,
-            ..
-        } = entries[file_index.index];
-
-        file.seek(SeekFrom::Start(offset))?;
-        let mut data = vec![0; len_compressed.try_into()?];
-        file.read_exact(&mut data)?;
+    fn all_file_indexs(&self) -> Vec<PakFileIndex> {
+        let mut v: Vec<_> = self.hash_map.values().cloned().collect();
+        v.sort();
+        v
+    }
+}
 
-        match encryption {
-            0 => {}
-            1 => {
-                let encrypted = data;
-                let mut encrypted = &encrypted[..];
-                let plain_len: usize = encrypted.read_u64()?.try_into()?;
-                if align_up(plain_len, 8) * 0x10 != encrypted.len() {
-                    bail!("Unexpected size for decryption")
+fn read_entry_table<F: Read + Seek>(file: &mut F) -> Result<Vec<PakEntry>> {
+    let magic = file.read_magic()?;
+    if &magic != b"KPKA" {
+        bail!("Wrong magic for PAK file");
+    }
+    let pak_version = file.read_u16()?;
+    if pak_version != 4 {
+        bail!("Wrong version for PAK file");
+    }
+    let flag = file.read_u16()?;
+    if flag & 1 != 0 {
+        bail!("Unimplemented flag 1")
+    }
+    let count = file.read_u32()?;
+    file.seek(SeekFrom::Current(4))?;
+
+    let mut entries_buffer = vec![0; count as usize * 0x30];
+    file.read_exact(&mut entries_buffer)?;
+
+    if flag & 8 != 0 {
+        let config = PakKeyConfig::get();
+        let key = if let Some(key) = config.key {
+            key
+        } else if let Some(m) = &config.modulus {
+            let mut encrypted_key = [0; 128];
+            file.read_exact(&mut encrypted_key)?;
+
+            let base = BigUint::from_bytes_le(&encrypted_key);
+            let modulus = BigUint::from_bytes_le(m);
+            let exponent = BigUint::from(0x10001u32);
+            let power = base.modpow(&exponent, &modulus);
+            let key_vec = power.to_bytes_le();
+            if key_vec.len() > 32 {
+                bail!("Key too long")
+            }
+            let mut key = [0; 32];
+            key[0..key_vec.len()].copy_from_slice(&key_vec);
+            key
+        } else {
+            // The encrypted table is unique enough to identify the archive
+            let digest = Md5::digest(&entries_buffer);
+            if let Some(key) = config.load_cached_key(&digest)? {
+                key
+            } else {
+                eprintln!("PAK key not provided. Going to guess the key...");
+                let guess = guess_key(&entries_buffer)?;
+                eprintln!("Guessed the key with confidence {:.3}", guess.confidence);
+                if guess.confidence < GUESS_KEY_MIN_CONFIDENCE {
+                    bail!("Failed to guess the key")
                 }
-                data = vec![0; plain_len];
-                let e = &*PAK_SUB_KEY_EXP;
-                let m = &*PAK_SUB_KEY_MOD;
-                let e = BigUint::from_bytes_le(e);
-                let m = BigUint::from_bytes_le(m);
-                for (plain, enc) in data.chunks_mut(8).zip(encrypted.chunks(0x80)) {
-                    let p = BigUint::from_bytes_le(&enc[0..0x40]);
-                    let q = BigUint::from_bytes_le(&enc[0x40..0x80]);
-                    let r = (q / (p.modpow(&e, &m))).to_bytes_le();
-                    if r.len() > plain.len() {
-                        bail!("Unexpected plain text")
-                    }
-                    plain[0..r.len()].copy_from_slice(&r);
+                config.save_cached_key(&digest, &guess.key)?;
+                guess.key
+            }
+        };
+        decrypt_pak_entry_table(&mut entries_buffer, &key);
+    }
+
+    entries_buffer
+        .chunks(0x30)
+        .map(|mut entry| {
+            let hash = entry.read_u64()?;
+            let offset = entry.read_u64()?;
+            let len_compressed = entry.read_u64()?;
+            let len = entry.read_u64()?;
+            let format = entry.read_u8()?;
+            let flag = entry.read_u8()?;
+            let encryption = entry.read_u8()?;
+            Ok(PakEntry {
+                hash,
+                offset,
+                len_compressed,
+                len,
+                format,
+                flag,
+                encryption,
+            })
+        })
+        .collect::<Result<Vec<PakEntry>>>()
+}
+
+/// Decrypts and decompresses the raw bytes of an entry
+fn decode_entry(entry: &PakEntry, mut data: Vec<u8>) -> Result<Vec<u8>> {
+    let PakEntry {
+        len,
+        format,
+        encryption,
+        ..
+    } = *entry;
+
+    match encryption {
+        0 => {}
+        1 => {
+            let encrypted = data;
+            let mut encrypted = &encrypted[..];
+            let plain_len: usize = encrypted.read_u64()?.try_into()?;
+            if align_up(plain_len, 8) * 0x10 != encrypted.len() {
+                bail!("Unexpected size for decryption")
+            }
+            data = vec![0; plain_len];
+            let e = &*PAK_SUB_KEY_EXP;
+            let m = &*PAK_SUB_KEY_MOD;
+            let e = BigUint::from_bytes_le(e);
+            let m = BigUint::from_bytes_le(m);
+            for (plain, enc) in data.chunks_mut(8).zip(encrypted.chunks(0x80)) {
+                let p = BigUint::from_bytes_le(&enc[0..0x40]);
+                let q = BigUint::from_bytes_le(&enc[0x40..0x80]);
+                let r = (q / (p.modpow(&e, &m))).to_bytes_le();
+                if r.len() > plain.len() {
+                    bail!("Unexpected plain text")
                 }
+                plain[0..r.len()].copy_from_slice(&r);
+            }
+        }
+        _ => bail!("Unsupported encryption {}", encryption),
+    }
+
+    match format {
+        0 => {
+            if len != u64::try_from(data.len())? {
+                bail!("Uncompressed file should have len == len_compressed")
+            }
+            Ok(data)
+        }
+        1 | 0x11 => {
+            let mut decompressed = Vec::new();
+            flate::Decoder::new(&data[..]).read_to_end(&mut decompressed)?;
+            if u64::try_from(decompressed.len()).unwrap() != len {
+                bail!("Expected size {}, actual size {}", len, decompressed.len());
             }
-            _ => bail!("Unsupported encryption {}", encryption),
+            Ok(decompressed)
         }
+        2 => {
+            let decoded = zstd::decode_all(&data[..])?;
+            if u64::try_from(decoded.len()).unwrap() != len {
+                bail!("Expected size {}, actual size {}", len, decoded.len());
+            }
+            Ok(decoded)
+        }
+        _ => bail!("Unsupported format: {}", format),
+    }
+}
 
-        match format {
-            0 => {
-                if len != u64::try_from(data.len())? {
-                    bail!("Uncompressed file should have len == len_compressed")
+enum PakEntryDecoder<R: Read> {
+    Stored(BufReader<R>),
+    Deflate(flate::Decoder<BufReader<R>>),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<R>>),
+    // Encrypted entries are decoded as a whole
+    Buffered(Cursor<Vec<u8>>),
+}
+
+/// Decompresses an entry while reading, and verifies the decompressed length at the end
+struct PakEntryReader<R: Read> {
+    decoder: PakEntryDecoder<R>,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Read> PakEntryReader<R> {
+    /// `raw` should be positioned at the entry and end after `len_compressed` bytes
+    fn new(entry: &PakEntry, mut raw: R) -> Result<PakEntryReader<R>> {
+        let decoder = if entry.encryption != 0 {
+            let mut data = vec![0; entry.len_compressed.try_into()?];
+            raw.read_exact(&mut data)?;
+            PakEntryDecoder::Buffered(Cursor::new(decode_entry(entry, data)?))
+        } else {
+            let raw = BufReader::new(raw);
+            match entry.format {
+                0 => {
+                    if entry.len != entry.len_compressed {
+                        bail!("Uncompressed file should have len == len_compressed")
+                    }
+                    PakEntryDecoder::Stored(raw)
                 }
-                Ok(data)
+                1 | 0x11 => PakEntryDecoder::Deflate(flate::Decoder::new(raw)),
+                2 => PakEntryDecoder::Zstd(zstd::stream::read::Decoder::with_buffer(raw)?),
+                format => bail!("Unsupported format: {}", format),
             }
-            1 | 0x11 => {
-                let mut decompressed = Vec::new();
-                flate::Decoder::new(&data[..]).read_to_end(&mut decompressed)?;
-                if u64::try_from(decompressed.len()).unwrap() != len {
-                    bail!("Expected size {}, actual size {}", len, decompressed.len());
+        };
+        Ok(PakEntryReader {
+            decoder,
+            len: entry.len,
+            pos: 0,
+        })
+    }
+}
+
+impl<R: Read> Read for PakEntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = match &mut self.decoder {
+            PakEntryDecoder::Stored(r) => r.read(buf)?,
+            PakEntryDecoder::Deflate(r) => r.read(buf)?,
+            PakEntryDecoder::Zstd(r) => r.read(buf)?,
+            PakEntryDecoder::Buffered(r) => r.read(buf)?,
+        };
+        self.pos += read as u64;
+        if self.pos > self.len || (read == 0 && !buf.is_empty() && self.pos != self.len) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Expected size {}, actual size {}", self.len, self.pos),
+            ));
+        }
+        Ok(read)
+    }
+}
+
+/// Reads a range of a file with positional reads, without touching the file cursor
+struct PositionalReader<'a> {
+    file: &'a File,
+    pos: u64,
+    end: u64,
+}
+
+impl<'a> Read for PositionalReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = std::cmp::min(buf.len() as u64, self.end - self.pos) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let read = read_at(self.file, &mut buf[..len], self.pos)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[derive(Debug)]
+pub struct PakReader<F> {
+    files: Vec<F>,
+    directory: PakDirectory,
+}
+
+impl<F: Read + Seek> PakReader<F> {
+    pub fn new(mut files: Vec<F>) -> Result<PakReader<F>> {
+        let directory = PakDirectory::new(&mut files)?;
+        Ok(PakReader { files, directory })
+    }
+
+    pub fn find_file(&self, path: &str) -> Result<PakFileIndex> {
+        self.directory.find_file(path)
+    }
+
+    /// Resolves names of entries from a list of paths without platform suffixes,
+    /// such as the output of the search-path command
+    pub fn list_files(&self, paths: &[String]) -> Result<PakListing> {
+        self.directory.list_files(paths)
+    }
+
+    pub fn read_file(&mut self, file_index: PakFileIndex) -> Result<Vec<u8>> {
+        let entry = self.directory.entry(file_index);
+        let file = &mut self.files[file_index.version];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut data = vec![0; entry.len_compressed.try_into()?];
+        file.read_exact(&mut data)?;
+        decode_entry(entry, data)
+    }
+
+    /// Opens an entry for streaming decompression
+    pub fn open_file(&mut self, file_index: PakFileIndex) -> Result<impl Read + '_> {
+        let entry = self.directory.entry(file_index);
+        let file = &mut self.files[file_index.version];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        PakEntryReader::new(entry, file.take(entry.len_compressed))
+    }
+
+    /// Decompresses an entry to the output without holding the whole entry in memory,
+    /// unless it is encrypted
+    pub fn copy_file_to(
+        &mut self,
+        file_index: PakFileIndex,
+        mut output: impl Write,
+    ) -> Result<u64> {
+        let mut reader = self.open_file(file_index)?;
+        Ok(std::io::copy(&mut reader, &mut output)?)
+    }
+
+    pub fn index_at(&self, version: usize, index: usize) -> Result<PakFileIndex> {
+        self.directory.index_at(version, index)
+    }
+
+    pub fn all_file_indexs(&self) -> Vec<PakFileIndex> {
+        self.directory.all_file_indexs()
+    }
+}
+
+/// A PAK reader that reads entries with positional reads instead of seeking a shared cursor.
+///
+/// Reading only needs `&self`, so entries can be decompressed from multiple threads.
+#[derive(Debug)]
+pub struct SyncPakReader {
+    files: Vec<File>,
+    directory: PakDirectory,
+}
+
+impl SyncPakReader {
+    pub fn new(mut files: Vec<File>) -> Result<SyncPakReader> {
+        let directory = PakDirectory::new(&mut files)?;
+        Ok(SyncPakReader { files, directory })
+    }
+
+    pub fn find_file(&self, path: &str) -> Result<PakFileIndex> {
+        self.directory.find_file(path)
+    }
+
+    pub fn find_file_i18n(&self, path: &str) -> Result<Vec<I18nPakFileIndex>> {
+        self.directory.find_file_i18n(path)
+    }
+
+    pub fn list_files(&self, paths: &[String]) -> Result<PakListing> {
+        self.directory.list_files(paths)
+    }
+
+    /// Reports entries that are overridden by later PAK files, such as patch PAK files.
+    ///
+    /// Copies with the same size are decompressed and compared by digest.
+    pub fn overrides(&self) -> Result<Vec<PakOverride>> {
+        self.directory
+            .override_layers()
+            .into_par_iter()
+            .map(|(hash, indexs)| {
+                let layers: Vec<PakEntryInfo> = indexs
+                    .iter()
+                    .map(|&index| self.directory.entry_info(index))
+                    .collect();
+                let content_differs = if layers.iter().any(|info| info.len != layers[0].len) {
+                    true
+                } else {
+                    let digests = indexs
+                        .iter()
+                        .map(|&index| Ok(Md5::digest(self.read_file(index)?)))
+                        .collect::<Result<Vec<_>>>()?;
+                    digests.iter().any(|digest| *digest != digests[0])
+                };
+                Ok(PakOverride {
+                    hash,
+                    layers,
+                    content_differs,
+                })
+            })
+            .collect()
+    }
+
+    /// Checks the layout of all entries and decodes every one of them,
+    /// collecting failures instead of stopping at the first one
+    pub fn verify(&self) -> Result<PakVerifyReport> {
+        let mut report = PakVerifyReport::default();
+        let mut in_bound = vec![];
+
+        for (version, (file, entries)) in self.files.iter().zip(&self.directory.entries).enumerate()
+        {
+            let file_len = file.metadata()?.len();
+            let mut ranges = vec![];
+            for (index, entry) in entries.iter().enumerate() {
+                let file_index = PakFileIndex { version, index };
+                report.entry_count += 1;
+                *report.format_counts.entry(entry.format).or_default() += 1;
+                *report
+                    .encryption_counts
+                    .entry(entry.encryption)
+                    .or_default() += 1;
+
+                match entry.offset.checked_add(entry.len_compressed) {
+                    Some(end) if end <= file_len => {
+                        in_bound.push(file_index);
+                        if entry.len_compressed != 0 {
+                            ranges.push((entry.offset, end, file_index));
+                        }
+                    }
+                    _ => report.failures.push((
+                        file_index,
+                        format!(
+                            "Range {:#X}+{:#X} exceeds file size {:#X}",
+                            entry.offset, entry.len_compressed, file_len
+                        ),
+                    )),
                 }
-                Ok(decompressed)
             }
-            2 => {
-                let decoded = zstd::decode_all(&data[..])?;
-                if u64::try_from(decoded.len()).unwrap() != len {
-                    bail!("Expected size {}, actual size {}", len, decoded.len());
+
+            ranges.sort();
+            let mut furthest: Option<(u64, PakFileIndex)> = None;
+            for (begin, end, file_index) in ranges {
+                if let Some((furthest_end, other)) = furthest {
+                    if begin < furthest_end {
+                        report.failures.push((
+                            file_index,
+                            format!("Overlaps with {}", other.short_string()),
+                        ));
+                    }
+                    if end <= furthest_end {
+                        continue;
+                    }
                 }
-                Ok(decoded)
+                furthest = Some((end, file_index));
             }
-            _ => bail!("Unsupported format: {}", format),
         }
+
+        let decode_failures: Vec<_> = in_bound
+            .into_par_iter()
+            .filter_map(|index| match self.read_file(index) {
+                Ok(_) => None,
+                Err(e) => Some((index, format!("{e:#}"))),
+            })
+            .collect();
+        report.failures.extend(decode_failures);
+        report.failures.sort_by_key(|(index, _)| *index);
+
+        Ok(report)
     }
 
-    pub fn read_file_at(&mut self, version: usize, index: usize) -> Result<Vec<u8>> {
-        if version > self.files.len() {
-            bail!("Version out of bound")
+    fn raw_reader(&self, file_index: PakFileIndex) -> PositionalReader<'_> {
+        let entry = self.directory.entry(file_index);
+        PositionalReader {
+            file: &self.files[file_index.version],
+            pos: entry.offset,
+            end: entry.offset + entry.len_compressed,
         }
-        if index >= self.files[version].entries.len() {
-            bail!("Index out of bound");
-        }
-        self.read_file(PakFileIndex { version, index })
+    }
+
+    /// Hash of the full path of the entry, which identifies it in any list of PAK files
+    pub fn path_hash_of(&self, file_index: PakFileIndex) -> u64 {
+        self.directory.entry(file_index).hash
+    }
+
+    pub fn find_path_hash(&self, hash: u64) -> Result<PakFileIndex> {
+        self.directory
+            .hash_map
+            .get(&hash)
+            .copied()
+            .with_context(|| format!("No entry with path hash {hash:016X}"))
+    }
+
+    pub fn read_file(&self, file_index: PakFileIndex) -> Result<Vec<u8>> {
+        let entry = self.directory.entry(file_index);
+        let mut data = vec![0; entry.len_compressed.try_into()?];
+        self.raw_reader(file_index).read_exact(&mut data)?;
+        decode_entry(entry, data)
+    }
+
+    /// Opens an entry for streaming decompression
+    pub fn open_file(&self, file_index: PakFileIndex) -> Result<impl Read + '_> {
+        PakEntryReader::new(
+            self.directory.entry(file_index),
+            self.raw_reader(file_index),
+        )
+    }
+
+    /// Decompresses an entry to the output without holding the whole entry in memory,
+    /// unless it is encrypted
+    pub fn copy_file_to(&self, file_index: PakFileIndex, mut output: impl Write) -> Result<u64> {
+        let mut reader = self.open_file(file_index)?;
+        Ok(std::io::copy(&mut reader, &mut output)?)
     }
 
     pub fn all_file_indexs(&self) -> Vec<PakFileIndex> {
-        let mut v: Vec<_> = self.hash_map.values().cloned().collect();
-        v.sort();
-        v
+        self.directory.all_file_indexs()
     }
 }
 
-fn guess_key(bytes: &[u8]) -> Result<[u8; 0x20]> {
+pub fn path_hash(full_path: &str) -> u64 {
+    u64::from(hash_as_utf16(&full_path.to_lowercase()))
+        | (u64::from(hash_as_utf16(&full_path.to_uppercase())) << 32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PakCompression {
+    Stored,
+    Deflate,
+    Zstd,
+}
+
+#[derive(Debug)]
+struct PakWriterEntry {
+    hash: u64,
+    source: PathBuf,
+    compression: PakCompression,
+}
+
+/// Builds a KPKA version 4 archive without encryption.
+///
+/// Paths are full paths as the reader looks them up,
+/// e.g. "natives/STM/gui/01_common/item_icon.tex.28"
+#[derive(Debug, Default)]
+pub struct PakWriter {
+    entries: Vec<PakWriterEntry>,
+    hashes: HashSet<u64>,
+}
+
+impl PakWriter {
+    pub fn new() -> PakWriter {
+        PakWriter::default()
+    }
+
+    pub fn add_file(
+        &mut self,
+        full_path: &str,
+        source: PathBuf,
+        compression: PakCompression,
+    ) -> Result<()> {
+        let hash = path_hash(full_path);
+        if !self.hashes.insert(hash) {
+            bail!("Duplicated path {full_path}")
+        }
+        self.entries.push(PakWriterEntry {
+            hash,
+            source,
+            compression,
+        });
+        Ok(())
+    }
+
+    /// Adds all files under the directory.
+    /// The path relative to the directory is used as the full path.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>, compression: PakCompression) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_owned()];
+        while let Some(current) = pending.pop() {
+            let mut entries = std::fs::read_dir(&current)?.collect::<std::io::Result<Vec<_>>>()?;
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let full_path = path
+                    .strip_prefix(dir)?
+                    .components()
+                    .map(|c| c.as_os_str().to_str().context("Bad path"))
+                    .collect::<Result<Vec<_>>>()?
+                    .join("/");
+                self.add_file(&full_path, path, compression)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write<W: Write + Seek>(mut self, mut output: W) -> Result<()> {
+        self.entries.sort_by_key(|entry| entry.hash);
+        let count = u32::try_from(self.entries.len())?;
+        let table_offset = 0x10;
+        let data_offset = table_offset + u64::from(count) * 0x30;
+        output.seek(SeekFrom::Start(data_offset))?;
+
+        let mut table = Vec::with_capacity(self.entries.len() * 0x30);
+        for entry in self.entries {
+            let data = std::fs::read(&entry.source)
+                .with_context(|| format!("Failed to read {}", entry.source.display()))?;
+            let len = u64::try_from(data.len())?;
+            let (format, data) = compress_entry(data, entry.compression)?;
+            let offset = output.stream_position()?;
+            output.write_all(&data)?;
+
+            table.write_u64(entry.hash)?;
+            table.write_u64(offset)?;
+            table.write_u64(u64::try_from(data.len())?)?;
+            table.write_u64(len)?;
+            table.write_u8(format)?;
+            table.write_u8(0)?; // flag
+            table.write_u8(0)?; // encryption
+            table.write_all(&[0; 13])?;
+        }
+
+        output.seek(SeekFrom::Start(0))?;
+        output.write_magic(b"KPKA")?;
+        output.write_u16(4)?;
+        output.write_u16(0)?;
+        output.write_u32(count)?;
+        output.write_u32(0)?;
+        output.write_all(&table)?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+// Falls back to store the data if compression doesn't help, which is what the game does for small files
+fn compress_entry(data: Vec<u8>, compression: PakCompression) -> Result<(u8, Vec<u8>)> {
+    let (format, compressed) = match compression {
+        PakCompression::Stored => return Ok((0, data)),
+        PakCompression::Deflate => {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            (1, encoder.finish()?)
+        }
+        PakCompression::Zstd => (2, zstd::encode_all(&data[..], 0)?),
+    };
+    if compressed.len() >= data.len() {
+        Ok((0, data))
+    } else {
+        Ok((format, compressed))
+    }
+}
+
+/// Guesses the key of an encrypted entry table from the statistics of its bytes.
+/// The caller decides whether the confidence is good enough.
+pub fn guess_key(bytes: &[u8]) -> Result<PakKeyGuess> {
     const P0: usize = 32;
     const P1: usize = 29;
     const MUL_TABLE_LEN: usize = P0 * (P0 + 1) / 2;
@@ -373,7 +1063,8 @@ fn guess_key(bytes: &[u8]) -> Result<[u8; 0x20]> {
 
     let key = key.map(|slot| slot.unwrap());
 
-    // Verify the matching rate
+    // Compute the matching rate
+    let mut confidence: f32 = 1.0;
     for x in 0..P0 {
         let mut matched = 0;
         for y in 0..P0 {
@@ -384,12 +1075,10 @@ fn guess_key(bytes: &[u8]) -> Result<[u8; 0x20]> {
             }
         }
         let rate = matched as f32 / P0 as f32;
-        if rate < 0.8 {
-            bail!("Failed to guess the key")
-        }
+        confidence = confidence.min(rate);
     }
 
-    Ok(key)
+    Ok(PakKeyGuess { key, confidence })
 }
 
 fn decrypt_pak_entry_table(bytes: &mut [u8], key: &[u8; 0x20]) {
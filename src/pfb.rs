@@ -1,11 +1,34 @@
+use crate::align::{align_up, pad16};
 use crate::file_ext::*;
 use crate::rsz::Rsz;
 use crate::user::UserChild;
 use anyhow::{bail, Context, Result};
-use std::io::{Read, Seek};
+use std::io::{Cursor, Read, Seek, Write};
+
+#[derive(Debug)]
+pub struct PfbGameObject {
+    /// Index of the RSZ root of the game object, followed by its components
+    pub object_index: u32,
+    pub parent_index: Option<u32>,
+    pub component_count: u32,
+}
+
+/// A field of an instance referencing another game object of the prefab
+#[derive(Debug, Clone)]
+pub struct PfbGameObjectRef {
+    /// Index of the RSZ root holding the field
+    pub object_id: u32,
+    pub property_id: u32,
+    /// Element of the field if it is an array
+    pub array_index: u32,
+    /// Index of the RSZ root of the referenced game object
+    pub target_id: u32,
+}
 
 #[derive(Debug)]
 pub struct Pfb {
+    pub game_objects: Vec<PfbGameObject>,
+    pub game_object_refs: Vec<PfbGameObjectRef>,
     pub resource_names: Vec<String>,
     pub children: Vec<UserChild>,
     pub rsz: Rsz,
@@ -17,7 +40,7 @@ impl Pfb {
         if &magic != b"PFB\0" {
             bail!("Wrong magic for PFB file");
         }
-        let zinogre_count = file.read_u32()?;
+        let game_object_count = file.read_u32()?;
         let resource_count = file.read_u32()?;
         let rathalos_count = file.read_u32()?;
         let child_count = file.read_u32()?;
@@ -30,24 +53,29 @@ impl Pfb {
         let child_list_offset = file.read_u64()?;
         let rsz_offset = file.read_u64()?;
 
-        let _ = (0..zinogre_count)
+        let game_objects = (0..game_object_count)
             .map(|_| {
-                file.read_u32()?;
-                file.read_u32()?;
-                file.read_u32()?;
-                Ok(())
+                let object_index = file.read_u32()?;
+                let parent_index = file.read_u32()?;
+                let component_count = file.read_u32()?;
+                Ok(PfbGameObject {
+                    object_index,
+                    parent_index: (parent_index != 0xFFFFFFFF).then_some(parent_index),
+                    component_count,
+                })
             })
             .collect::<Result<Vec<_>>>()?;
         file.seek_noop(rathalos_offset)
             .context("Undisconvered data before rathalos list")?;
 
-        let _ = (0..rathalos_count)
+        let game_object_refs = (0..rathalos_count)
             .map(|_| {
-                file.read_u32()?;
-                file.read_u32()?;
-                file.read_u32()?;
-                file.read_u32()?;
-                Ok(())
+                Ok(PfbGameObjectRef {
+                    object_id: file.read_u32()?,
+                    property_id: file.read_u32()?,
+                    array_index: file.read_u32()?,
+                    target_id: file.read_u32()?,
+                })
             })
             .collect::<Result<Vec<_>>>()?;
 
@@ -100,9 +128,79 @@ impl Pfb {
         let rsz = Rsz::new(file, rsz_offset)?;
 
         Ok(Pfb {
+            game_objects,
+            game_object_refs,
             resource_names,
             children,
             rsz,
         })
     }
+
+    pub fn write<F: Write>(&self, mut file: F) -> Result<()> {
+        let rathalos_offset = 0x38 + self.game_objects.len() as u64 * 0xC;
+        let resource_list_offset = align_up(
+            rathalos_offset + self.game_object_refs.len() as u64 * 0x10,
+            16,
+        );
+        let child_list_offset = align_up(
+            resource_list_offset + self.resource_names.len() as u64 * 8,
+            16,
+        );
+        let mut string_offset = child_list_offset + self.children.len() as u64 * 0x10;
+
+        let mut strings = vec![];
+        let mut string_offsets = vec![];
+        for name in self
+            .resource_names
+            .iter()
+            .chain(self.children.iter().map(|child| &child.name))
+        {
+            string_offsets.push(string_offset);
+            let len = strings.len();
+            strings.write_u16str(name)?;
+            string_offset += (strings.len() - len) as u64;
+        }
+        let rsz_offset = align_up(string_offset, 16);
+
+        let mut buf = Cursor::new(vec![]);
+        buf.write_magic(b"PFB\0")?;
+        buf.write_u32(u32::try_from(self.game_objects.len())?)?;
+        buf.write_u32(u32::try_from(self.resource_names.len())?)?;
+        buf.write_u32(u32::try_from(self.game_object_refs.len())?)?;
+        buf.write_u32(u32::try_from(self.children.len())?)?;
+        buf.write_u32(0)?;
+        buf.write_u64(rathalos_offset)?;
+        buf.write_u64(resource_list_offset)?;
+        buf.write_u64(child_list_offset)?;
+        buf.write_u64(rsz_offset)?;
+        for go in &self.game_objects {
+            buf.write_u32(go.object_index)?;
+            buf.write_u32(go.parent_index.unwrap_or(0xFFFFFFFF))?;
+            buf.write_u32(go.component_count)?;
+        }
+        for r in &self.game_object_refs {
+            buf.write_u32(r.object_id)?;
+            buf.write_u32(r.property_id)?;
+            buf.write_u32(r.array_index)?;
+            buf.write_u32(r.target_id)?;
+        }
+        pad16(&mut buf)?;
+        let (resource_string_offsets, child_string_offsets) =
+            string_offsets.split_at(self.resource_names.len());
+        for &offset in resource_string_offsets {
+            buf.write_u64(offset)?;
+        }
+        pad16(&mut buf)?;
+        for (child, &offset) in self.children.iter().zip(child_string_offsets) {
+            buf.write_u32(child.hash)?;
+            buf.write_u32(0)?;
+            buf.write_u64(offset)?;
+        }
+        buf.write_all(&strings)?;
+        pad16(&mut buf)?;
+        self.rsz.write(&mut buf)?;
+
+        file.write_all(&buf.into_inner())?;
+        Ok(())
+    }
 }
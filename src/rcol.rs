@@ -5,10 +5,11 @@ use crate::mesh::*;
 use crate::rsz::*;
 use anyhow::{bail, Context, Result};
 use nalgebra_glm::*;
+use std::any::Any;
 use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
 use std::io::{Cursor, Read, Seek, SeekFrom};
-use std::rc::*;
+use std::sync::Arc;
 
 pub enum UserData {
     RszRootIndex(usize),
@@ -31,9 +32,9 @@ impl UserData {
         }
     }
 
-    pub fn downcast<T: 'static>(self) -> Option<T> {
+    pub fn downcast<T: Any + Send + Sync>(self) -> Option<T> {
         if let UserData::Data(data) = self {
-            Rc::try_unwrap(data.downcast().ok()?).ok()
+            Arc::try_unwrap(data.downcast().ok()?).ok()
         } else {
             panic!();
         }
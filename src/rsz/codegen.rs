@@ -0,0 +1,446 @@
+use super::*;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct TdbJson {
+    types: Vec<TdbTypeJson>,
+}
+
+#[derive(Deserialize)]
+struct TdbTypeJson {
+    full_name: String,
+    ti_base: Option<usize>,
+    fields: Vec<TdbFieldJson>,
+}
+
+#[derive(Deserialize)]
+struct TdbFieldJson {
+    name: String,
+    ti: usize,
+    #[serde(rename = "via.clr.FieldFlag")]
+    flags: u16,
+    value: Option<TdbValueJson>,
+}
+
+#[derive(Deserialize)]
+enum TdbValueJson {
+    String(serde::de::IgnoredAny),
+    Bytes(Vec<u8>),
+}
+
+/// Enum type found in a TDB dump
+pub struct TdbEnum {
+    /// Rust integer type of the underlying value
+    base: &'static str,
+    values: Vec<(String, i64)>,
+}
+
+/// Loads all enum types from a TDB dumped as JSON by `read-tdb --json`
+pub fn load_tdb_enums(path: impl AsRef<Path>) -> Result<HashMap<String, TdbEnum>> {
+    const FIELD_STATIC: u16 = 0x0010;
+    const FIELD_LITERAL: u16 = 0x0040;
+
+    let path = path.as_ref();
+    let file = std::io::BufReader::new(
+        std::fs::File::open(path)
+            .with_context(|| format!("Failed to open TDB dump {}", path.display()))?,
+    );
+    let tdb: TdbJson = serde_json::from_reader(file)
+        .with_context(|| format!("Failed to parse TDB dump {}", path.display()))?;
+
+    let mut enums = HashMap::new();
+    for ty in &tdb.types {
+        let Some(base) = ty.ti_base.and_then(|ti| tdb.types.get(ti)) else {
+            continue;
+        };
+        if base.full_name != "System.Enum" {
+            continue;
+        }
+        let Some(value_field) = ty.fields.iter().find(|f| f.flags & FIELD_STATIC == 0) else {
+            continue;
+        };
+        let (base, signed) = match tdb.types[value_field.ti].full_name.as_str() {
+            "System.SByte" => ("i8", true),
+            "System.Byte" => ("u8", false),
+            "System.Int16" => ("i16", true),
+            "System.UInt16" => ("u16", false),
+            "System.Int32" => ("i32", true),
+            "System.UInt32" => ("u32", false),
+            "System.Int64" => ("i64", true),
+            "System.UInt64" => ("u64", false),
+            _ => continue,
+        };
+        let values = ty
+            .fields
+            .iter()
+            .filter(|f| f.flags & (FIELD_STATIC | FIELD_LITERAL) == FIELD_STATIC | FIELD_LITERAL)
+            .filter_map(|f| match &f.value {
+                Some(TdbValueJson::Bytes(bytes)) if !bytes.is_empty() && bytes.len() <= 8 => {
+                    let mut buf = if signed && bytes[bytes.len() - 1] & 0x80 != 0 {
+                        [0xFF; 8]
+                    } else {
+                        [0; 8]
+                    };
+                    buf[0..bytes.len()].copy_from_slice(bytes);
+                    Some((f.name.clone(), i64::from_le_bytes(buf)))
+                }
+                _ => None,
+            })
+            .collect();
+        enums.insert(ty.full_name.clone(), TdbEnum { base, values });
+    }
+    Ok(enums)
+}
+
+/// Rust name for a type symbol, made of the type names without the namespace.
+/// e.g. snow.data.AlchemyPatturnUserData.Param -> AlchemyPatturnUserDataParam
+fn rust_type_name(symbol: &str) -> String {
+    let segments: Vec<&str> = symbol.split('.').collect();
+    let first_type = segments
+        .iter()
+        .position(|s| s.starts_with(|c: char| c.is_ascii_uppercase()))
+        .unwrap_or(segments.len() - 1);
+    let name: String = segments[first_type..]
+        .concat()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("T{name}")
+    } else {
+        name
+    }
+}
+
+/// Rust name for a field, in snake case.
+/// e.g. _ParamList -> param_list, <Value>k__BackingField -> value
+fn rust_field_name(name: &str) -> String {
+    let name = name
+        .strip_prefix('<')
+        .and_then(|s| s.split_once('>'))
+        .map_or(name, |(inner, _)| inner);
+    let chars: Vec<char> = name.trim_start_matches('_').chars().collect();
+    let mut result = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !result.ends_with('_') {
+                result.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i != 0 && !result.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                result.push('_');
+            }
+        }
+        result.push(c.to_ascii_lowercase());
+    }
+    let result = result.trim_end_matches('_').to_owned();
+    if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("f_{result}")
+    } else if matches!(
+        result.as_str(),
+        "type"
+            | "ref"
+            | "move"
+            | "loop"
+            | "match"
+            | "mod"
+            | "fn"
+            | "impl"
+            | "self"
+            | "struct"
+            | "enum"
+            | "use"
+            | "in"
+            | "box"
+            | "const"
+            | "static"
+            | "where"
+            | "trait"
+    ) {
+        format!("{result}_")
+    } else {
+        result
+    }
+}
+
+fn format_version(version: u32) -> String {
+    if version == 0 {
+        "0".to_owned()
+    } else {
+        format!(
+            "{}_{:02}_{:02}",
+            version / 10000,
+            version / 100 % 100,
+            version % 100
+        )
+    }
+}
+
+/// Element type of an array or list symbol.
+/// e.g. snow.data.Foo[] -> snow.data.Foo, System.Collections.Generic.List`1<snow.data.Foo> -> snow.data.Foo
+fn element_symbol(symbol: &str) -> &str {
+    if let Some(element) = symbol.strip_suffix("[]") {
+        return element;
+    }
+    if let (Some(start), Some(end)) = (symbol.find('<'), symbol.rfind('>')) {
+        if start < end {
+            return &symbol[start + 1..end];
+        }
+    }
+    symbol
+}
+
+struct CodeGen<'a> {
+    schema: Option<&'a RszSchema>,
+    schema_by_name: HashMap<&'a str, &'a RszTypeSchema>,
+    enums: &'a HashMap<String, TdbEnum>,
+    version: u32,
+    structs: BTreeSet<&'a str>,
+    used_enums: BTreeSet<String>,
+}
+
+impl<'a> CodeGen<'a> {
+    /// Rust type of a single element, plus an optional trailing comment
+    fn element_type(&mut self, field: &RszFieldSchema) -> (String, Option<String>) {
+        let original = element_symbol(&field.original_type);
+        let int_type = match field.type_name.as_str() {
+            "S8" => Some("i8"),
+            "U8" => Some("u8"),
+            "S16" => Some("i16"),
+            "U16" => Some("u16"),
+            "S32" => Some("i32"),
+            "U32" => Some("u32"),
+            "S64" => Some("i64"),
+            "U64" => Some("u64"),
+            _ => None,
+        };
+        if let Some(int_type) = int_type {
+            if let Some(tdb_enum) = self.enums.get(original) {
+                if tdb_enum.base == int_type {
+                    self.used_enums.insert(original.to_owned());
+                    return (rust_type_name(original), None);
+                }
+            }
+            let comment = (!original.is_empty() && !original.starts_with("System."))
+                .then(|| original.to_owned());
+            return (int_type.to_owned(), comment);
+        }
+
+        let simple = match field.type_name.as_str() {
+            "Bool" => "bool",
+            "F32" => "f32",
+            "F64" => "f64",
+            "String" => "String",
+            "Resource" => "Option<String>",
+            "Guid" | "Uuid" | "GameObjectRef" => "Guid",
+            "Vec2" => "Vec2",
+            "Vec3" => "Vec3",
+            "Vec4" => "Vec4",
+            "Quaternion" => "Quat",
+            "Mat4" => "Mat4x4",
+            "Int3" => "IVec3",
+            "Float2" => "InlineArray<f32, 2>",
+            "Float3" => "InlineArray<f32, 3>",
+            "Float4" => "InlineArray<f32, 4>",
+            "Int2" => "InlineArray<i32, 2>",
+            "Int4" => "InlineArray<i32, 4>",
+            "Uint2" => "InlineArray<u32, 2>",
+            "Uint3" => "InlineArray<u32, 3>",
+            "Uint4" => "InlineArray<u32, 4>",
+            "Color" => "u32",
+            _ => "",
+        };
+        if !simple.is_empty() {
+            return (simple.to_owned(), None);
+        }
+
+        match field.type_name.as_str() {
+            "Object" | "UserData" if original.is_empty() => (
+                "u32".to_owned(),
+                Some(format!("TODO: {} of unknown type", field.type_name)),
+            ),
+            "Object" | "UserData" => {
+                if let Some((&name, _)) = self.schema_by_name.get_key_value(original) {
+                    if !RSZ_TYPE_MAP.contains_key(&hash_as_utf8(name)) {
+                        self.add_struct(name);
+                    }
+                }
+                let name = rust_type_name(original);
+                if field.type_name == "UserData" {
+                    (format!("ExternUser<{name}>"), None)
+                } else {
+                    (name, None)
+                }
+            }
+            _ => (
+                format!("RawBytes<{}, {}>", field.size, field.align),
+                Some(
+                    format!("TODO: {} {}", field.type_name, field.original_type)
+                        .trim_end()
+                        .to_owned(),
+                ),
+            ),
+        }
+    }
+
+    /// Queues the struct, and the unregistered structs it refers to
+    fn add_struct(&mut self, name: &'a str) {
+        if self.structs.contains(name) {
+            return;
+        }
+        let Some(&schema) = self.schema_by_name.get(name) else {
+            return;
+        };
+        self.structs.insert(name);
+        for field in &schema.fields {
+            if matches!(field.type_name.as_str(), "Object" | "UserData") {
+                let element = element_symbol(&field.original_type);
+                if let Some((&name, _)) = self.schema_by_name.get_key_value(element) {
+                    if !RSZ_TYPE_MAP.contains_key(&hash_as_utf8(name)) {
+                        self.add_struct(name)
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_struct(&mut self, output: &mut String, schema: &RszTypeSchema) -> Result<()> {
+        writeln!(output, "rsz_struct! {{")?;
+        writeln!(output, "    #[rsz(\"{}\",", schema.name)?;
+        writeln!(
+            output,
+            "        0x{:08X} = {}",
+            schema.crc,
+            format_version(self.version)
+        )?;
+        writeln!(output, "    )]")?;
+        writeln!(output, "    #[derive(Debug, Serialize)]")?;
+        writeln!(output, "    pub struct {} {{", rust_type_name(&schema.name))?;
+        let mut field_names = HashSet::new();
+        for field in &schema.fields {
+            let (element, comment) = self.element_type(field);
+            let ty = if field.array {
+                format!("Vec<{element}>")
+            } else {
+                element
+            };
+            let mut name = rust_field_name(&field.name);
+            while !field_names.insert(name.clone()) {
+                name += "_";
+            }
+            write!(output, "        pub {name}: {ty},")?;
+            if let Some(comment) = comment {
+                write!(output, " // {comment}")?;
+            }
+            writeln!(output)?;
+        }
+        writeln!(output, "    }}")?;
+        writeln!(output, "}}")?;
+        Ok(())
+    }
+
+    fn write_enum(&self, output: &mut String, symbol: &str, tdb_enum: &TdbEnum) -> Result<()> {
+        writeln!(output, "// {symbol}")?;
+        writeln!(output, "rsz_enum! {{")?;
+        writeln!(output, "    #[rsz({})]", tdb_enum.base)?;
+        writeln!(
+            output,
+            "    #[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]"
+        )?;
+        writeln!(output, "    pub enum {} {{", rust_type_name(symbol))?;
+        let mut seen_values = HashSet::new();
+        for (name, value) in &tdb_enum.values {
+            // Aliased values can't be told apart when reading
+            let prefix = if seen_values.insert(*value) {
+                ""
+            } else {
+                "// "
+            };
+            writeln!(output, "        {prefix}{name} = {value},")?;
+        }
+        writeln!(output, "    }}")?;
+        writeln!(output, "}}")?;
+        Ok(())
+    }
+}
+
+/// Generates `rsz_struct!` and `rsz_enum!` source for the type `name`,
+/// or all types in it if it is a namespace or an outer type.
+pub fn generate_rsz_source(
+    name: &str,
+    schema: Option<&RszSchema>,
+    enums: &HashMap<String, TdbEnum>,
+    version: u32,
+) -> Result<String> {
+    let schema_by_name: HashMap<&str, &RszTypeSchema> = schema
+        .into_iter()
+        .flat_map(|schema| schema.types())
+        .map(|ty| (ty.name.as_str(), ty))
+        .collect();
+    let matches = |symbol: &str| {
+        symbol == name
+            || symbol
+                .strip_prefix(name)
+                .is_some_and(|rest| rest.starts_with('.'))
+    };
+
+    let mut gen = CodeGen {
+        schema,
+        schema_by_name,
+        enums,
+        version,
+        structs: BTreeSet::new(),
+        used_enums: BTreeSet::new(),
+    };
+
+    let mut selected: Vec<&str> = gen
+        .schema_by_name
+        .keys()
+        .copied()
+        .filter(|symbol| matches(symbol))
+        .collect();
+    selected.sort();
+    for symbol in selected {
+        gen.add_struct(symbol);
+    }
+    gen.used_enums
+        .extend(enums.keys().filter(|symbol| matches(symbol)).cloned());
+
+    let mut output = String::new();
+    let structs: Vec<&str> = gen.structs.iter().copied().collect();
+    for symbol in &structs {
+        let schema = gen.schema_by_name[symbol];
+        gen.write_struct(&mut output, schema)?;
+        writeln!(output)?;
+    }
+    for symbol in &gen.used_enums {
+        gen.write_enum(&mut output, symbol, &enums[symbol])?;
+        writeln!(output)?;
+    }
+
+    if structs.is_empty() && gen.used_enums.is_empty() {
+        if gen.schema.is_none() && enums.is_empty() {
+            bail!("Neither RSZ schema nor TDB is given")
+        }
+        bail!("No type found for {name}")
+    }
+
+    if !structs.is_empty() {
+        writeln!(output, "// Register with:")?;
+        writeln!(output, "// r!(")?;
+        for symbol in &structs {
+            writeln!(output, "//     {},", rust_type_name(symbol))?;
+        }
+        writeln!(output, "// );")?;
+    }
+    Ok(output)
+}
@@ -13,6 +13,38 @@ macro_rules! rsz_inner {
     }
 }
 
+#[macro_export]
+macro_rules! rsz_inner_to {
+    ($self:ident, $rsz:ident, $($field_name:ident : $field_type:ty,)*) => {{
+        $(
+            <$field_type>::field_to_rsz(&$self.$field_name, $rsz).context(stringify!($field_name))?;
+        )*
+        Ok(())
+    }}
+}
+
+#[macro_export]
+macro_rules! rsz_inner_from_json {
+    ($json:ident, $value:ident, $($field_name:ident : $field_type:ty,)*) => {
+        Ok(Self {
+            $(
+                $field_name: $json.read_field::<$field_type>($value, stringify!($field_name))?,
+            )*
+        })
+    }
+}
+
+#[macro_export]
+macro_rules! rsz_inner_resolve {
+    ($self:ident, $resolver:ident, $($field_name:ident : $field_type:ty,)*) => {{
+        $(
+            <$field_type>::field_resolve_externs(&mut $self.$field_name, $resolver)
+                .context(stringify!($field_name))?;
+        )*
+        Ok(())
+    }}
+}
+
 #[macro_export]
 macro_rules! rsz_inner_trait {
     (rsz($symbol:tt $(,path=$singleton:literal)? $(,$vhash:literal=$version:literal)*),
@@ -24,6 +56,28 @@ macro_rules! rsz_inner_trait {
             fn from_rsz(rsz: &mut $crate::rsz::RszDeserializer) -> Result<Self> {
                 $crate::rsz_inner!(rsz, $($field_name : $field_type,)*)
             }
+
+            #[allow(unused_variables)]
+            fn from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                json.read_node(value, |json, value| {
+                    $crate::rsz_inner_from_json!(json, value, $($field_name : $field_type,)*)
+                })
+            }
+
+            #[allow(unused_variables)]
+            fn resolve_externs(&mut self, resolver: &mut $crate::rsz::ExternResolver) -> Result<()> {
+                $crate::rsz_inner_resolve!(self, resolver, $($field_name : $field_type,)*)
+            }
+        }
+
+        impl $crate::rsz::ToRsz for $struct_name {
+            #[allow(unused_variables)]
+            fn to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                $crate::rsz_inner_to!(self, rsz, $($field_name : $field_type,)*)
+            }
         }
 
         $(impl $crate::rsz::SingletonUser for $struct_name {
@@ -41,6 +95,33 @@ macro_rules! rsz_inner_trait {
             fn field_from_rsz(rsz: &mut $crate::rsz::RszDeserializer) -> Result<Self> {
                 $crate::rsz_inner!(rsz, $($field_name : $field_type,)*)
             }
+
+            const JSON_FLATTENED: bool = true;
+
+            #[allow(unused_variables)]
+            fn field_from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                json.read_object(value, |json, value| {
+                    $crate::rsz_inner_from_json!(json, value, $($field_name : $field_type,)*)
+                })
+            }
+
+            #[allow(unused_variables)]
+            fn field_resolve_externs(
+                &mut self,
+                resolver: &mut $crate::rsz::ExternResolver,
+            ) -> Result<()> {
+                $crate::rsz_inner_resolve!(self, resolver, $($field_name : $field_type,)*)
+            }
+        }
+
+        impl $crate::rsz::FieldToRsz for $struct_name {
+            #[allow(unused_variables)]
+            fn field_to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                $crate::rsz_inner_to!(self, rsz, $($field_name : $field_type,)*)
+            }
         }
     }
 }
@@ -80,6 +161,22 @@ macro_rules! rsz_enum_arm {
     };
 }
 
+#[macro_export]
+macro_rules! rsz_enum_arm_from_json {
+    ($json:ident, $inner:ident, $enum_name:ident, $variant:ident, $field:ty) => {
+        $enum_name::$variant(<$field>::field_from_json(
+            $json,
+            $inner.with_context(|| format!("Missing value for {}", stringify!($variant)))?,
+        )?)
+    };
+    ($json:ident, $inner:ident, $enum_name:ident, $variant:ident) => {{
+        if $inner.is_some() {
+            bail!("Unexpected value for {}", stringify!($variant))
+        }
+        $enum_name::$variant
+    }};
+}
+
 #[macro_export]
 macro_rules! rsz_enum_arm_rev_left {
     ($i: ident, $enum_name:ident, $variant:ident, $value:literal, $end_value:literal) => {
@@ -134,6 +231,15 @@ macro_rules! rsz_enum {
                     )*
                 }
             }
+
+            pub fn as_raw(&self) -> $base {
+                match *self {
+                    $(
+                        $crate::rsz_enum_arm_rev_left!(i,$enum_name, $variant, $value $(, $end_value)?)
+                        => $crate::rsz_enum_arm_rev_right!(i, $value $(, $end_value)?),
+                    )*
+                }
+            }
         }
 
         impl $crate::rsz::FieldFromRsz for $enum_name {
@@ -141,6 +247,38 @@ macro_rules! rsz_enum {
                 let raw = <$base>::field_from_rsz(rsz)?;
                 Self::from_raw(raw)
             }
+
+            /// Takes the variant name as written by serde, or the raw value
+            fn field_from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                let (name, inner) = match value {
+                    serde_json::Value::Number(_) => {
+                        return Self::from_raw(<$base>::field_from_json(json, value)?)
+                    }
+                    serde_json::Value::String(name) => (name.as_str(), None),
+                    value => {
+                        let (name, inner) = json.read_variant(value)?;
+                        (name, Some(inner))
+                    }
+                };
+                let v = match name {
+                    $(
+                        stringify!($variant) =>
+                        $crate::rsz_enum_arm_from_json!(json, inner, $enum_name, $variant $(, $field)?),
+                    )*
+                    _ => bail!("Unknown variant {} for enum {}", name, stringify!($enum_name))
+                };
+                // Checks the value of ranged variants against the raw mapping
+                Self::from_raw(v.as_raw())
+            }
+        }
+
+        impl $crate::rsz::FieldToRsz for $enum_name {
+            fn field_to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                self.as_raw().field_to_rsz(rsz)
+            }
         }
     };
 }
@@ -169,6 +307,37 @@ macro_rules! rsz_sumtype {
                 )*
                 bail!("No matching type for sum type {}", stringify!($enum_name))
             }
+
+            fn field_from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                let (name, inner) = json.read_variant(value)?;
+                json.enter(format!(".{name}"), |json| Ok(match name {
+                    $(
+                        stringify!($variant) =>
+                        $enum_name::$variant(<$variant_type>::field_from_json(json, inner)?),
+                    )*
+                    _ => bail!("Unknown variant {} for sum type {}", name, stringify!($enum_name))
+                }))
+            }
+
+            fn field_resolve_externs(
+                &mut self,
+                resolver: &mut $crate::rsz::ExternResolver,
+            ) -> Result<()> {
+                match self {
+                    $( $enum_name::$variant(v) => v.field_resolve_externs(resolver), )*
+                }
+            }
+        }
+
+        impl $crate::rsz::FieldToRsz for $enum_name {
+            fn field_to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                match self {
+                    $( $enum_name::$variant(v) => v.field_to_rsz(rsz), )*
+                }
+            }
         }
     }
 }
@@ -196,6 +365,26 @@ macro_rules! rsz_bitflags {
                     format!("Unknown bit flag {:08X} for {}", value, stringify!($name))
                 })
             }
+
+            fn field_from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                let mut result = <$name>::empty();
+                for flag in json.read_array(value, |json, flag| Ok(json.read_str(flag)?.to_owned()))? {
+                    result |= match flag.as_str() {
+                        $( stringify!($field_name) => <$name>::$field_name, )*
+                        _ => bail!("Unknown bit flag {} for {}", flag, stringify!($name))
+                    };
+                }
+                Ok(result)
+            }
+        }
+
+        impl $crate::rsz::FieldToRsz for $name {
+            fn field_to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                self.bits().field_to_rsz(rsz)
+            }
         }
 
         impl From<$name> for Vec<&'static str> {
@@ -225,6 +414,19 @@ macro_rules! rsz_newtype {
                 let raw = <$base>::field_from_rsz(rsz)?;
                 Ok($name(raw + $offset))
             }
+
+            fn field_from_json(
+                json: &mut $crate::rsz::RszJsonReader,
+                value: &serde_json::Value,
+            ) -> Result<Self> {
+                Ok($name(<$base>::field_from_json(json, value)?))
+            }
+        }
+
+        impl $crate::rsz::FieldToRsz for $name {
+            fn field_to_rsz(&self, rsz: &mut $crate::rsz::RszSerializer) -> Result<()> {
+                (self.0 - $offset).field_to_rsz(rsz)
+            }
         }
     )
 }
@@ -270,12 +472,34 @@ impl FieldFromRsz for bool {
             _ => bail!("Invalid bool"),
         }
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        value
+            .as_bool()
+            .with_context(|| format!("Expected bool, found {value}"))
+    }
+}
+
+impl FieldToRsz for bool {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.write_u8(u8::from(*self))
+    }
 }
 
 impl FieldFromRsz for u8 {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.read_u8()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for u8 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.write_u8(*self)
+    }
 }
 
 impl FieldFromRsz for u16 {
@@ -283,6 +507,17 @@ impl FieldFromRsz for u16 {
         rsz.cursor.seek_align_up(2)?;
         rsz.read_u16()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for u16 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(2);
+        rsz.write_u16(*self)
+    }
 }
 
 impl FieldFromRsz for u32 {
@@ -290,6 +525,17 @@ impl FieldFromRsz for u32 {
         rsz.cursor.seek_align_up(4)?;
         rsz.read_u32()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for u32 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_u32(*self)
+    }
 }
 
 impl FieldFromRsz for u64 {
@@ -297,12 +543,33 @@ impl FieldFromRsz for u64 {
         rsz.cursor.seek_align_up(8)?;
         rsz.read_u64()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for u64 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(8);
+        rsz.write_u64(*self)
+    }
 }
 
 impl FieldFromRsz for i8 {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.read_i8()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for i8 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.write_i8(*self)
+    }
 }
 
 impl FieldFromRsz for i16 {
@@ -310,6 +577,17 @@ impl FieldFromRsz for i16 {
         rsz.cursor.seek_align_up(2)?;
         rsz.read_i16()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for i16 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(2);
+        rsz.write_i16(*self)
+    }
 }
 
 impl FieldFromRsz for i32 {
@@ -317,6 +595,17 @@ impl FieldFromRsz for i32 {
         rsz.cursor.seek_align_up(4)?;
         rsz.read_i32()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for i32 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_i32(*self)
+    }
 }
 
 impl FieldFromRsz for i64 {
@@ -324,6 +613,17 @@ impl FieldFromRsz for i64 {
         rsz.cursor.seek_align_up(8)?;
         rsz.read_i64()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_integer(value)
+    }
+}
+
+impl FieldToRsz for i64 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(8);
+        rsz.write_i64(*self)
+    }
 }
 
 impl FieldFromRsz for f32 {
@@ -331,6 +631,35 @@ impl FieldFromRsz for f32 {
         rsz.cursor.seek_align_up(4)?;
         rsz.read_f32()
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(json.read_float(value)? as f32)
+    }
+}
+
+impl FieldToRsz for f32 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_f32(*self)
+    }
+}
+
+impl FieldFromRsz for f64 {
+    fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
+        rsz.cursor.seek_align_up(8)?;
+        rsz.read_f64()
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_float(value)
+    }
+}
+
+impl FieldToRsz for f64 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(8);
+        rsz.write_f64(*self)
+    }
 }
 
 // A wrapper of f32 that has bit-equality semantics
@@ -342,6 +671,17 @@ impl FieldFromRsz for MeqF32 {
         rsz.cursor.seek_align_up(4)?;
         Ok(MeqF32(rsz.read_f32()?))
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(MeqF32(f32::field_from_json(json, value)?))
+    }
+}
+
+impl FieldToRsz for MeqF32 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_f32(self.0)
+    }
 }
 
 impl std::cmp::PartialEq for MeqF32 {
@@ -387,6 +727,96 @@ impl<const ALIGN: u64> FieldFromRsz for Aligner<ALIGN> {
         rsz.cursor.seek_align_up(ALIGN)?;
         Ok(Aligner)
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        if !value.is_null() {
+            bail!("Expected null for padding, found {value}")
+        }
+        Ok(Aligner)
+    }
+}
+
+impl<const ALIGN: u64> FieldToRsz for Aligner<ALIGN> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(ALIGN);
+        Ok(())
+    }
+}
+
+/// N elements stored inline, unlike `[T; N]` which is read as a counted RSZ array.
+/// Used for via.Float2, via.Int4 and the like.
+#[allow(dead_code)] // Written by the code generated with gen-rsz
+#[derive(Debug, Clone)]
+pub struct InlineArray<T, const N: usize>(pub [T; N]);
+
+impl<T: FieldFromRsz + 'static, const N: usize> FieldFromRsz for InlineArray<T, N> {
+    fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
+        let v = (0..N)
+            .map(|_| T::field_from_rsz(rsz))
+            .collect::<Result<Vec<T>>>()?;
+        Ok(InlineArray(
+            v.try_into().map_err(|_| anyhow!("Expected {N} elements"))?,
+        ))
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(InlineArray(<[T; N]>::field_from_json(json, value)?))
+    }
+}
+
+impl<T: FieldToRsz + 'static, const N: usize> FieldToRsz for InlineArray<T, N> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        for v in &self.0 {
+            v.field_to_rsz(rsz)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Serialize, const N: usize> Serialize for InlineArray<T, N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0[..].serialize(serializer)
+    }
+}
+
+/// SIZE bytes of a field of unknown type, aligned to ALIGN
+#[allow(dead_code)] // Written by the code generated with gen-rsz
+#[derive(Debug, Clone)]
+pub struct RawBytes<const SIZE: usize, const ALIGN: u64>(pub [u8; SIZE]);
+
+impl<const SIZE: usize, const ALIGN: u64> FieldFromRsz for RawBytes<SIZE, ALIGN> {
+    fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
+        rsz.cursor.seek_align_up(ALIGN)?;
+        let mut bytes = [0; SIZE];
+        rsz.read_exact(&mut bytes)?;
+        Ok(RawBytes(bytes))
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(RawBytes(
+            InlineArray::<u8, SIZE>::field_from_json(json, value)?.0,
+        ))
+    }
+}
+
+impl<const SIZE: usize, const ALIGN: u64> FieldToRsz for RawBytes<SIZE, ALIGN> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(ALIGN);
+        rsz.write_all(&self.0)?;
+        Ok(())
+    }
+}
+
+impl<const SIZE: usize, const ALIGN: u64> Serialize for RawBytes<SIZE, ALIGN> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0[..].serialize(serializer)
+    }
 }
 
 impl<T: FromRsz + 'static> FieldFromRsz for T {
@@ -394,6 +824,24 @@ impl<T: FromRsz + 'static> FieldFromRsz for T {
         rsz.cursor.seek_align_up(4)?;
         rsz.get_child()
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        self.resolve_externs(resolver)
+    }
+
+    const JSON_FLATTENED: bool = true;
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        T::from_json(json, value)
+    }
+}
+
+impl<T: FromRsz + 'static> FieldToRsz for T {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        let index = rsz.put_child(self)?;
+        rsz.write_u32(index)
+    }
 }
 
 impl<T: FromRsz + 'static> FieldFromRsz for Option<T> {
@@ -401,12 +849,87 @@ impl<T: FromRsz + 'static> FieldFromRsz for Option<T> {
         rsz.cursor.seek_align_up(4)?;
         rsz.get_child_opt()
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        match self {
+            Some(child) => child.resolve_externs(resolver),
+            None => Ok(()),
+        }
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(T::from_json(json, value)?)),
+        }
+    }
+}
+
+impl<T: FromRsz + 'static> FieldToRsz for Option<T> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        let index = match self {
+            Some(child) => rsz.put_child(child)?,
+            None => 0,
+        };
+        rsz.write_u32(index)
+    }
+}
+
+impl<T: FromRsz + Clone + 'static> FieldFromRsz for Shared<T> {
+    fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
+        rsz.cursor.seek_align_up(4)?;
+        Ok(Shared(rsz.get_child_arc()?))
+    }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        resolver.resolve_shared(&mut self.0, |child, resolver| {
+            child.resolve_externs(resolver)
+        })
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(Shared(json.read_shared_node(value)?))
+    }
+}
+
+impl<T: FromRsz + 'static> FieldToRsz for Shared<T> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        let index = rsz.put_child_arc(&self.0)?;
+        rsz.write_u32(index)
+    }
 }
 
-impl<T: FromRsz + 'static> FieldFromRsz for Rc<T> {
+impl<T: FromRsz + Clone + 'static> FieldFromRsz for Option<Shared<T>> {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.cursor.seek_align_up(4)?;
-        rsz.get_child_rc()
+        Ok(rsz.get_child_arc_opt()?.map(Shared))
+    }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        match self {
+            Some(child) => child.field_resolve_externs(resolver),
+            None => Ok(()),
+        }
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(Shared::field_from_json(json, value)?)),
+        }
+    }
+}
+
+impl<T: FromRsz + 'static> FieldToRsz for Option<Shared<T>> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        let index = match self {
+            Some(child) => rsz.put_child_arc(&child.0)?,
+            None => 0,
+        };
+        rsz.write_u32(index)
     }
 }
 
@@ -418,6 +941,35 @@ impl<T: FieldFromRsz + 'static> FieldFromRsz for Vec<T> {
             .map(|_| T::field_from_rsz(rsz))
             .collect::<Result<Vec<_>>>()
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        for (i, item) in self.iter_mut().enumerate() {
+            item.field_resolve_externs(resolver)
+                .with_context(|| format!("[{i}]"))?;
+        }
+        Ok(())
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_array(value, T::field_from_json)
+    }
+}
+
+impl<T: FieldToRsz + 'static> FieldToRsz for Vec<T> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        self[..].field_to_rsz(rsz)
+    }
+}
+
+impl<T: FieldToRsz + 'static> FieldToRsz for [T] {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_u32(u32::try_from(self.len())?)?;
+        for item in self {
+            item.field_to_rsz(rsz)?;
+        }
+        Ok(())
+    }
 }
 
 impl FieldFromRsz for Vec<()> {
@@ -429,6 +981,21 @@ impl FieldFromRsz for Vec<()> {
         }
         Ok(vec![])
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        if json.read_array(value, |_, _| Ok(()))?.is_empty() {
+            Ok(vec![])
+        } else {
+            bail!("Placeholder array not empty")
+        }
+    }
+}
+
+impl FieldToRsz for Vec<()> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_u32(0)
+    }
 }
 
 impl<T: FieldFromRsz + 'static, const N: usize> FieldFromRsz for [T; N] {
@@ -437,12 +1004,44 @@ impl<T: FieldFromRsz + 'static, const N: usize> FieldFromRsz for [T; N] {
             .try_into()
             .map_err(|v: Vec<T>| anyhow!("Expected array size {}, found {}", N, v.len()))
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        for (i, item) in self.iter_mut().enumerate() {
+            item.field_resolve_externs(resolver)
+                .with_context(|| format!("[{i}]"))?;
+        }
+        Ok(())
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Vec::<T>::field_from_json(json, value)?
+            .try_into()
+            .map_err(|v: Vec<T>| anyhow!("Expected array size {}, found {}", N, v.len()))
+    }
+}
+
+impl<T: FieldToRsz + 'static, const N: usize> FieldToRsz for [T; N] {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        self[..].field_to_rsz(rsz)
+    }
 }
 
 impl FieldFromRsz for String {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         Option::<String>::field_from_rsz(rsz)?.context("Null String")
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(json.read_str(value)?.to_owned())
+    }
+}
+
+impl FieldToRsz for String {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        rsz.write_u32(u32::try_from(self.encode_utf16().count() + 1)?)?;
+        rsz.write_u16str(self)
+    }
 }
 
 impl FieldFromRsz for Option<String> {
@@ -460,6 +1059,25 @@ impl FieldFromRsz for Option<String> {
         }
         Ok(Some(String::from_utf16(&utf16)?))
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(json.read_str(value)?.to_owned())),
+        }
+    }
+}
+
+impl FieldToRsz for Option<String> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        match self {
+            Some(s) => s.field_to_rsz(rsz),
+            None => {
+                rsz.align_up(4);
+                rsz.write_u32(0)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -469,6 +1087,22 @@ impl<T: FromRsz> FieldFromRsz for Flatten<T> {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         Ok(Flatten(T::from_rsz(rsz)?))
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        self.0.resolve_externs(resolver)
+    }
+
+    const JSON_FLATTENED: bool = true;
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Ok(Flatten(T::from_json(json, value)?))
+    }
+}
+
+impl<T: ToRsz> FieldToRsz for Flatten<T> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        self.0.to_rsz(rsz)
+    }
 }
 
 impl<T> Deref for Flatten<T> {
@@ -492,6 +1126,36 @@ impl<T: FieldFromRsz, const MIN: u32, const MAX: u32> FieldFromRsz for Versioned
             None
         }))
     }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        match &mut self.0 {
+            Some(v) => v.field_resolve_externs(resolver),
+            None => Ok(()),
+        }
+    }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        let version = json.version();
+        if version >= MIN && version <= MAX {
+            Ok(Versioned(Some(T::field_from_json(json, value)?)))
+        } else if value.is_null() {
+            Ok(Versioned(None))
+        } else {
+            bail!("Unexpected field for version {}", version)
+        }
+    }
+}
+
+impl<T: FieldToRsz, const MIN: u32, const MAX: u32> FieldToRsz for Versioned<T, MIN, MAX> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        let version = rsz.version();
+        match (version >= MIN && version <= MAX, &self.0) {
+            (true, Some(v)) => v.field_to_rsz(rsz),
+            (false, None) => Ok(()),
+            (true, None) => bail!("Missing field for version {}", version),
+            (false, Some(_)) => bail!("Unexpected field for version {}", version),
+        }
+    }
 }
 
 #[macro_export]
@@ -515,6 +1179,32 @@ macro_rules! rsz_versioned_choice {
                     _ => bail!("Unknown version for {}: {}", stringify!($enum_name), version)
                 })
             }
+
+            fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+                let version = json.version();
+                let (name, inner) = json.read_variant(value)?;
+                json.enter(format!(".{name}"), |json| Ok(match name {
+                    $(
+                        stringify!($variant) if matches!(version, $version) =>
+                        $enum_name::$variant(<$field>::field_from_json(json, inner)?),
+                    )*
+                    _ => bail!("Variant {} of {} doesn't match version {}", name, stringify!($enum_name), version)
+                }))
+            }
+
+            fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+                match self {
+                    $($enum_name::$variant(v) => v.field_resolve_externs(resolver),)*
+                }
+            }
+        }
+
+        impl FieldToRsz for $enum_name {
+            fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+                match self {
+                    $($enum_name::$variant(v) => v.field_to_rsz(rsz),)*
+                }
+            }
         }
     }
 }
@@ -547,6 +1237,18 @@ impl FieldFromRsz for Guid {
         rsz.read_exact(&mut bytes)?;
         Ok(Guid { bytes })
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        json.read_str(value)?.parse()
+    }
+}
+
+impl FieldToRsz for Guid {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(8);
+        rsz.write_all(&self.bytes)?;
+        Ok(())
+    }
 }
 
 impl From<Guid> for String {
@@ -573,12 +1275,43 @@ impl From<Guid> for String {
     }
 }
 
+impl std::str::FromStr for Guid {
+    type Err = anyhow::Error;
+
+    /// Parses the form written by `From<Guid> for String`
+    fn from_str(s: &str) -> Result<Guid> {
+        let hex: String = s.chars().filter(|&c| c != '-').collect();
+        if hex.len() != 32 || s.len() != 36 || !hex.is_ascii() {
+            bail!("Bad GUID {s}")
+        }
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .with_context(|| format!("Bad GUID {s}"))?;
+        }
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+        Ok(Guid { bytes })
+    }
+}
+
 impl FieldFromRsz for Quat {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.cursor.seek_align_up(16)?;
         let v = rsz.read_f32vec4()?;
         Ok(Quat::from(v))
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected Quat, found {value}"))
+    }
+}
+
+impl FieldToRsz for Quat {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        self.coords.field_to_rsz(rsz)
+    }
 }
 
 impl FieldFromRsz for Vec4 {
@@ -587,6 +1320,20 @@ impl FieldFromRsz for Vec4 {
         let v = rsz.read_f32vec4()?;
         Ok(v)
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected Vec4, found {value}"))
+    }
+}
+
+impl FieldToRsz for Vec4 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(16);
+        for &v in self.iter() {
+            rsz.write_f32(v)?;
+        }
+        Ok(())
+    }
 }
 
 impl FieldFromRsz for Vec3 {
@@ -596,6 +1343,21 @@ impl FieldFromRsz for Vec3 {
         rsz.cursor.seek_align_up(16)?;
         Ok(v)
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected Vec3, found {value}"))
+    }
+}
+
+impl FieldToRsz for Vec3 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(16);
+        for &v in self.iter() {
+            rsz.write_f32(v)?;
+        }
+        rsz.align_up(16);
+        Ok(())
+    }
 }
 
 impl FieldFromRsz for IVec3 {
@@ -606,6 +1368,20 @@ impl FieldFromRsz for IVec3 {
         let z = rsz.read_i32()?;
         Ok(vec3(x, y, z))
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected IVec3, found {value}"))
+    }
+}
+
+impl FieldToRsz for IVec3 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        for &v in self.iter() {
+            rsz.write_i32(v)?;
+        }
+        Ok(())
+    }
 }
 
 impl FieldFromRsz for Vec2 {
@@ -615,6 +1391,21 @@ impl FieldFromRsz for Vec2 {
         rsz.cursor.seek_align_up(16)?;
         Ok(v)
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected Vec2, found {value}"))
+    }
+}
+
+impl FieldToRsz for Vec2 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(16);
+        for &v in self.iter() {
+            rsz.write_f32(v)?;
+        }
+        rsz.align_up(16);
+        Ok(())
+    }
 }
 
 impl FieldFromRsz for Mat4x4 {
@@ -623,4 +1414,18 @@ impl FieldFromRsz for Mat4x4 {
         let v = rsz.read_f32m4x4()?;
         Ok(v)
     }
+
+    fn field_from_json(_json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        Self::deserialize(value).with_context(|| format!("Expected Mat4x4, found {value}"))
+    }
+}
+
+impl FieldToRsz for Mat4x4 {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(16);
+        for &v in self.iter() {
+            rsz.write_f32(v)?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,74 @@
+use super::*;
+use once_cell::sync::OnceCell;
+use std::collections::BTreeSet;
+
+/// Versions tried for CRCs not registered yet, by type hash and CRC
+static RSZ_CRC_CANDIDATES: OnceCell<HashMap<(u32, u32), u32>> = OnceCell::new();
+
+/// Reads instances of the types with the given hash and unregistered CRC as the given version,
+/// to check whether the layout of that version still applies. Can only be called once.
+pub fn install_crc_candidates(candidates: HashMap<(u32, u32), u32>) -> Result<()> {
+    RSZ_CRC_CANDIDATES
+        .set(candidates)
+        .map_err(|_| anyhow!("RSZ CRC candidates are already set"))
+}
+
+pub(super) fn candidate_version(hash: u32, crc: u32) -> Option<u32> {
+    RSZ_CRC_CANDIDATES.get()?.get(&(hash, crc)).copied()
+}
+
+/// A registered type found with a CRC that none of its versions has
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrcMismatch {
+    pub symbol: String,
+    pub hash: u32,
+    pub crc: u32,
+    /// The newest of the registered versions
+    pub latest_version: u32,
+    /// Files containing the type with this CRC, by the hex of their path hash
+    pub files: BTreeSet<String>,
+}
+
+/// Mismatched CRCs gathered by `scan-rsz`, sorted by symbol and CRC
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CrcReport {
+    pub mismatches: Vec<CrcMismatch>,
+}
+
+impl CrcReport {
+    fn insert(&mut self, mismatch: CrcMismatch) {
+        let key = (mismatch.symbol.as_str(), mismatch.crc);
+        match self
+            .mismatches
+            .binary_search_by(|m| (m.symbol.as_str(), m.crc).cmp(&key))
+        {
+            Ok(i) => self.mismatches[i].files.extend(mismatch.files),
+            Err(i) => self.mismatches.insert(i, mismatch),
+        }
+    }
+
+    pub fn add(&mut self, rsz: &Rsz, file: &str) {
+        for td in &rsz.type_descriptors {
+            let Some(type_info) = RSZ_TYPE_MAP.get(&td.hash) else {
+                continue;
+            };
+            if type_info.versions.is_empty() || type_info.versions.contains_key(&td.crc) {
+                continue;
+            }
+            self.insert(CrcMismatch {
+                symbol: type_info.symbol.to_owned(),
+                hash: td.hash,
+                crc: td.crc,
+                latest_version: type_info.versions.values().copied().max().unwrap(),
+                files: BTreeSet::from([file.to_owned()]),
+            })
+        }
+    }
+
+    pub fn merge(mut self, other: CrcReport) -> CrcReport {
+        for mismatch in other.mismatches {
+            self.insert(mismatch)
+        }
+        self
+    }
+}
@@ -0,0 +1,302 @@
+use super::*;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+struct JsonObjectFrame {
+    object: *const Map<String, Value>,
+    /// Keys read by the struct, or by structs flattened into it
+    used: HashSet<String>,
+}
+
+/// Reads RSZ instances back from the JSON written by `AnyRsz::to_json`,
+/// validating it against the registered types and the schema.
+///
+/// Errors are reported with the JSON path of the offending value, such as `$.list[2].name`.
+pub struct RszJsonReader<'a> {
+    /// Version of the game, used to pick the CRC of types not in `crc_hint`
+    game_version: u32,
+    crc_hint: &'a HashMap<u32, u32>,
+    /// Hash and CRC of the referenced USER files, by path
+    extern_hint: &'a HashMap<String, (u32, u32)>,
+    /// Version of the instance being read
+    version: u32,
+    path: Vec<String>,
+    objects: Vec<JsonObjectFrame>,
+    /// Nodes written as `{"$shared": id, ...}`, for the following `{"$ref": id}`
+    shared: HashMap<u64, AnyRsz>,
+}
+
+impl<'a> RszJsonReader<'a> {
+    pub fn new(
+        game_version: u32,
+        crc_hint: &'a HashMap<u32, u32>,
+        extern_hint: &'a HashMap<String, (u32, u32)>,
+    ) -> Self {
+        RszJsonReader {
+            game_version,
+            crc_hint,
+            extern_hint,
+            version: game_version,
+            path: vec![],
+            objects: vec![],
+            shared: HashMap::new(),
+        }
+    }
+
+    /// Reads one root instance. `name` is the start of the JSON path in errors.
+    pub fn read_root(&mut self, value: &Value, name: &str) -> Result<AnyRsz> {
+        self.path = vec![name.to_owned()];
+        self.objects.clear();
+        self.read_any(value, None)
+            .with_context(|| format!("At {}", self.path.concat()))
+    }
+
+    pub(super) fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub(super) fn enter<R>(
+        &mut self,
+        segment: String,
+        f: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        self.path.push(segment);
+        // The path is kept on error, so that the outermost caller can report it
+        let result = f(self)?;
+        self.path.pop();
+        Ok(result)
+    }
+
+    pub(super) fn read_object<R>(
+        &mut self,
+        value: &Value,
+        f: impl FnOnce(&mut Self, &Value) -> Result<R>,
+    ) -> Result<R> {
+        let Value::Object(object) = value else {
+            bail!("Expected object, found {}", json_kind(value))
+        };
+        let key = object as *const _;
+        // Structs flattened into their parent share the frame of the parent
+        let outermost = self.objects.last().is_none_or(|frame| frame.object != key);
+        if outermost {
+            self.objects.push(JsonObjectFrame {
+                object: key,
+                used: HashSet::new(),
+            });
+        }
+        let result = f(self, value)?;
+        if outermost {
+            let frame = self.objects.pop().unwrap();
+            if let Some(unknown) = object
+                .keys()
+                .find(|key| *key != "$type" && !frame.used.contains(*key))
+            {
+                bail!("Unknown field {unknown}")
+            }
+        }
+        Ok(result)
+    }
+
+    /// Marks the key of the object being read by `read_object` as known
+    pub(super) fn mark_field(&mut self, name: &str) {
+        if let Some(frame) = self.objects.last_mut() {
+            frame.used.insert(name.to_owned());
+        }
+    }
+
+    /// Reads a field of the object being read by `read_object`
+    pub(super) fn read_field<T: FieldFromRsz>(&mut self, parent: &Value, name: &str) -> Result<T> {
+        self.mark_field(name);
+        match parent.get(name) {
+            Some(value) => self.enter(format!(".{name}"), |json| T::field_from_json(json, value)),
+            None if T::JSON_FLATTENED => T::field_from_json(self, parent),
+            None => bail!("Missing field {name}"),
+        }
+    }
+
+    /// Reads an instance of a registered type, as the version given by its CRC
+    pub(super) fn read_node<T: FromRsz>(
+        &mut self,
+        value: &Value,
+        f: impl FnOnce(&mut Self, &Value) -> Result<T>,
+    ) -> Result<T> {
+        if let Some(symbol) = value.get("$type") {
+            if symbol.as_str() != Some(T::SYMBOL) {
+                bail!("Expected type {}, found {}", T::SYMBOL, symbol)
+            }
+        }
+        let outer_version = self.version;
+        if let Some(type_info) = RSZ_TYPE_MAP.get(&T::type_hash()) {
+            self.version = self.type_version(type_info)?;
+        }
+        let result = self.read_object(value, f)?;
+        self.version = outer_version;
+        Ok(result)
+    }
+
+    fn type_version(&self, type_info: &RszTypeInfo) -> Result<u32> {
+        if type_info.versions.is_empty() {
+            return Ok(self.game_version);
+        }
+        let crc = pick_crc(type_info, self.game_version, self.crc_hint)?;
+        Ok(type_info.versions[&crc])
+    }
+
+    pub(super) fn read_array<T>(
+        &mut self,
+        value: &Value,
+        mut f: impl FnMut(&mut Self, &Value) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let Value::Array(array) = value else {
+            bail!("Expected array, found {}", json_kind(value))
+        };
+        array
+            .iter()
+            .enumerate()
+            .map(|(i, item)| self.enter(format!("[{i}]"), |json| f(json, item)))
+            .collect()
+    }
+
+    pub(super) fn read_integer<T: TryFrom<i64> + TryFrom<u64>>(&self, value: &Value) -> Result<T> {
+        let v = if let Some(v) = value.as_i64() {
+            T::try_from(v).ok()
+        } else if let Some(v) = value.as_u64() {
+            T::try_from(v).ok()
+        } else {
+            bail!("Expected integer, found {}", value)
+        };
+        v.with_context(|| format!("{} out of range for {}", value, type_name::<T>()))
+    }
+
+    /// NaN and infinities are written as null
+    pub(super) fn read_float(&self, value: &Value) -> Result<f64> {
+        match value {
+            Value::Null => Ok(f64::NAN),
+            value => value
+                .as_f64()
+                .with_context(|| format!("Expected number, found {}", json_kind(value))),
+        }
+    }
+
+    pub(super) fn read_str<'v>(&self, value: &'v Value) -> Result<&'v str> {
+        value
+            .as_str()
+            .with_context(|| format!("Expected string, found {}", json_kind(value)))
+    }
+
+    /// Reads a value written by serde as an externally tagged enum, giving the variant name
+    pub(super) fn read_variant<'v>(&self, value: &'v Value) -> Result<(&'v str, &'v Value)> {
+        match value {
+            Value::Object(object) if object.len() == 1 => {
+                let (name, inner) = object.iter().next().unwrap();
+                Ok((name.as_str(), inner))
+            }
+            _ => bail!("Expected object with a single variant, found {}", value),
+        }
+    }
+
+    /// Reads a node that may be written as `{"$shared": id, "$value": ...}` or `{"$ref": id}`
+    fn read_shared(
+        &mut self,
+        value: &Value,
+        f: impl FnOnce(&mut Self, &Value) -> Result<AnyRsz>,
+    ) -> Result<AnyRsz> {
+        if let Some(id) = value.get("$ref") {
+            let id = id.as_u64().context("Bad $ref")?;
+            return self
+                .shared
+                .get(&id)
+                .cloned()
+                .with_context(|| format!("$ref {id} before its $shared"));
+        }
+        if let Some(id) = value.get("$shared") {
+            let id = id.as_u64().context("Bad $shared")?;
+            let inner = value.get("$value").context("$shared without $value")?;
+            let node = self.enter(".$value".to_owned(), |json| f(json, inner))?;
+            if self.shared.insert(id, node.clone()).is_some() {
+                bail!("Duplicate $shared {id}")
+            }
+            return Ok(node);
+        }
+        f(self, value)
+    }
+
+    pub(super) fn read_shared_node<T: FromRsz + 'static>(
+        &mut self,
+        value: &Value,
+    ) -> Result<Arc<T>> {
+        self.read_shared(value, |json, value| {
+            let type_info = RSZ_TYPE_MAP
+                .get(&T::type_hash())
+                .with_context(|| format!("Unregistered type {}", T::SYMBOL))?;
+            let any = Arc::new(T::from_json(json, value)?);
+            Ok(AnyRsz { any, type_info })
+        })?
+        .downcast()
+    }
+
+    /// Reads a node of any type, named by its `$type`.
+    /// A string is a path to a USER file, with its root of type `extern_type` if not hinted.
+    pub(super) fn read_any(&mut self, value: &Value, extern_type: Option<u32>) -> Result<AnyRsz> {
+        if let Value::String(path) = value {
+            return Ok(AnyRsz::new_extern(self.extern_path(path, extern_type)?));
+        }
+        self.read_shared(value, |json, value| {
+            if let Some(hash) = value.get("$hash") {
+                bail!("Opaque instance of type {hash} can't be written back")
+            }
+            let symbol = value
+                .get("$type")
+                .context("Missing $type")?
+                .as_str()
+                .context("Bad $type")?;
+            let hash = hash_as_utf8(symbol);
+            let type_info = RSZ_TYPE_MAP
+                .get(&hash)
+                .or_else(|| schema_type_info(hash))
+                .or_else(|| schema_type_info_by_symbol(symbol))
+                .with_context(|| format!("Unknown type {symbol}"))?;
+            let outer_version = json.version;
+            json.version = json.type_version(type_info)?;
+            let node = (type_info.from_json)(json, value, type_info)?;
+            json.version = outer_version;
+            Ok(node)
+        })
+    }
+
+    pub(super) fn extern_path(&self, path: &str, type_hash: Option<u32>) -> Result<ExternPath> {
+        let (hash, crc) = if let Some(&hint) = self.extern_hint.get(path) {
+            hint
+        } else {
+            let hash = type_hash.with_context(|| {
+                format!(
+                    "Unknown type of the referenced {path}. \
+                    The original file can be given as the template to provide it"
+                )
+            })?;
+            let crc = match RSZ_TYPE_MAP.get(&hash).or_else(|| schema_type_info(hash)) {
+                Some(type_info) => pick_crc(type_info, self.game_version, self.crc_hint)?,
+                None => *self.crc_hint.get(&hash).with_context(|| {
+                    format!("Unknown CRC of the type {hash:08X} referenced by {path}")
+                })?,
+            };
+            (hash, crc)
+        };
+        Ok(ExternPath {
+            path: path.to_owned(),
+            hash,
+            crc,
+        })
+    }
+}
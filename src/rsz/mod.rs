@@ -3,16 +3,19 @@ mod anger_data;
 mod armor;
 mod award;
 mod boss_init_set_data;
+mod codegen;
 mod collision;
 mod common;
 mod condition_damage_data;
 mod condition_damage_preset;
+mod crc_report;
 mod custom_buildup;
 mod data_base;
 mod data_tune;
 mod dlc;
 mod facility;
 mod item;
+mod json;
 mod lot;
 mod map;
 mod meat_data;
@@ -23,6 +26,7 @@ mod parts_break_data;
 mod quest_data;
 mod random_quest;
 mod scene;
+mod schema;
 mod skill;
 mod weapon;
 
@@ -31,16 +35,19 @@ pub use anger_data::*;
 pub use armor::*;
 pub use award::*;
 pub use boss_init_set_data::*;
+pub use codegen::*;
 pub use collision::*;
 pub use common::*;
 pub use condition_damage_data::*;
 pub use condition_damage_preset::*;
+pub use crc_report::*;
 pub use custom_buildup::*;
 pub use data_base::*;
 pub use data_tune::*;
 pub use dlc::*;
 pub use facility::*;
 pub use item::*;
+pub use json::*;
 pub use lot::*;
 pub use map::*;
 pub use meat_data::*;
@@ -51,9 +58,11 @@ pub use parts_break_data::*;
 pub use quest_data::*;
 pub use random_quest::*;
 pub use scene::*;
+pub use schema::*;
 pub use skill::*;
 pub use weapon::*;
 
+use crate::align::{align_up, pad16};
 use crate::file_ext::*;
 use crate::hash::*;
 use anyhow::{anyhow, bail, Context, Result};
@@ -64,9 +73,9 @@ use std::any::*;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::ops::Deref;
-use std::rc::*;
+use std::sync::Arc;
 
 /****
 
@@ -194,51 +203,81 @@ impl Rsz {
         })
     }
 
+    fn extern_node(&self, index: usize) -> Result<Option<AnyRsz>> {
+        let Some(slot_extern) = self.extern_slots.get(&u32::try_from(index)?) else {
+            return Ok(None);
+        };
+        let TypeDescriptor { hash, crc } = self.type_descriptors[index];
+        if slot_extern.hash != hash {
+            bail!("Extern hash mismatch")
+        }
+        Ok(Some(AnyRsz::new_extern(ExternPath {
+            path: slot_extern.path.clone(),
+            hash,
+            crc,
+        })))
+    }
+
+    /// Reads one instance at the cursor. The indices of the children moved out of `node_buf`
+    /// are added to `moved`, so that they can be restored if this fails.
+    fn read_node(
+        node_buf: &mut [Option<AnyRsz>],
+        cursor: &mut Cursor<&Vec<u8>>,
+        &TypeDescriptor { hash, crc }: &TypeDescriptor,
+        version_hint: Option<u32>,
+        moved: &mut Vec<usize>,
+    ) -> Result<AnyRsz> {
+        let pos = cursor.tell().unwrap();
+        let type_info = find_type_info(hash, crc).with_context(|| {
+            let mut buffer = [0; 0x100];
+            let read = cursor.read(&mut buffer).unwrap();
+            format!(
+                "Unsupported type {:08X} at {:08X}: {:02X?}...",
+                hash,
+                pos,
+                &buffer[0..read]
+            )
+        })?;
+        let version = if type_info.versions.is_empty() {
+            version_hint.unwrap_or(0)
+        } else {
+            known_version(type_info, crc).with_context(|| {
+                format!(
+                    "Unknown type CRC {:08X} for type {:08X} ({}) at {:08X}",
+                    crc, hash, type_info.symbol, pos
+                )
+            })?
+        };
+        let mut rsz_deserializer = RszDeserializer {
+            node_buf,
+            cursor,
+            version,
+            moved,
+        };
+        let node = (type_info.deserializer)(&mut rsz_deserializer, type_info);
+        node.with_context(|| {
+            format!(
+                "Error deserializing for type {} at {:08X}",
+                type_info.symbol, pos
+            )
+        })
+    }
+
     pub fn deserialize(&self, version_hint: Option<u32>) -> Result<Vec<AnyRsz>> {
         let mut node_buf: Vec<Option<AnyRsz>> = vec![None];
         let mut cursor = Cursor::new(&self.data);
-        for (i, &TypeDescriptor { hash, crc }) in self.type_descriptors.iter().enumerate().skip(1) {
-            if let Some(slot_extern) = self.extern_slots.get(&u32::try_from(i)?) {
-                if slot_extern.hash != hash {
-                    bail!("Extern hash mismatch")
-                }
-                node_buf.push(Some(AnyRsz::new_extern(slot_extern.path.clone())));
+        for (i, type_descriptor) in self.type_descriptors.iter().enumerate().skip(1) {
+            if let Some(node) = self.extern_node(i)? {
+                node_buf.push(Some(node));
                 continue;
             }
-
-            let pos = cursor.tell().unwrap();
-            let type_info = RSZ_TYPE_MAP.get(&hash).with_context(|| {
-                let mut buffer = [0; 0x100];
-                let read = cursor.read(&mut buffer).unwrap();
-                format!(
-                    "Unsupported type {:08X} at {:08X}: {:02X?}...",
-                    hash,
-                    pos,
-                    &buffer[0..read]
-                )
-            })?;
-            let version = if type_info.versions.is_empty() {
-                version_hint.unwrap_or(0)
-            } else {
-                *type_info.versions.get(&crc).with_context(|| {
-                    format!(
-                        "Unknown type CRC {:08X} for type {:08X} ({}) at {:08X}",
-                        crc, hash, type_info.symbol, pos
-                    )
-                })?
-            };
-            let mut rsz_deserializer = RszDeserializer {
-                node_buf: &mut node_buf,
-                cursor: &mut cursor,
-                version,
-            };
-            let node =
-                (type_info.deserializer)(&mut rsz_deserializer, type_info).with_context(|| {
-                    format!(
-                        "Error deserializing for type {} at {:08X}",
-                        type_info.symbol, pos
-                    )
-                })?;
+            let node = Self::read_node(
+                &mut node_buf,
+                &mut cursor,
+                type_descriptor,
+                version_hint,
+                &mut vec![],
+            )?;
             node_buf.push(Some(node));
         }
 
@@ -256,7 +295,7 @@ impl Rsz {
 
         for (i, node) in node_buf.into_iter().enumerate() {
             if let Some(node) = node {
-                if Rc::strong_count(&node.any) == 1 {
+                if !node.is_referenced() {
                     bail!("Left over node {} ({})", i, node.symbol())
                 }
             }
@@ -271,6 +310,146 @@ impl Rsz {
         Ok(result)
     }
 
+    /// Reads again an instance moved out of `node_buf` by an instance that failed,
+    /// along with the children it moved itself
+    fn restore_node(
+        &self,
+        node_buf: &mut [Option<AnyRsz>],
+        read_at: &[Option<(u64, Vec<usize>)>],
+        index: usize,
+        version_hint: Option<u32>,
+    ) -> Result<()> {
+        let (pos, moved) = read_at[index]
+            .as_ref()
+            .with_context(|| format!("Instance {index} was never read"))?;
+        for &child in moved {
+            self.restore_node(node_buf, read_at, child, version_hint)?;
+        }
+        let mut cursor = Cursor::new(&self.data);
+        cursor.set_position(*pos);
+        let node = Self::read_node(
+            node_buf,
+            &mut cursor,
+            &self.type_descriptors[index],
+            version_hint,
+            &mut vec![],
+        )?;
+        node_buf[index] = Some(node);
+        Ok(())
+    }
+
+    fn opaque_node(&self, index: usize, start: u64, end: u64) -> Result<AnyRsz> {
+        let TypeDescriptor { hash, crc } = self.type_descriptors[index];
+        let data = self.data[usize::try_from(start)?..usize::try_from(end)?].to_vec();
+        Ok(AnyRsz::new_opaque(OpaqueRsz { hash, crc, data }))
+    }
+
+    /// Like `deserialize`, but instances that can't be read are kept as `OpaqueRsz`
+    /// instead of failing the whole block.
+    ///
+    /// An instance is only skipped if its size is known without reading it, that is
+    /// if the installed schema describes it with a fixed layout. Otherwise, the data after it
+    /// can't be located, so reading stops and the remaining instances are reported as unread.
+    pub fn deserialize_lenient(
+        &self,
+        version_hint: Option<u32>,
+    ) -> Result<(Vec<AnyRsz>, RszSkipReport)> {
+        let data_len = self.data.len() as u64;
+        let mut report = RszSkipReport::default();
+        let mut node_buf: Vec<Option<AnyRsz>> = vec![None];
+        // Position and moved children of each instance read, to restore them
+        // if their parent fails
+        let mut read_at: Vec<Option<(u64, Vec<usize>)>> = vec![None];
+        let mut cursor = Cursor::new(&self.data);
+        for (i, type_descriptor) in self.type_descriptors.iter().enumerate().skip(1) {
+            if let Some(node) = self.extern_node(i)? {
+                node_buf.push(Some(node));
+                read_at.push(None);
+                continue;
+            }
+
+            let start = cursor.tell()?;
+            let mut moved = vec![];
+            let error = match Self::read_node(
+                &mut node_buf,
+                &mut cursor,
+                type_descriptor,
+                version_hint,
+                &mut moved,
+            ) {
+                Ok(node) => {
+                    node_buf.push(Some(node));
+                    read_at.push(Some((start, moved)));
+                    continue;
+                }
+                Err(e) => e,
+            };
+
+            for index in moved {
+                self.restore_node(&mut node_buf, &read_at, index, version_hint)?;
+            }
+            let TypeDescriptor { hash, crc } = *type_descriptor;
+            let reason = format!("{error:#}");
+            match schema_fixed_end(hash, crc, start).filter(|&end| end <= data_len) {
+                Some(end) => {
+                    node_buf.push(Some(self.opaque_node(i, start, end)?));
+                    read_at.push(None);
+                    report.skipped.push(RszSkip {
+                        index: i,
+                        hash,
+                        crc,
+                        offset: start,
+                        size: end - start,
+                        reason,
+                    });
+                    cursor.set_position(end);
+                }
+                None => {
+                    report.unread = Some(RszUnread {
+                        index: i,
+                        hash,
+                        crc,
+                        offset: start,
+                        count: self.type_descriptors.len() - i,
+                        reason,
+                    });
+                    break;
+                }
+            }
+        }
+        node_buf.resize(self.type_descriptors.len(), None);
+
+        if report.unread.is_none() {
+            report.leftover_data = data_len - cursor.tell()?;
+        }
+
+        let result = self
+            .roots
+            .iter()
+            .map(|&root| {
+                let index = usize::try_from(root)?;
+                let slot = node_buf.get_mut(index).context("Root index out of bound")?;
+                Ok(match slot.take() {
+                    Some(node) => node,
+                    None => {
+                        report.missing_roots.push(index);
+                        self.opaque_node(index, 0, 0)?
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, node) in node_buf.into_iter().enumerate() {
+            if let Some(node) = node {
+                if !node.is_referenced() {
+                    report.leftover_nodes.push(i)
+                }
+            }
+        }
+
+        Ok((result, report))
+    }
+
     pub fn deserialize_single_any(&self, version_hint: Option<u32>) -> Result<AnyRsz> {
         let mut result = self.deserialize(version_hint)?;
         if result.len() != 1 {
@@ -279,15 +458,109 @@ impl Rsz {
         Ok(result.pop().unwrap())
     }
 
-    pub fn deserialize_single<T: 'static>(&self, version_hint: Option<u32>) -> Result<T> {
+    pub fn deserialize_single<T: Any + Send + Sync>(&self, version_hint: Option<u32>) -> Result<T> {
         let mut result = self.deserialize(version_hint)?;
         if result.len() != 1 {
             bail!("Not a single-valued RSZ");
         }
-        Rc::try_unwrap(result.pop().unwrap().downcast().context("Type mismatch")?)
+        Arc::try_unwrap(result.pop().unwrap().downcast().context("Type mismatch")?)
             .map_err(|_| anyhow!("Shared node"))
     }
 
+    /// Builds an RSZ block from the roots, with children placed before their parents.
+    /// The CRC of each type is taken from `crc_hint` if it is a known one,
+    /// otherwise the latest one not newer than `version`.
+    pub fn serialize(roots: &[AnyRsz], version: u32, crc_hint: &HashMap<u32, u32>) -> Result<Rsz> {
+        let mut context = RszSerializeContext {
+            version,
+            crc_hint,
+            type_descriptors: vec![TypeDescriptor { hash: 0, crc: 0 }],
+            extern_slots: HashMap::new(),
+            node_data: vec![vec![]],
+            shared: HashMap::new(),
+        };
+        let mut serializer = RszSerializer {
+            context: &mut context,
+            pieces: vec![],
+            version,
+        };
+        let roots = roots
+            .iter()
+            .map(|root| serializer.put_child_any(root))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut data = vec![];
+        for piece in context.node_data.into_iter().flatten() {
+            data.resize(
+                usize::try_from(align_up(data.len() as u64, piece.align))?,
+                0,
+            );
+            data.extend(piece.bytes);
+        }
+
+        Ok(Rsz {
+            roots,
+            extern_slots: context.extern_slots,
+            type_descriptors: context.type_descriptors,
+            data,
+        })
+    }
+
+    /// Writes the RSZ block at the current position, which must be aligned to 16
+    pub fn write<F: Write + Seek>(&self, mut file: F) -> Result<()> {
+        if file.stream_position()? % 16 != 0 {
+            bail!("RSZ block not aligned")
+        }
+
+        let mut slots: Vec<_> = self.extern_slots.iter().collect();
+        slots.sort_by_key(|(&slot, _)| slot);
+
+        let mut block = Cursor::new(vec![]);
+        block.write_magic(b"RSZ\0")?;
+        block.write_u32(0x10)?;
+        block.write_u32(u32::try_from(self.roots.len())?)?;
+        block.write_u32(u32::try_from(self.type_descriptors.len())?)?;
+        block.write_u32(u32::try_from(slots.len())?)?;
+        block.write_u32(0)?;
+        // Offsets are filled in below
+        let offsets_position = block.position();
+        block.write_u64(0)?;
+        block.write_u64(0)?;
+        block.write_u64(0)?;
+        for &root in &self.roots {
+            block.write_u32(root)?;
+        }
+        let type_descriptor_offset = block.position();
+        for td in &self.type_descriptors {
+            block.write_u32(td.hash)?;
+            block.write_u32(td.crc)?;
+        }
+        pad16(&mut block)?;
+        let string_table_offset = block.position();
+        let mut string_offset = string_table_offset + slots.len() as u64 * 0x10;
+        let mut strings = vec![];
+        for (&slot, slot_extern) in &slots {
+            block.write_u32(slot)?;
+            block.write_u32(slot_extern.hash)?;
+            block.write_u64(string_offset)?;
+            let len = strings.len();
+            strings.write_u16str(&slot_extern.path)?;
+            string_offset += (strings.len() - len) as u64;
+        }
+        block.write_all(&strings)?;
+        pad16(&mut block)?;
+        let data_offset = block.position();
+        block.write_all(&self.data)?;
+
+        block.set_position(offsets_position);
+        block.write_u64(type_descriptor_offset)?;
+        block.write_u64(data_offset)?;
+        block.write_u64(string_table_offset)?;
+
+        file.write_all(block.get_ref())?;
+        Ok(())
+    }
+
     pub fn root_count(&self) -> usize {
         self.roots.len()
     }
@@ -295,7 +568,9 @@ impl Rsz {
     pub fn verify_crc(&self, crc_mismatches: &mut BTreeMap<&str, u32>, print_all: bool) {
         for td in &self.type_descriptors {
             if let Some(type_info) = RSZ_TYPE_MAP.get(&td.hash) {
-                if print_all || (!type_info.versions.contains_key(&td.crc) && !type_info.versions.is_empty())  {
+                if print_all
+                    || (!type_info.versions.contains_key(&td.crc) && !type_info.versions.is_empty())
+                {
                     crc_mismatches.insert(type_info.symbol, td.crc);
                 }
             }
@@ -307,10 +582,12 @@ pub struct RszDeserializer<'a, 'b> {
     node_buf: &'a mut [Option<AnyRsz>],
     cursor: &'a mut Cursor<&'b Vec<u8>>,
     version: u32,
+    // Indices of the children moved out of node_buf
+    moved: &'a mut Vec<usize>,
 }
 
 impl<'a, 'b> RszDeserializer<'a, 'b> {
-    pub fn get_child_opt<T: 'static>(&mut self) -> Result<Option<T>> {
+    pub fn get_child_opt<T: Any + Send + Sync>(&mut self) -> Result<Option<T>> {
         let index = self.cursor.read_u32()?;
         if index == 0 {
             return Ok(None);
@@ -320,16 +597,22 @@ impl<'a, 'b> RszDeserializer<'a, 'b> {
             .get_mut(usize::try_from(index)?)
             .context("Child index out of bound")?;
 
-        let slot_inner = slot.as_mut().context("Child taken")?;
-        if Rc::strong_count(&slot_inner.any) != 1 {
-            bail!("Shared node")
+        let slot_inner = slot.as_mut().with_context(|| {
+            format!("Child {index} already taken. Shared nodes should be read as Shared<T>")
+        })?;
+        if Arc::strong_count(&slot_inner.any) != 1 {
+            bail!(
+                "Child {index} ({}) is shared. Shared nodes should be read as Shared<T>",
+                slot_inner.symbol()
+            )
         }
-        let node: Rc<T> = slot_inner.clone().downcast()?;
+        let node: Arc<T> = slot_inner.clone().downcast()?;
         slot.take();
-        Ok(Some(Rc::try_unwrap(node).map_err(|_| ()).unwrap()))
+        self.moved.push(usize::try_from(index)?);
+        Ok(Some(Arc::try_unwrap(node).map_err(|_| ()).unwrap()))
     }
 
-    pub fn get_child<T: 'static>(&mut self) -> Result<T> {
+    pub fn get_child<T: Any + Send + Sync>(&mut self) -> Result<T> {
         self.get_child_opt()?.context("Null child")
     }
 
@@ -343,7 +626,9 @@ impl<'a, 'b> RszDeserializer<'a, 'b> {
             .get_mut(usize::try_from(index)?)
             .context("Child index out of bound")?
             .clone()
-            .context("Child taken")?;
+            .with_context(|| {
+                format!("Child {index} already taken. Shared nodes should be read as Shared<T>")
+            })?;
         Ok(Some(node))
     }
 
@@ -351,7 +636,7 @@ impl<'a, 'b> RszDeserializer<'a, 'b> {
         self.get_child_any_opt()?.context("Null child")
     }
 
-    pub fn get_child_rc_opt<T: 'static>(&mut self) -> Result<Option<Rc<T>>> {
+    pub fn get_child_arc_opt<T: Any + Send + Sync>(&mut self) -> Result<Option<Arc<T>>> {
         if let Some(child) = self.get_child_any_opt()? {
             Ok(Some(child.downcast()?))
         } else {
@@ -359,7 +644,7 @@ impl<'a, 'b> RszDeserializer<'a, 'b> {
         }
     }
 
-    pub fn get_child_rc<T: 'static>(&mut self) -> Result<Rc<T>> {
+    pub fn get_child_arc<T: Any + Send + Sync>(&mut self) -> Result<Arc<T>> {
         self.get_child_any()?.downcast()
     }
 
@@ -374,14 +659,421 @@ impl<'a, 'b> Read for RszDeserializer<'a, 'b> {
     }
 }
 
+/// Bytes of an instance that start at the given alignment within the data block
+struct RszPiece {
+    align: u64,
+    bytes: Vec<u8>,
+}
+
+struct RszSerializeContext<'a> {
+    version: u32,
+    crc_hint: &'a HashMap<u32, u32>,
+    type_descriptors: Vec<TypeDescriptor>,
+    extern_slots: HashMap<u32, Extern>,
+    node_data: Vec<Vec<RszPiece>>,
+    // Instance index of nodes behind Arc, so that shared nodes are only written once
+    shared: HashMap<*const (), u32>,
+}
+
+/// The CRC in `crc_hint` if it is a known one, otherwise the latest one not newer than `version`
+fn pick_crc(type_info: &RszTypeInfo, version: u32, crc_hint: &HashMap<u32, u32>) -> Result<u32> {
+    if let Some(&crc) = crc_hint.get(&type_info.hash) {
+        if type_info.versions.is_empty() || type_info.versions.contains_key(&crc) {
+            return Ok(crc);
+        }
+    }
+    type_info
+        .versions
+        .iter()
+        .filter(|(_, &v)| v <= version)
+        .max_by_key(|(&crc, &v)| (v, crc))
+        .map(|(&crc, _)| crc)
+        .with_context(|| {
+            format!(
+                "No known CRC for type {} at version {}",
+                type_info.symbol, version
+            )
+        })
+}
+
+impl RszSerializeContext<'_> {
+    fn push(&mut self, type_descriptor: TypeDescriptor, pieces: Vec<RszPiece>) -> Result<u32> {
+        let index = u32::try_from(self.type_descriptors.len())?;
+        self.type_descriptors.push(type_descriptor);
+        self.node_data.push(pieces);
+        Ok(index)
+    }
+}
+
+pub struct RszSerializer<'a, 'b> {
+    context: &'a mut RszSerializeContext<'b>,
+    pieces: Vec<RszPiece>,
+    version: u32,
+}
+
+impl<'a, 'b> RszSerializer<'a, 'b> {
+    /// Pads the following bytes to the alignment
+    pub fn align_up(&mut self, align: u64) {
+        self.pieces.push(RszPiece {
+            align,
+            bytes: vec![],
+        })
+    }
+
+    fn put_node(&mut self, any: &dyn Any, type_info: &'static RszTypeInfo) -> Result<u32> {
+        let crc = pick_crc(type_info, self.context.version, self.context.crc_hint)?;
+        let version = if type_info.versions.is_empty() {
+            self.context.version
+        } else {
+            type_info.versions[&crc]
+        };
+        let mut serializer = RszSerializer {
+            context: &mut *self.context,
+            pieces: vec![],
+            version,
+        };
+        (type_info.serializer)(any, &mut serializer)
+            .with_context(|| format!("Error serializing for type {}", type_info.symbol))?;
+        let pieces = serializer.pieces;
+        let type_descriptor = TypeDescriptor {
+            hash: type_info.hash,
+            crc,
+        };
+        self.context.push(type_descriptor, pieces)
+    }
+
+    pub fn put_child<T: FromRsz + 'static>(&mut self, child: &T) -> Result<u32> {
+        let type_info = RSZ_TYPE_MAP
+            .get(&T::type_hash())
+            .with_context(|| format!("Unregistered type {}", T::SYMBOL))?;
+        self.put_node(child, type_info)
+    }
+
+    pub fn put_child_arc<T: FromRsz + 'static>(&mut self, child: &Arc<T>) -> Result<u32> {
+        let key = Arc::as_ptr(child) as *const ();
+        if let Some(&index) = self.context.shared.get(&key) {
+            return Ok(index);
+        }
+        let index = self.put_child(&**child)?;
+        self.context.shared.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn put_child_any(&mut self, child: &AnyRsz) -> Result<u32> {
+        if let Ok(path) = child.clone().downcast::<ExternPath>() {
+            return self.put_extern(&path);
+        }
+        if let Some(opaque) = child.downcast_ref::<OpaqueRsz>() {
+            bail!(
+                "Opaque instance of type {:08X} can't be written back",
+                opaque.hash
+            )
+        }
+        let key = Arc::as_ptr(&child.any) as *const ();
+        if let Some(&index) = self.context.shared.get(&key) {
+            return Ok(index);
+        }
+        let index = self.put_node(&*child.any, child.type_info)?;
+        self.context.shared.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn put_extern(&mut self, path: &Arc<ExternPath>) -> Result<u32> {
+        let key = Arc::as_ptr(path) as *const ();
+        if let Some(&index) = self.context.shared.get(&key) {
+            return Ok(index);
+        }
+        let index = self.context.push(
+            TypeDescriptor {
+                hash: path.hash,
+                crc: path.crc,
+            },
+            vec![],
+        )?;
+        self.context.extern_slots.insert(
+            index,
+            Extern {
+                hash: path.hash,
+                path: path.path.clone(),
+            },
+        );
+        self.context.shared.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl<'a, 'b> Write for RszSerializer<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.pieces.is_empty() {
+            self.align_up(1);
+        }
+        self.pieces.last_mut().unwrap().bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub struct AnyRsz {
-    any: Rc<dyn Any>,
+    any: Arc<dyn Any + Send + Sync>,
     type_info: &'static RszTypeInfo,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ExternPath(String);
+/// Instance that couldn't be read, kept as its raw bytes
+#[derive(Debug)]
+pub struct OpaqueRsz {
+    pub hash: u32,
+    pub crc: u32,
+    pub data: Vec<u8>,
+}
+
+impl Serialize for OpaqueRsz {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("$hash", &format!("{:08X}", self.hash))?;
+        map.serialize_entry("$crc", &format!("{:08X}", self.crc))?;
+        map.serialize_entry(
+            "$data",
+            &self
+                .data
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>(),
+        )?;
+        map.end()
+    }
+}
+
+#[derive(Debug)]
+pub struct RszSkip {
+    pub index: usize,
+    pub hash: u32,
+    pub crc: u32,
+    /// Offset in the data block
+    pub offset: u64,
+    pub size: u64,
+    pub reason: String,
+}
+
+/// Instance where `Rsz::deserialize_lenient` stopped
+#[derive(Debug)]
+pub struct RszUnread {
+    pub index: usize,
+    pub hash: u32,
+    pub crc: u32,
+    /// Offset in the data block
+    pub offset: u64,
+    /// Number of instances not read, including this one
+    pub count: usize,
+    pub reason: String,
+}
+
+/// Problems tolerated by `Rsz::deserialize_lenient`
+#[derive(Debug, Default)]
+pub struct RszSkipReport {
+    /// Instances kept as `OpaqueRsz`
+    pub skipped: Vec<RszSkip>,
+    /// Instance that could neither be read nor skipped. It and all instances after it are missing.
+    pub unread: Option<RszUnread>,
+    /// Roots already taken by other instances, replaced by empty `OpaqueRsz`
+    pub missing_roots: Vec<usize>,
+    /// Instances that are neither roots nor referenced
+    pub leftover_nodes: Vec<usize>,
+    /// Bytes at the end of the data not belonging to any instance
+    pub leftover_data: u64,
+}
+
+impl RszSkipReport {
+    pub fn is_empty(&self) -> bool {
+        self.skipped.is_empty()
+            && self.unread.is_none()
+            && self.missing_roots.is_empty()
+            && self.leftover_nodes.is_empty()
+            && self.leftover_data == 0
+    }
+}
+
+impl std::fmt::Display for RszSkipReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = |hash| {
+            RSZ_TYPE_MAP
+                .get(&hash)
+                .or_else(|| schema_type_info(hash))
+                .map_or("", |t| t.symbol)
+        };
+        for skip in &self.skipped {
+            writeln!(
+                f,
+                "Skipped instance {} ({:08X} {}, CRC {:08X}), {} bytes at {:08X}: {}",
+                skip.index,
+                skip.hash,
+                symbol(skip.hash),
+                skip.crc,
+                skip.size,
+                skip.offset,
+                skip.reason
+            )?;
+        }
+        if let Some(unread) = &self.unread {
+            writeln!(
+                f,
+                "Stopped at instance {} ({:08X} {}, CRC {:08X}) at {:08X}, {} instances not read: {}",
+                unread.index,
+                unread.hash,
+                symbol(unread.hash),
+                unread.crc,
+                unread.offset,
+                unread.count,
+                unread.reason
+            )?;
+        }
+        if !self.missing_roots.is_empty() {
+            writeln!(f, "Missing roots: {:?}", self.missing_roots)?;
+        }
+        if !self.leftover_nodes.is_empty() {
+            writeln!(f, "Left over instances: {:?}", self.leftover_nodes)?;
+        }
+        if self.leftover_data != 0 {
+            writeln!(f, "Left over data: {} bytes", self.leftover_data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Path to an external USER file, and the type descriptor of its slot
+#[derive(Debug)]
+pub struct ExternPath {
+    pub path: String,
+    hash: u32,
+    crc: u32,
+}
+
+impl Serialize for ExternPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct("ExternPath", &self.path)
+    }
+}
+
+struct AnyRszContent<'a>(&'a AnyRsz);
+
+impl Serialize for AnyRszContent<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(node) = self.0.downcast_ref::<DynamicRsz>() {
+            return node.serialize(serializer);
+        }
+        if let Some(opaque) = self.0.downcast_ref::<OpaqueRsz>() {
+            return opaque.serialize(serializer);
+        }
+        let json = self.0.to_json().map_err(serde::ser::Error::custom)?;
+        serde_json::from_str::<serde_json::Value>(&json)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl Serialize for AnyRsz {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(path) = self.downcast_ref::<ExternPath>() {
+            return path.serialize(serializer);
+        }
+        with_shared_json(|| {
+            serialize_shared(
+                Arc::as_ptr(&self.any) as *const (),
+                Arc::strong_count(&self.any) > 1,
+                &AnyRszContent(self),
+                serializer,
+            )
+        })
+    }
+}
+
+thread_local! {
+    /// Ids of the shared nodes already written by the JSON serialization in progress
+    static SHARED_JSON_IDS: std::cell::RefCell<Option<HashMap<*const (), usize>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+struct SharedJsonScope;
+
+impl Drop for SharedJsonScope {
+    fn drop(&mut self) {
+        SHARED_JSON_IDS.with(|ids| *ids.borrow_mut() = None)
+    }
+}
+
+/// Runs a serialization in which each shared node is written once.
+/// Nested calls share the scope of the outermost one.
+fn with_shared_json<R>(f: impl FnOnce() -> R) -> R {
+    let outermost = SHARED_JSON_IDS.with(|ids| {
+        let mut ids = ids.borrow_mut();
+        if ids.is_some() {
+            return false;
+        }
+        *ids = Some(HashMap::new());
+        true
+    });
+    // Created lazily, as dropping it ends the scope
+    let _scope = outermost.then(|| SharedJsonScope);
+    f()
+}
+
+/// Writes the first occurrence of a shared node as `{"$shared": id, "$value": ...}`,
+/// and the following ones as `{"$ref": id}`.
+fn serialize_shared<S: Serializer>(
+    key: *const (),
+    shared: bool,
+    value: &impl Serialize,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let id = shared
+        .then(|| {
+            SHARED_JSON_IDS.with(|ids| {
+                let mut ids = ids.borrow_mut();
+                let ids = ids.as_mut()?;
+                let next = ids.len();
+                Some(match ids.entry(key) {
+                    std::collections::hash_map::Entry::Occupied(id) => Err(*id.get()),
+                    std::collections::hash_map::Entry::Vacant(id) => Ok(*id.insert(next)),
+                })
+            })
+        })
+        .flatten();
+    match id {
+        None => value.serialize(serializer),
+        Some(Ok(id)) => {
+            let mut map = serializer.serialize_map(Some(2))?;
+            map.serialize_entry("$shared", &id)?;
+            map.serialize_entry("$value", value)?;
+            map.end()
+        }
+        Some(Err(id)) => {
+            let mut map = serializer.serialize_map(Some(1))?;
+            map.serialize_entry("$ref", &id)?;
+            map.end()
+        }
+    }
+}
 
 impl Debug for AnyRsz {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -390,16 +1082,31 @@ impl Debug for AnyRsz {
 }
 
 impl AnyRsz {
-    pub fn new<T: Any + Serialize + Debug>(v: T, type_info: &'static RszTypeInfo) -> AnyRsz {
-        let any = Rc::new(v);
+    pub fn new<T: Any + Send + Sync + Serialize + Debug>(
+        v: T,
+        type_info: &'static RszTypeInfo,
+    ) -> AnyRsz {
+        let any = Arc::new(v);
         AnyRsz { any, type_info }
     }
 
-    pub fn new_extern(path: String) -> AnyRsz {
-        Self::new(ExternPath(path), &EXTERN_PATH_TYPE_INFO)
+    /// Wraps an instance of a registered type
+    pub fn from_node<T: FromRsz + Any + Serialize + Debug>(v: T) -> Result<AnyRsz> {
+        let type_info = RSZ_TYPE_MAP
+            .get(&T::type_hash())
+            .with_context(|| format!("Unregistered type {}", T::SYMBOL))?;
+        Ok(AnyRsz::new(v, type_info))
     }
 
-    pub fn downcast<T: Any>(self) -> Result<Rc<T>> {
+    pub fn new_extern(path: ExternPath) -> AnyRsz {
+        Self::new(path, &EXTERN_PATH_TYPE_INFO)
+    }
+
+    pub fn new_opaque(opaque: OpaqueRsz) -> AnyRsz {
+        Self::new(opaque, &OPAQUE_TYPE_INFO)
+    }
+
+    pub fn downcast<T: Any + Send + Sync>(self) -> Result<Arc<T>> {
         let symbol = self.type_info.symbol;
         match self.any.downcast() {
             Ok(b) => Ok(b),
@@ -413,17 +1120,37 @@ impl AnyRsz {
         self.any.downcast_ref()
     }
 
+    /// None if the type mismatches or the node is shared
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        Arc::get_mut(&mut self.any)?.downcast_mut()
+    }
+
+    /// For nodes still in the node buffer. Nodes read by value are moved out of it,
+    /// while shared ones stay and are also held by each parent referencing them.
+    fn is_referenced(&self) -> bool {
+        Arc::strong_count(&self.any) > 1
+    }
+
     pub fn to_json(&self) -> Result<String> {
         (self.type_info.to_json)(&*self.any)
     }
 
+    /// JSON of each node, with the shared nodes numbered across all of them
+    pub fn to_json_all(nodes: &[AnyRsz]) -> Result<Vec<String>> {
+        with_shared_json(|| nodes.iter().map(|node| node.to_json()).collect())
+    }
+
     pub fn symbol(&self) -> &'static str {
         self.type_info.symbol
     }
 }
 
-pub trait FromRsz: Sized {
+pub trait FromRsz: Sized + Send + Sync {
     fn from_rsz(rsz: &mut RszDeserializer) -> Result<Self>;
+    fn from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self>;
+    fn resolve_externs(&mut self, _resolver: &mut ExternResolver) -> Result<()> {
+        Ok(())
+    }
     const SYMBOL: &'static str;
     const VERSIONS: &'static [(u32, u32)];
     fn type_hash() -> u32 {
@@ -431,21 +1158,42 @@ pub trait FromRsz: Sized {
     }
 }
 
+pub trait ToRsz: FromRsz {
+    fn to_rsz(&self, rsz: &mut RszSerializer) -> Result<()>;
+}
+
 pub trait SingletonUser: Sized {
     const PATH: &'static str;
-    type RszType: 'static;
+    type RszType: 'static + Send + Sync;
     fn from_rsz(value: Self::RszType) -> Self;
 }
 
 trait FieldFromRsz: Sized {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self>;
+    /// Whether serde may write the field flattened into the parent object
+    const JSON_FLATTENED: bool = false;
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self>;
+    fn field_resolve_externs(&mut self, _resolver: &mut ExternResolver) -> Result<()> {
+        Ok(())
+    }
+}
+
+trait FieldToRsz {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()>;
 }
 
+type ResolveExterns = fn(&mut dyn Any, &mut ExternResolver) -> Result<()>;
+
 pub struct RszTypeInfo {
     deserializer: fn(&mut RszDeserializer, type_info: &'static RszTypeInfo) -> Result<AnyRsz>,
+    serializer: fn(&dyn Any, &mut RszSerializer) -> Result<()>,
     to_json: fn(&dyn Any) -> Result<String>,
     debug: fn(&dyn Any, &mut std::fmt::Formatter) -> std::fmt::Result,
+    /// None for types that can't hold references to other files
+    resolve_externs: Option<ResolveExterns>,
+    from_json: fn(&mut RszJsonReader, &serde_json::Value, &'static RszTypeInfo) -> Result<AnyRsz>,
     versions: HashMap<u32, u32>,
+    hash: u32,
     pub symbol: &'static str,
 }
 
@@ -456,41 +1204,67 @@ fn rsz_deserializer<T: 'static + FromRsz + Serialize + Debug>(
     Ok(AnyRsz::new(T::from_rsz(rsz)?, type_info))
 }
 
+fn rsz_serializer<T: 'static + ToRsz>(any: &dyn Any, rsz: &mut RszSerializer) -> Result<()> {
+    any.downcast_ref::<T>().unwrap().to_rsz(rsz)
+}
+
 fn rsz_to_json<T: 'static + Serialize>(any: &dyn Any) -> Result<String> {
-    serde_json::to_string_pretty(any.downcast_ref::<T>().unwrap())
+    with_shared_json(|| serde_json::to_string_pretty(any.downcast_ref::<T>().unwrap()))
         .context("Failed to convert to json")
 }
 
+/// JSON of a registered type, tagged with `$type` so that it can be read back
+fn rsz_to_tagged_json<T: 'static + FromRsz + Serialize>(any: &dyn Any) -> Result<String> {
+    #[derive(Serialize)]
+    struct Tagged<'a, T> {
+        #[serde(rename = "$type")]
+        symbol: &'static str,
+        #[serde(flatten)]
+        value: &'a T,
+    }
+    let value = any.downcast_ref::<T>().unwrap();
+    with_shared_json(|| {
+        serde_json::to_string_pretty(&Tagged {
+            symbol: T::SYMBOL,
+            value,
+        })
+    })
+    .context("Failed to convert to json")
+}
+
+fn rsz_from_json<T: 'static + FromRsz + Serialize + Debug>(
+    json: &mut RszJsonReader,
+    value: &serde_json::Value,
+    type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    Ok(AnyRsz::new(T::from_json(json, value)?, type_info))
+}
+
+fn rsz_resolve_externs<T: 'static + FromRsz>(
+    any: &mut dyn Any,
+    resolver: &mut ExternResolver,
+) -> Result<()> {
+    any.downcast_mut::<T>().unwrap().resolve_externs(resolver)
+}
+
 fn rsz_debug<T: 'static + Debug>(any: &dyn Any, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     std::fmt::Debug::fmt(any.downcast_ref::<T>().unwrap(), f)
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub enum ExternUser<T> {
-    Path(Rc<ExternPath>),
-    Loaded(T),
+    Path(Arc<ExternPath>),
+    Loaded(Shared<T>),
 }
 
-impl<T: 'static> ExternUser<T> {
-    pub fn load<'a>(
-        &'a mut self,
-        pak: &'_ mut crate::pak::PakReader<impl Read + Seek>,
-        version_hint: Option<u32>,
-    ) -> Result<&'a mut T> {
-        match self {
-            ExternUser::Path(path) => {
-                let index = pak.find_file(&path.0)?;
-                let file = pak.read_file(index)?;
-                let user = crate::user::User::new(Cursor::new(file))?;
-                *self = ExternUser::Loaded(user.rsz.deserialize_single(version_hint)?);
-                if let ExternUser::Loaded(t) = self {
-                    Ok(t)
-                } else {
-                    unreachable!()
-                }
-            }
-            ExternUser::Loaded(t) => Ok(t),
+impl<T: Any + Send + Sync> ExternUser<T> {
+    /// Loads the referenced file, together with the files it references in turn.
+    /// Files already loaded by the resolver are not read again.
+    pub fn load<'a>(&'a mut self, resolver: &mut ExternResolver) -> Result<&'a T> {
+        if let ExternUser::Path(path) = self {
+            *self = ExternUser::Loaded(Shared(resolver.load(&path.path)?));
         }
+        Ok(self.unwrap())
     }
 
     pub fn unwrap(&self) -> &T {
@@ -503,6 +1277,175 @@ impl<T: 'static> ExternUser<T> {
     }
 }
 
+/// Loads the USER files referenced by deserialized values.
+/// Each file is read once, and its content is shared by all references to it.
+pub struct ExternResolver<'a> {
+    pak: &'a crate::pak::SyncPakReader,
+    version_hint: Option<u32>,
+    loaded: HashMap<String, AnyRsz>,
+    /// Files being resolved, from the outermost one
+    loading: Vec<String>,
+    /// Resolved copies of the nodes referenced more than once, by the address of the original
+    /// and of the copy itself
+    shared: HashMap<usize, Arc<dyn Any + Send + Sync>>,
+    /// Originals replaced by their copy, kept so that their address is not reused
+    replaced: Vec<Arc<dyn Any + Send + Sync>>,
+}
+
+fn shared_key<T: ?Sized>(node: &Arc<T>) -> usize {
+    Arc::as_ptr(node) as *const () as usize
+}
+
+impl<'a> ExternResolver<'a> {
+    pub fn new(pak: &'a crate::pak::SyncPakReader, version_hint: Option<u32>) -> Self {
+        ExternResolver {
+            pak,
+            version_hint,
+            loaded: HashMap::new(),
+            loading: vec![],
+            shared: HashMap::new(),
+            replaced: vec![],
+        }
+    }
+
+    pub fn load_any(&mut self, path: &str) -> Result<AnyRsz> {
+        if let Some(node) = self.loaded.get(path) {
+            return Ok(node.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|p| p == path) {
+            let chain = self.loading[start..].join(" -> ");
+            bail!("Cyclic USER reference: {chain} -> {path}")
+        }
+        let mut node = (|| {
+            let index = self.pak.find_file(path)?;
+            let file = self.pak.read_file(index)?;
+            let user = crate::user::User::new(Cursor::new(file))?;
+            user.rsz.deserialize_single_any(self.version_hint)
+        })()
+        .with_context(|| format!("Failed to load {path}"))?;
+        self.loading.push(path.to_owned());
+        let result = self.resolve_any(&mut node);
+        self.loading.pop();
+        result.with_context(|| format!("In {path}"))?;
+        self.loaded.insert(path.to_owned(), node.clone());
+        Ok(node)
+    }
+
+    pub fn load<T: Any + Send + Sync>(&mut self, path: &str) -> Result<Arc<T>> {
+        self.load_any(path)?
+            .downcast()
+            .with_context(|| format!("Unexpected type in {path}"))
+    }
+
+    /// Resolves the references in the node, and replaces the node itself if it is one
+    pub fn resolve_any(&mut self, node: &mut AnyRsz) -> Result<()> {
+        if let Some(path) = node.downcast_ref::<ExternPath>() {
+            let path = path.path.clone();
+            *node = self.load_any(&path)?;
+            return Ok(());
+        }
+        let Some(resolve_externs) = node.type_info.resolve_externs else {
+            return Ok(());
+        };
+        if let Some(any) = Arc::get_mut(&mut node.any) {
+            return resolve_externs(any, self);
+        }
+        if let Some(resolved) = self.shared.get(&shared_key(&node.any)) {
+            node.any = resolved.clone();
+            return Ok(());
+        }
+        // Only nodes read with the schema can be copied without knowing their type
+        let Some(dynamic) = node.downcast_ref::<DynamicRsz>() else {
+            bail!(
+                "Shared node of type {} can't be copied to resolve its references",
+                node.symbol()
+            )
+        };
+        let mut copy = dynamic.clone();
+        resolve_externs(&mut copy, self)?;
+        let resolved: Arc<dyn Any + Send + Sync> = Arc::new(copy);
+        let original = std::mem::replace(&mut node.any, resolved.clone());
+        self.record_shared(original, resolved);
+        Ok(())
+    }
+
+    /// Resolves the references in a node that can be shared by several fields.
+    /// A shared node is resolved once on a copy, which then replaces it in all fields.
+    pub fn resolve_shared<T: Any + Send + Sync + Clone>(
+        &mut self,
+        node: &mut Arc<T>,
+        resolve: impl FnOnce(&mut T, &mut Self) -> Result<()>,
+    ) -> Result<()> {
+        if let Some(node) = Arc::get_mut(node) {
+            return resolve(node, self);
+        }
+        if let Some(resolved) = self.shared.get(&shared_key(node)) {
+            *node = resolved
+                .clone()
+                .downcast()
+                .map_err(|_| anyhow!("Shared node type mismatch"))?;
+            return Ok(());
+        }
+        let mut copy = T::clone(node);
+        resolve(&mut copy, self)?;
+        let resolved = Arc::new(copy);
+        let original = std::mem::replace(node, resolved.clone());
+        self.record_shared(original, resolved);
+        Ok(())
+    }
+
+    fn record_shared(
+        &mut self,
+        original: Arc<dyn Any + Send + Sync>,
+        resolved: Arc<dyn Any + Send + Sync>,
+    ) {
+        self.shared.insert(shared_key(&original), resolved.clone());
+        self.shared.insert(shared_key(&resolved), resolved);
+        self.replaced.push(original);
+    }
+
+    pub fn resolve<T: FromRsz>(&mut self, value: &mut T) -> Result<()> {
+        value.resolve_externs(self)
+    }
+}
+
+/// Node that can be referenced by more than one field.
+/// In JSON, it is written in full only where it is first met.
+pub struct Shared<T>(pub Arc<T>);
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Debug> Debug for Shared<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: Serialize> Serialize for Shared<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_shared(
+            Arc::as_ptr(&self.0) as *const (),
+            Arc::strong_count(&self.0) > 1,
+            &*self.0,
+            serializer,
+        )
+    }
+}
+
 fn extern_path_deserializer(
     _rsz: &mut RszDeserializer,
     _type_info: &'static RszTypeInfo,
@@ -510,38 +1453,164 @@ fn extern_path_deserializer(
     unreachable!()
 }
 
-impl<T> FieldFromRsz for ExternUser<T> {
+fn extern_path_serializer(_any: &dyn Any, _rsz: &mut RszSerializer) -> Result<()> {
+    unreachable!()
+}
+
+fn extern_path_from_json(
+    _json: &mut RszJsonReader,
+    _value: &serde_json::Value,
+    _type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    unreachable!()
+}
+
+/// Type of the root of the USER files referenced by `ExternUser<Self>`
+trait ExternUserType {
+    fn extern_type_hash() -> Option<u32>;
+}
+
+impl<T: FromRsz> ExternUserType for T {
+    fn extern_type_hash() -> Option<u32> {
+        Some(T::type_hash())
+    }
+}
+
+/// `ExternUser<()>` stands for a type not modeled here
+impl ExternUserType for () {
+    fn extern_type_hash() -> Option<u32> {
+        None
+    }
+}
+
+impl<T: Any + Send + Sync + ExternUserType> FieldFromRsz for ExternUser<T> {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.cursor.seek_align_up(4)?;
-        let extern_path = rsz.get_child_rc()?;
+        let extern_path = rsz.get_child_arc()?;
         Ok(ExternUser::Path(extern_path))
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        let (variant, path) = json.read_variant(value)?;
+        if variant != "Path" {
+            bail!("Loaded ExternUser can't be written back")
+        }
+        let path = json.read_str(path)?;
+        let extern_path = json.extern_path(path, T::extern_type_hash())?;
+        Ok(ExternUser::Path(Arc::new(extern_path)))
+    }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        if TypeId::of::<T>() == TypeId::of::<()>() {
+            return Ok(());
+        }
+        if let ExternUser::Path(path) = self {
+            *self = ExternUser::Loaded(Shared(resolver.load(&path.path)?));
+        }
+        Ok(())
+    }
 }
 
-impl<T> FieldFromRsz for Option<ExternUser<T>> {
+impl<T: Any + Send + Sync + ExternUserType> FieldFromRsz for Option<ExternUser<T>> {
     fn field_from_rsz(rsz: &mut RszDeserializer) -> Result<Self> {
         rsz.cursor.seek_align_up(4)?;
-        let extern_path = rsz.get_child_rc_opt()?;
+        let extern_path = rsz.get_child_arc_opt()?;
         Ok(extern_path.map(ExternUser::Path))
     }
+
+    fn field_from_json(json: &mut RszJsonReader, value: &serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(ExternUser::field_from_json(json, value)?)),
+        }
+    }
+
+    fn field_resolve_externs(&mut self, resolver: &mut ExternResolver) -> Result<()> {
+        match self {
+            Some(extern_user) => extern_user.field_resolve_externs(resolver),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> FieldToRsz for ExternUser<T> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        rsz.align_up(4);
+        let index = match self {
+            ExternUser::Path(path) => rsz.put_extern(path)?,
+            ExternUser::Loaded(_) => bail!("Loaded ExternUser can't be written back"),
+        };
+        rsz.write_u32(index)
+    }
+}
+
+impl<T> FieldToRsz for Option<ExternUser<T>> {
+    fn field_to_rsz(&self, rsz: &mut RszSerializer) -> Result<()> {
+        match self {
+            Some(extern_user) => extern_user.field_to_rsz(rsz),
+            None => {
+                rsz.align_up(4);
+                rsz.write_u32(0)
+            }
+        }
+    }
 }
 
 static EXTERN_PATH_TYPE_INFO: Lazy<RszTypeInfo> = Lazy::new(|| RszTypeInfo {
     deserializer: extern_path_deserializer,
+    serializer: extern_path_serializer,
     to_json: rsz_to_json::<ExternPath>,
     debug: rsz_debug::<ExternPath>,
+    resolve_externs: None,
+    from_json: extern_path_from_json,
     versions: HashMap::new(),
+    hash: 0,
     symbol: "FAKE_SYMBOL_ExternPath",
 });
 
-pub fn register<T: 'static + FromRsz + Serialize + Debug>(m: &mut HashMap<u32, RszTypeInfo>) {
+fn opaque_deserializer(
+    _rsz: &mut RszDeserializer,
+    _type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    unreachable!()
+}
+
+fn opaque_serializer(_any: &dyn Any, _rsz: &mut RszSerializer) -> Result<()> {
+    unreachable!()
+}
+
+fn opaque_from_json(
+    _json: &mut RszJsonReader,
+    _value: &serde_json::Value,
+    _type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    unreachable!()
+}
+
+static OPAQUE_TYPE_INFO: Lazy<RszTypeInfo> = Lazy::new(|| RszTypeInfo {
+    deserializer: opaque_deserializer,
+    serializer: opaque_serializer,
+    to_json: rsz_to_json::<OpaqueRsz>,
+    debug: rsz_debug::<OpaqueRsz>,
+    resolve_externs: None,
+    from_json: opaque_from_json,
+    versions: HashMap::new(),
+    hash: 0,
+    symbol: "FAKE_SYMBOL_Opaque",
+});
+
+pub fn register<T: 'static + ToRsz + Serialize + Debug>(m: &mut HashMap<u32, RszTypeInfo>) {
     let hash = T::type_hash();
 
     let package = RszTypeInfo {
         deserializer: rsz_deserializer::<T>,
-        to_json: rsz_to_json::<T>,
+        serializer: rsz_serializer::<T>,
+        to_json: rsz_to_tagged_json::<T>,
         debug: rsz_debug::<T>,
+        resolve_externs: Some(rsz_resolve_externs::<T>),
+        from_json: rsz_from_json::<T>,
         versions: T::VERSIONS.iter().copied().collect(),
+        hash,
         symbol: T::SYMBOL,
     };
 
@@ -551,6 +1620,33 @@ pub fn register<T: 'static + FromRsz + Serialize + Debug>(m: &mut HashMap<u32, R
     }
 }
 
+/// Version of the type for the CRC, including the candidates installed to check new CRCs
+fn known_version(type_info: &RszTypeInfo, crc: u32) -> Option<u32> {
+    type_info
+        .versions
+        .get(&crc)
+        .copied()
+        .or_else(|| candidate_version(type_info.hash, crc))
+}
+
+/// Registered type for the hash, or the one described by the installed schema.
+/// The schema is also preferred for a registered type if only the schema knows the CRC.
+fn find_type_info(hash: u32, crc: u32) -> Option<&'static RszTypeInfo> {
+    let registered = RSZ_TYPE_MAP.get(&hash);
+    let schema = schema_type_info(hash);
+    match (registered, schema) {
+        (Some(registered), Some(schema))
+            if !registered.versions.is_empty()
+                && known_version(registered, crc).is_none()
+                && schema.versions.contains_key(&crc) =>
+        {
+            Some(schema)
+        }
+        (Some(registered), _) => Some(registered),
+        (None, schema) => schema,
+    }
+}
+
 pub static RSZ_TYPE_MAP: Lazy<HashMap<u32, RszTypeInfo>> = Lazy::new(|| {
     let mut m = HashMap::new();
 
@@ -393,7 +393,7 @@ pub mod unique_mystery {
             }
 
             pub mod loader {
-                use std::rc::*;
+                use std::sync::Arc;
                 use anyhow::{anyhow, Context, Result};
                 use super::FromRsz;
                 $(
@@ -401,7 +401,7 @@ pub mod unique_mystery {
                     pub fn $name(rsz: super::AnyRsz) -> Result<super::EnemyUniqueMysteryDataWrapper> {
                         let downcasted = rsz.downcast::<super::$name>()
                             .with_context(||format!("Unexpected type for {}", <super::$name>::SYMBOL))?;
-                        let value = Rc::try_unwrap(downcasted)
+                        let value = Arc::try_unwrap(downcasted)
                             .map_err(|_|anyhow!("Shared node for {}", <super::$name>::SYMBOL))?;
                         Ok(super::EnemyUniqueMysteryDataWrapper {
                             base: value.base.0,
@@ -9,7 +9,7 @@ rsz_struct! {
     #[rsz("via.Folder",
         0xadd98040 = 10_00_02
     )]
-    #[derive(Debug, Serialize)]
+    #[derive(Debug, Serialize, Clone)]
     pub struct Folder {
         pub name: Option<String>,
         pub tag: Option<String>,
@@ -17,9 +17,17 @@ rsz_struct! {
         pub draw_self: bool,
         pub paolumu: bool,
         pub path: Option<String>,
-        pub x: u64,
-        pub y: u64,
-        pub z: u64
+        // Offset of the folder content, as via.Position
+        pub x: f64,
+        pub y: f64,
+        pub z: f64
+    }
+}
+
+impl Folder {
+    /// Matrix from the space of the folder content to the space of its parent
+    pub fn local_matrix(&self) -> Mat4 {
+        translation(&vec3(self.x as f32, self.y as f32, self.z as f32))
     }
 }
 
@@ -27,7 +35,7 @@ rsz_struct! {
     #[rsz("via.GameObject",
         0x0ce8a1f8 = 10_00_02
     )]
-    #[derive(Debug, Serialize)]
+    #[derive(Debug, Serialize, Clone)]
     pub struct GameObject {
         pub name: Option<String>,
         pub tag: Option<String>,
@@ -54,6 +62,14 @@ rsz_struct! {
     }
 }
 
+impl Transform {
+    /// Matrix from the space of the object to the space of its parent
+    pub fn local_matrix(&self) -> Mat4 {
+        let rotation = quat_to_mat4(&quat_normalize(&Quat::from(self.rotation)));
+        translation(&self.position.xyz()) * rotation * scaling(&self.scale.xyz())
+    }
+}
+
 // snow.wwise.WwiseMediaLoadManager.MediaType
 rsz_enum! {
     #[rsz(i32)]
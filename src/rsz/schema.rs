@@ -0,0 +1,558 @@
+use super::*;
+use once_cell::sync::OnceCell;
+use std::path::Path;
+
+static RSZ_SCHEMA: OnceCell<RszSchema> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    String,
+    Object,
+    Guid,
+    F32s,
+    I32s,
+    U32s,
+    Data,
+}
+
+impl FieldKind {
+    fn from_name(name: &str) -> FieldKind {
+        match name {
+            "Bool" => FieldKind::Bool,
+            "S8" => FieldKind::S8,
+            "U8" => FieldKind::U8,
+            "S16" => FieldKind::S16,
+            "U16" => FieldKind::U16,
+            "S32" => FieldKind::S32,
+            "U32" | "Color" => FieldKind::U32,
+            "S64" => FieldKind::S64,
+            "U64" => FieldKind::U64,
+            "F32" => FieldKind::F32,
+            "F64" => FieldKind::F64,
+            "String" | "Resource" => FieldKind::String,
+            "Object" | "UserData" => FieldKind::Object,
+            "Guid" | "Uuid" | "GameObjectRef" => FieldKind::Guid,
+            "Vec2" | "Vec3" | "Vec4" | "Float2" | "Float3" | "Float4" | "Quaternion" | "Mat4"
+            | "Range" | "Sphere" | "Capsule" | "OBB" | "AABB" | "Rect" | "Size" | "Point" => {
+                FieldKind::F32s
+            }
+            "Int2" | "Int3" | "Int4" | "RangeI" => FieldKind::I32s,
+            "Uint2" | "Uint3" | "Uint4" => FieldKind::U32s,
+            // Anything else is kept as raw bytes of the given size
+            _ => FieldKind::Data,
+        }
+    }
+
+    /// Size of a single element, or None if it is given by the schema
+    fn fixed_size(self) -> Option<u64> {
+        match self {
+            FieldKind::Bool | FieldKind::S8 | FieldKind::U8 => Some(1),
+            FieldKind::S16 | FieldKind::U16 => Some(2),
+            FieldKind::S32 | FieldKind::U32 | FieldKind::F32 => Some(4),
+            FieldKind::String | FieldKind::Object => Some(4),
+            FieldKind::S64 | FieldKind::U64 | FieldKind::F64 => Some(8),
+            FieldKind::Guid => Some(16),
+            FieldKind::F32s | FieldKind::I32s | FieldKind::U32s | FieldKind::Data => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RszFieldSchema {
+    pub name: String,
+    pub type_name: String,
+    /// The declared type in the TDB, such as the enum type of an integer field
+    pub original_type: String,
+    kind: FieldKind,
+    pub size: u64,
+    pub align: u64,
+    pub array: bool,
+}
+
+pub struct RszTypeSchema {
+    pub name: String,
+    pub crc: u32,
+    pub fields: Vec<RszFieldSchema>,
+    type_info: RszTypeInfo,
+}
+
+/// Field layouts of RSZ types, used to read and write types without a hand-written struct.
+///
+/// The JSON file maps the type hash in hex to the type, in the same format as RSZ dumps
+/// used by other RE Engine tools:
+/// `{"<hash>": {"name": ..., "crc": "<hex>", "fields": [{"name", "type", "size", "align", "array"}]}}`
+pub struct RszSchema {
+    types: HashMap<u32, RszTypeSchema>,
+}
+
+#[derive(Deserialize)]
+struct TypeJson {
+    name: String,
+    crc: String,
+    fields: Vec<FieldJson>,
+}
+
+#[derive(Deserialize)]
+struct FieldJson {
+    name: String,
+    #[serde(rename = "type")]
+    type_name: String,
+    size: u64,
+    align: u64,
+    array: bool,
+    #[serde(default)]
+    original_type: String,
+}
+
+fn parse_hex(s: &str) -> Result<u32> {
+    u32::from_str_radix(s.trim_start_matches("0x"), 16).with_context(|| format!("Bad hex {s}"))
+}
+
+impl RszSchema {
+    pub fn load(path: impl AsRef<Path>) -> Result<RszSchema> {
+        let path = path.as_ref();
+        let file = std::fs::read(path)
+            .with_context(|| format!("Failed to read RSZ schema {}", path.display()))?;
+        let json: HashMap<String, TypeJson> = serde_json::from_slice(&file)
+            .with_context(|| format!("Failed to parse RSZ schema {}", path.display()))?;
+
+        let mut types = HashMap::new();
+        for (hash, ty) in json {
+            let hash = parse_hex(&hash)?;
+            if hash == 0 {
+                continue;
+            }
+            let crc = parse_hex(&ty.crc)?;
+            let fields = ty
+                .fields
+                .into_iter()
+                .map(|field| {
+                    let kind = FieldKind::from_name(&field.type_name);
+                    if let Some(size) = kind.fixed_size() {
+                        if size != field.size {
+                            bail!(
+                                "Unexpected size {} for field {}.{} of type {}",
+                                field.size,
+                                ty.name,
+                                field.name,
+                                field.type_name
+                            )
+                        }
+                    }
+                    if field.align == 0
+                        || (matches!(kind, FieldKind::F32s | FieldKind::I32s | FieldKind::U32s)
+                            && field.size % 4 != 0)
+                    {
+                        bail!("Bad layout for field {}.{}", ty.name, field.name)
+                    }
+                    Ok(RszFieldSchema {
+                        name: field.name,
+                        type_name: field.type_name,
+                        original_type: field.original_type,
+                        kind,
+                        size: field.size,
+                        align: field.align,
+                        array: field.array,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // The schema lives until the end of the program, so is the symbol
+            let symbol: &'static str = Box::leak(ty.name.clone().into_boxed_str());
+            let type_info = RszTypeInfo {
+                deserializer: dynamic_deserializer,
+                serializer: dynamic_serializer,
+                to_json: rsz_to_json::<DynamicRsz>,
+                debug: rsz_debug::<DynamicRsz>,
+                resolve_externs: Some(dynamic_resolve_externs),
+                from_json: dynamic_from_json,
+                versions: HashMap::from([(crc, 0)]),
+                hash,
+                symbol,
+            };
+            types.insert(
+                hash,
+                RszTypeSchema {
+                    name: ty.name,
+                    crc,
+                    fields,
+                    type_info,
+                },
+            );
+        }
+        Ok(RszSchema { types })
+    }
+
+    /// Sets the schema used as fallback for all unregistered types.
+    /// Can only be called once.
+    pub fn install(self) -> Result<()> {
+        RSZ_SCHEMA
+            .set(self)
+            .map_err(|_| anyhow!("RSZ schema is already set"))
+    }
+
+    pub fn installed() -> Option<&'static RszSchema> {
+        RSZ_SCHEMA.get()
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = &RszTypeSchema> {
+        self.types.values()
+    }
+}
+
+pub(super) fn schema_type_info(hash: u32) -> Option<&'static RszTypeInfo> {
+    RSZ_SCHEMA
+        .get()?
+        .types
+        .get(&hash)
+        .map(|schema| &schema.type_info)
+}
+
+/// Looks up by name, for schemas not keyed by the hash of the name
+pub(super) fn schema_type_info_by_symbol(symbol: &str) -> Option<&'static RszTypeInfo> {
+    RSZ_SCHEMA
+        .get()?
+        .types
+        .values()
+        .find(|schema| schema.name == symbol)
+        .map(|schema| &schema.type_info)
+}
+
+/// End of an instance starting at `start`, if the schema describes the type at this CRC
+/// and none of its fields has a size depending on the data
+pub(super) fn schema_fixed_end(hash: u32, crc: u32, start: u64) -> Option<u64> {
+    let schema = RSZ_SCHEMA.get()?.types.get(&hash)?;
+    if schema.crc != crc {
+        return None;
+    }
+    schema.fields.iter().try_fold(start, |pos, field| {
+        if field.array || field.kind == FieldKind::String {
+            return None;
+        }
+        Some(align_up(pos, field.align) + field.size)
+    })
+}
+
+fn type_schema(type_info: &RszTypeInfo) -> Result<&'static RszTypeSchema> {
+    RSZ_SCHEMA
+        .get()
+        .and_then(|schema| schema.types.get(&type_info.hash))
+        .with_context(|| format!("No schema for type {}", type_info.symbol))
+}
+
+/// Value of a field in an RSZ instance read with the schema
+#[derive(Debug, Clone)]
+pub enum RszValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    F32(f32),
+    F64(f64),
+    String(Option<String>),
+    Object(Option<AnyRsz>),
+    Guid(Guid),
+    F32s(Vec<f32>),
+    I32s(Vec<i32>),
+    U32s(Vec<u32>),
+    Data(Vec<u8>),
+    Array(Vec<RszValue>),
+}
+
+impl Serialize for RszValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            RszValue::Bool(v) => v.serialize(serializer),
+            RszValue::Int(v) => v.serialize(serializer),
+            RszValue::UInt(v) => v.serialize(serializer),
+            RszValue::F32(v) => v.serialize(serializer),
+            RszValue::F64(v) => v.serialize(serializer),
+            RszValue::String(v) => v.serialize(serializer),
+            RszValue::Object(v) => v.serialize(serializer),
+            RszValue::Guid(v) => v.serialize(serializer),
+            RszValue::F32s(v) => v.serialize(serializer),
+            RszValue::I32s(v) => v.serialize(serializer),
+            RszValue::U32s(v) => v.serialize(serializer),
+            RszValue::Data(v) => v
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+                .serialize(serializer),
+            RszValue::Array(v) => v.serialize(serializer),
+        }
+    }
+}
+
+/// RSZ instance of a type described by the schema
+#[derive(Clone)]
+pub struct DynamicRsz {
+    schema: &'static RszTypeSchema,
+    pub fields: Vec<RszValue>,
+}
+
+impl Debug for DynamicRsz {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut s = f.debug_struct(&self.schema.name);
+        for (field, value) in self.schema.fields.iter().zip(&self.fields) {
+            s.field(&field.name, value);
+        }
+        s.finish()
+    }
+}
+
+impl Serialize for DynamicRsz {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.fields.len() + 1))?;
+        map.serialize_entry("$type", &self.schema.name)?;
+        for (field, value) in self.schema.fields.iter().zip(&self.fields) {
+            map.serialize_entry(&field.name, value)?;
+        }
+        map.end()
+    }
+}
+
+fn read_value(rsz: &mut RszDeserializer, field: &RszFieldSchema) -> Result<RszValue> {
+    rsz.cursor.seek_align_up(field.align)?;
+    let count = usize::try_from(field.size / 4)?;
+    Ok(match field.kind {
+        FieldKind::Bool => RszValue::Bool(rsz.read_bool()?),
+        FieldKind::S8 => RszValue::Int(rsz.read_i8()?.into()),
+        FieldKind::U8 => RszValue::Int(rsz.read_u8()?.into()),
+        FieldKind::S16 => RszValue::Int(rsz.read_i16()?.into()),
+        FieldKind::U16 => RszValue::Int(rsz.read_u16()?.into()),
+        FieldKind::S32 => RszValue::Int(rsz.read_i32()?.into()),
+        FieldKind::U32 => RszValue::Int(rsz.read_u32()?.into()),
+        FieldKind::S64 => RszValue::Int(rsz.read_i64()?),
+        FieldKind::U64 => RszValue::UInt(rsz.read_u64()?),
+        FieldKind::F32 => RszValue::F32(rsz.read_f32()?),
+        FieldKind::F64 => RszValue::F64(rsz.read_f64()?),
+        FieldKind::String => RszValue::String(Option::<String>::field_from_rsz(rsz)?),
+        FieldKind::Object => RszValue::Object(rsz.get_child_any_opt()?),
+        FieldKind::Guid => RszValue::Guid(Guid::field_from_rsz(rsz)?),
+        FieldKind::F32s => {
+            RszValue::F32s((0..count).map(|_| rsz.read_f32()).collect::<Result<_>>()?)
+        }
+        FieldKind::I32s => {
+            RszValue::I32s((0..count).map(|_| rsz.read_i32()).collect::<Result<_>>()?)
+        }
+        FieldKind::U32s => {
+            RszValue::U32s((0..count).map(|_| rsz.read_u32()).collect::<Result<_>>()?)
+        }
+        FieldKind::Data => {
+            let mut data = vec![0; usize::try_from(field.size)?];
+            rsz.read_exact(&mut data)?;
+            RszValue::Data(data)
+        }
+    })
+}
+
+fn write_value(rsz: &mut RszSerializer, field: &RszFieldSchema, value: &RszValue) -> Result<()> {
+    rsz.align_up(field.align);
+    let count = usize::try_from(field.size / 4)?;
+    match (field.kind, value) {
+        (FieldKind::Bool, RszValue::Bool(v)) => rsz.write_u8(u8::from(*v)),
+        (FieldKind::S8, RszValue::Int(v)) => rsz.write_i8(i8::try_from(*v)?),
+        (FieldKind::U8, RszValue::Int(v)) => rsz.write_u8(u8::try_from(*v)?),
+        (FieldKind::S16, RszValue::Int(v)) => rsz.write_i16(i16::try_from(*v)?),
+        (FieldKind::U16, RszValue::Int(v)) => rsz.write_u16(u16::try_from(*v)?),
+        (FieldKind::S32, RszValue::Int(v)) => rsz.write_i32(i32::try_from(*v)?),
+        (FieldKind::U32, RszValue::Int(v)) => rsz.write_u32(u32::try_from(*v)?),
+        (FieldKind::S64, RszValue::Int(v)) => rsz.write_i64(*v),
+        (FieldKind::U64, RszValue::UInt(v)) => rsz.write_u64(*v),
+        (FieldKind::F32, RszValue::F32(v)) => rsz.write_f32(*v),
+        (FieldKind::F64, RszValue::F64(v)) => rsz.write_f64(*v),
+        (FieldKind::String, RszValue::String(v)) => v.field_to_rsz(rsz),
+        (FieldKind::Object, RszValue::Object(v)) => {
+            let index = match v {
+                Some(child) => rsz.put_child_any(child)?,
+                None => 0,
+            };
+            rsz.write_u32(index)
+        }
+        (FieldKind::Guid, RszValue::Guid(v)) => v.field_to_rsz(rsz),
+        (FieldKind::F32s, RszValue::F32s(v)) if v.len() == count => {
+            v.iter().try_for_each(|&v| rsz.write_f32(v))
+        }
+        (FieldKind::I32s, RszValue::I32s(v)) if v.len() == count => {
+            v.iter().try_for_each(|&v| rsz.write_i32(v))
+        }
+        (FieldKind::U32s, RszValue::U32s(v)) if v.len() == count => {
+            v.iter().try_for_each(|&v| rsz.write_u32(v))
+        }
+        (FieldKind::Data, RszValue::Data(v)) if v.len() as u64 == field.size => {
+            rsz.write_all(v)?;
+            Ok(())
+        }
+        _ => bail!("Value {value:?} doesn't match the field type"),
+    }
+}
+
+fn dynamic_deserializer(
+    rsz: &mut RszDeserializer,
+    type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    let schema = type_schema(type_info)?;
+    let mut fields = vec![];
+    for field in &schema.fields {
+        let value = if field.array {
+            rsz.cursor.seek_align_up(4)?;
+            let count = rsz.read_u32()?;
+            (0..count)
+                .map(|_| read_value(rsz, field))
+                .collect::<Result<Vec<_>>>()
+                .map(RszValue::Array)
+        } else {
+            read_value(rsz, field)
+        }
+        .context(field.name.clone())?;
+        fields.push(value);
+    }
+    Ok(AnyRsz::new(DynamicRsz { schema, fields }, type_info))
+}
+
+fn resolve_value_externs(value: &mut RszValue, resolver: &mut ExternResolver) -> Result<()> {
+    match value {
+        RszValue::Object(Some(node)) => resolver.resolve_any(node),
+        RszValue::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                resolve_value_externs(value, resolver).with_context(|| format!("[{i}]"))?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn dynamic_resolve_externs(any: &mut dyn Any, resolver: &mut ExternResolver) -> Result<()> {
+    let node = any.downcast_mut::<DynamicRsz>().unwrap();
+    for (field, value) in node.schema.fields.iter().zip(&mut node.fields) {
+        resolve_value_externs(value, resolver).context(field.name.clone())?;
+    }
+    Ok(())
+}
+
+fn value_from_json(
+    json: &mut RszJsonReader,
+    field: &RszFieldSchema,
+    value: &serde_json::Value,
+) -> Result<RszValue> {
+    fn read_array<T: FieldFromRsz>(
+        json: &mut RszJsonReader,
+        field: &RszFieldSchema,
+        value: &serde_json::Value,
+    ) -> Result<Vec<T>> {
+        let values = json.read_array(value, T::field_from_json)?;
+        if values.len() as u64 != field.size / 4 {
+            bail!(
+                "Expected array size {}, found {}",
+                field.size / 4,
+                values.len()
+            )
+        }
+        Ok(values)
+    }
+    Ok(match field.kind {
+        FieldKind::Bool => RszValue::Bool(bool::field_from_json(json, value)?),
+        FieldKind::S8 => RszValue::Int(i8::field_from_json(json, value)?.into()),
+        FieldKind::U8 => RszValue::Int(u8::field_from_json(json, value)?.into()),
+        FieldKind::S16 => RszValue::Int(i16::field_from_json(json, value)?.into()),
+        FieldKind::U16 => RszValue::Int(u16::field_from_json(json, value)?.into()),
+        FieldKind::S32 => RszValue::Int(i32::field_from_json(json, value)?.into()),
+        FieldKind::U32 => RszValue::Int(u32::field_from_json(json, value)?.into()),
+        FieldKind::S64 => RszValue::Int(i64::field_from_json(json, value)?),
+        FieldKind::U64 => RszValue::UInt(u64::field_from_json(json, value)?),
+        FieldKind::F32 => RszValue::F32(f32::field_from_json(json, value)?),
+        FieldKind::F64 => RszValue::F64(f64::field_from_json(json, value)?),
+        FieldKind::String => RszValue::String(Option::<String>::field_from_json(json, value)?),
+        FieldKind::Object => RszValue::Object(match value {
+            serde_json::Value::Null => None,
+            value => {
+                // The declared type of a field is the best guess for a referenced USER file
+                let extern_type =
+                    (!field.original_type.is_empty()).then(|| hash_as_utf8(&field.original_type));
+                Some(json.read_any(value, extern_type)?)
+            }
+        }),
+        FieldKind::Guid => RszValue::Guid(Guid::field_from_json(json, value)?),
+        FieldKind::F32s => RszValue::F32s(read_array::<f32>(json, field, value)?),
+        FieldKind::I32s => RszValue::I32s(read_array::<i32>(json, field, value)?),
+        FieldKind::U32s => RszValue::U32s(read_array::<u32>(json, field, value)?),
+        FieldKind::Data => {
+            let hex = json.read_str(value)?;
+            if hex.len() as u64 != field.size * 2 || !hex.is_ascii() {
+                bail!("Expected {} bytes in hex, found {hex}", field.size)
+            }
+            let data = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("Bad hex {hex}"))?;
+            RszValue::Data(data)
+        }
+    })
+}
+
+fn dynamic_from_json(
+    json: &mut RszJsonReader,
+    value: &serde_json::Value,
+    type_info: &'static RszTypeInfo,
+) -> Result<AnyRsz> {
+    let schema = type_schema(type_info)?;
+    let fields = json.read_object(value, |json, value| {
+        schema
+            .fields
+            .iter()
+            .map(|field| {
+                let value = value
+                    .get(&field.name)
+                    .with_context(|| format!("Missing field {}", field.name))?;
+                json.mark_field(&field.name);
+                json.enter(format!(".{}", field.name), |json| {
+                    if field.array {
+                        json.read_array(value, |json, value| value_from_json(json, field, value))
+                            .map(RszValue::Array)
+                    } else {
+                        value_from_json(json, field, value)
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+    Ok(AnyRsz::new(DynamicRsz { schema, fields }, type_info))
+}
+
+fn dynamic_serializer(any: &dyn Any, rsz: &mut RszSerializer) -> Result<()> {
+    let node = any.downcast_ref::<DynamicRsz>().unwrap();
+    for (field, value) in node.schema.fields.iter().zip(&node.fields) {
+        if field.array {
+            let RszValue::Array(values) = value else {
+                bail!("Expected array for {}", field.name)
+            };
+            rsz.align_up(4);
+            rsz.write_u32(u32::try_from(values.len())?)?;
+            for value in values {
+                write_value(rsz, field, value).context(field.name.clone())?;
+            }
+        } else {
+            write_value(rsz, field, value).context(field.name.clone())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,303 @@
+use crate::magic::FileKind;
+use crate::pfb::Pfb;
+use crate::rsz::AnyRsz;
+use crate::scn::Scn;
+use crate::user::User;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::Cursor;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RszDiffChange {
+    Added { value: Value },
+    Removed { value: Value },
+    Changed { old: Value, new: Value },
+}
+
+#[derive(Debug, Serialize)]
+pub struct RszDiffEntry {
+    /// JSON path. Array elements matched by a key field are written as `[key=value]`
+    pub path: String,
+    #[serde(flatten)]
+    pub change: RszDiffChange,
+}
+
+impl Display for RszDiffEntry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.change {
+            RszDiffChange::Added { value } => write!(f, "+ {}: {}", self.path, value),
+            RszDiffChange::Removed { value } => write!(f, "- {}: {}", self.path, value),
+            RszDiffChange::Changed { old, new } => {
+                write!(f, "~ {}: {} -> {}", self.path, old, new)
+            }
+        }
+    }
+}
+
+/// Deserializes the RSZ data of a USER, PFB or SCN file as a JSON array of the roots.
+/// Instances that can't be read are kept opaque, and compared by their bytes.
+pub fn rsz_file_json(data: &[u8]) -> Result<Value> {
+    let file = Cursor::new(data);
+    let rsz = match FileKind::detect(data) {
+        Some(FileKind::User) => User::new(file)?.rsz,
+        Some(FileKind::Pfb) => Pfb::new(file)?.rsz,
+        Some(FileKind::Scn) => Scn::new(file)?.rsz,
+        _ => bail!("Not a USER, PFB or SCN file"),
+    };
+    let (roots, _) = rsz.deserialize_lenient(None)?;
+    let roots = AnyRsz::to_json_all(&roots)?
+        .iter()
+        .map(|root| Ok(serde_json::from_str(root)?))
+        .collect::<Result<Vec<Value>>>()?;
+    Ok(Value::Array(roots))
+}
+
+/// Structural diff of two JSON values written by `AnyRsz::to_json`.
+///
+/// Shared nodes are compared by their content, wherever they are written in full
+/// or referenced by `{"$ref": id}`.
+/// Elements of arrays of objects are matched by a key field when one identifies all of them,
+/// trying `keys` first, then fields named `id` or ending with `_id` or `_no`.
+/// Other arrays are matched by position, after skipping the equal elements at both ends.
+pub fn diff_json(old: &Value, new: &Value, keys: &[String]) -> Vec<RszDiffEntry> {
+    let mut old_shared = HashMap::new();
+    collect_shared(old, &mut old_shared);
+    let mut new_shared = HashMap::new();
+    collect_shared(new, &mut new_shared);
+    let mut differ = Differ {
+        keys,
+        old_shared,
+        new_shared,
+        entries: vec![],
+    };
+    differ.diff("$".to_owned(), old, new);
+    differ.entries
+}
+
+/// Shared nodes by their id, from each `{"$shared": id, "$value": node}`
+type SharedTable<'a> = HashMap<u64, &'a Value>;
+
+fn collect_shared<'a>(value: &'a Value, table: &mut SharedTable<'a>) {
+    match value {
+        Value::Object(map) => {
+            if let (Some(id), Some(node)) = (
+                map.get("$shared").and_then(Value::as_u64),
+                map.get("$value"),
+            ) {
+                table.insert(id, node);
+            }
+            for child in map.values() {
+                collect_shared(child, table)
+            }
+        }
+        Value::Array(array) => {
+            for child in array {
+                collect_shared(child, table)
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The node written in place of `{"$shared": id, "$value": node}` or `{"$ref": id}`.
+/// Shared ids are numbered per file, so they are not compared themselves.
+fn expand_shared<'a>(value: &'a Value, table: &SharedTable<'a>) -> &'a Value {
+    if let Some(node) = value.get("$shared").and(value.get("$value")) {
+        return node;
+    }
+    value
+        .get("$ref")
+        .and_then(Value::as_u64)
+        .and_then(|id| table.get(&id).copied())
+        .unwrap_or(value)
+}
+
+fn is_key_name(name: &str) -> bool {
+    name == "id" || name.ends_with("_id") || name.ends_with("_no")
+}
+
+fn element_key(element: &Value, key: &str, table: &SharedTable) -> Option<String> {
+    match expand_shared(element, table).get(key)? {
+        Value::Null | Value::Array(_) => None,
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+fn has_unique_key(array: &[Value], key: &str, table: &SharedTable) -> bool {
+    let mut seen = HashSet::new();
+    array
+        .iter()
+        .all(|element| element_key(element, key, table).is_some_and(|k| seen.insert(k)))
+}
+
+struct Differ<'a> {
+    keys: &'a [String],
+    old_shared: SharedTable<'a>,
+    new_shared: SharedTable<'a>,
+    entries: Vec<RszDiffEntry>,
+}
+
+impl<'a> Differ<'a> {
+    fn push(&mut self, path: String, change: RszDiffChange) {
+        self.entries.push(RszDiffEntry { path, change })
+    }
+
+    fn diff(&mut self, path: String, old: &'a Value, new: &'a Value) {
+        let old = expand_shared(old, &self.old_shared);
+        let new = expand_shared(new, &self.new_shared);
+        if self.same(old, new) {
+            return;
+        }
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) if old.get("$type") == new.get("$type") => {
+                for (name, old_value) in old {
+                    let path = format!("{path}.{name}");
+                    match new.get(name) {
+                        Some(new_value) => self.diff(path, old_value, new_value),
+                        None => self.push(
+                            path,
+                            RszDiffChange::Removed {
+                                value: old_value.clone(),
+                            },
+                        ),
+                    }
+                }
+                for (name, new_value) in new {
+                    if !old.contains_key(name) {
+                        self.push(
+                            format!("{path}.{name}"),
+                            RszDiffChange::Added {
+                                value: new_value.clone(),
+                            },
+                        )
+                    }
+                }
+            }
+            (Value::Array(old), Value::Array(new)) => self.diff_array(path, old, new),
+            (old, new) => self.push(
+                path,
+                RszDiffChange::Changed {
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+            ),
+        }
+    }
+
+    /// Equality with the shared nodes of both sides expanded
+    fn same(&self, old: &Value, new: &Value) -> bool {
+        let old = expand_shared(old, &self.old_shared);
+        let new = expand_shared(new, &self.new_shared);
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => {
+                old.len() == new.len()
+                    && old
+                        .iter()
+                        .all(|(name, o)| new.get(name).is_some_and(|n| self.same(o, n)))
+            }
+            (Value::Array(old), Value::Array(new)) => {
+                old.len() == new.len() && old.iter().zip(new).all(|(o, n)| self.same(o, n))
+            }
+            (old, new) => old == new,
+        }
+    }
+
+    fn find_key(&self, old: &[Value], new: &[Value]) -> Option<String> {
+        let first = match (old.first(), new.first()) {
+            (Some(first), _) => expand_shared(first, &self.old_shared),
+            (None, Some(first)) => expand_shared(first, &self.new_shared),
+            (None, None) => return None,
+        }
+        .as_object()?;
+        self.keys
+            .iter()
+            .chain(first.keys().filter(|name| is_key_name(name)))
+            .find(|key| {
+                has_unique_key(old, key, &self.old_shared)
+                    && has_unique_key(new, key, &self.new_shared)
+            })
+            .cloned()
+    }
+
+    fn diff_array(&mut self, path: String, old: &'a [Value], new: &'a [Value]) {
+        if let Some(key) = self.find_key(old, new) {
+            let new_by_key: HashMap<String, &Value> = new
+                .iter()
+                .map(|element| {
+                    (
+                        element_key(element, &key, &self.new_shared).unwrap(),
+                        element,
+                    )
+                })
+                .collect();
+            let mut old_keys = HashSet::new();
+            for old_element in old {
+                let k = element_key(old_element, &key, &self.old_shared).unwrap();
+                let element_path = format!("{path}[{key}={k}]");
+                match new_by_key.get(&k) {
+                    Some(new_element) => self.diff(element_path, old_element, new_element),
+                    None => self.push(
+                        element_path,
+                        RszDiffChange::Removed {
+                            value: old_element.clone(),
+                        },
+                    ),
+                }
+                old_keys.insert(k);
+            }
+            for new_element in new {
+                let k = element_key(new_element, &key, &self.new_shared).unwrap();
+                if !old_keys.contains(&k) {
+                    self.push(
+                        format!("{path}[{key}={k}]"),
+                        RszDiffChange::Added {
+                            value: new_element.clone(),
+                        },
+                    )
+                }
+            }
+            return;
+        }
+
+        // Skipping the equal ends keeps one inserted element from shifting all the following ones
+        let prefix = old
+            .iter()
+            .zip(new)
+            .take_while(|(o, n)| self.same(o, n))
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(o, n)| self.same(o, n))
+            .count();
+        let old_middle = &old[prefix..old.len() - suffix];
+        let new_middle = &new[prefix..new.len() - suffix];
+        for i in 0..old_middle.len().max(new_middle.len()) {
+            let element_path = format!("{path}[{}]", prefix + i);
+            match (old_middle.get(i), new_middle.get(i)) {
+                (Some(old_element), Some(new_element)) => {
+                    self.diff(element_path, old_element, new_element)
+                }
+                (Some(old_element), None) => self.push(
+                    element_path,
+                    RszDiffChange::Removed {
+                        value: old_element.clone(),
+                    },
+                ),
+                (None, Some(new_element)) => self.push(
+                    element_path,
+                    RszDiffChange::Added {
+                        value: new_element.clone(),
+                    },
+                ),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+}
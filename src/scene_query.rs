@@ -0,0 +1,300 @@
+use crate::scn::{Folder, GameObject, Scene};
+use anyhow::{bail, Context, Result};
+use nalgebra_glm::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Matches `text` against `pattern`, where `*` matches within a path segment,
+/// `**` matches across segments, and `?` matches one character other than `/`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn glob(p: &[char], t: &[char]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            ['*', '*', rest @ ..] => (0..=t.len()).any(|i| glob(rest, &t[i..])),
+            ['*', rest @ ..] => (0..=t.len())
+                .take_while(|&i| i == 0 || t[i - 1] != '/')
+                .any(|i| glob(rest, &t[i..])),
+            ['?', rest @ ..] => matches!(t, [c, ..] if *c != '/') && glob(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && glob(rest, &t[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    glob(&p, &t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Glob,
+}
+
+/// A condition on a field of a component, such as `pop_id=5`,
+/// `snow.stage.pop.ItemPopBehavior:pop_id>=100` or `name~Sm*`.
+///
+/// The field is a path into the JSON of the component, with segments joined by `.`,
+/// and array elements given by their index. The value is read as JSON if possible,
+/// otherwise as a string, so that enum variants can be written by name.
+/// For `~`, the value is kept as text and globbed against the field as written in JSON,
+/// without the quotes for strings.
+#[derive(Debug, Clone)]
+pub struct FieldPredicate {
+    /// Glob on the symbol of the component. Any component if None
+    component: Option<String>,
+    field: Vec<String>,
+    op: CompareOp,
+    text: String,
+    value: Value,
+}
+
+impl FromStr for FieldPredicate {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let op_start = s
+            .find(['=', '!', '<', '>', '~'])
+            .with_context(|| format!("No comparison in {s}"))?;
+        let (left, right) = s.split_at(op_start);
+        let (op, text) = [
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("=", CompareOp::Eq),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+            ("~", CompareOp::Glob),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| Some((op, right.strip_prefix(token)?)))
+        .with_context(|| format!("Bad comparison in {s}"))?;
+        let (component, field) = match left.split_once(':') {
+            Some((component, field)) => (Some(component.to_owned()), field),
+            None => (None, left),
+        };
+        if field.is_empty() {
+            bail!("No field in {s}")
+        }
+        Ok(FieldPredicate {
+            component,
+            field: field.split('.').map(str::to_owned).collect(),
+            op,
+            text: text.to_owned(),
+            value: match op {
+                CompareOp::Glob => Value::String(text.to_owned()),
+                _ => serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_owned())),
+            },
+        })
+    }
+}
+
+impl FieldPredicate {
+    fn test(&self, component: &Value) -> bool {
+        let mut field = component;
+        for segment in &self.field {
+            let next = match field {
+                Value::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get(i)),
+                Value::Object(object) => object.get(segment),
+                _ => None,
+            };
+            let Some(next) = next else {
+                return false;
+            };
+            field = next;
+        }
+
+        if self.op == CompareOp::Glob {
+            return match field {
+                Value::String(field) => glob_match(&self.text, field),
+                field => glob_match(&self.text, &field.to_string()),
+            };
+        }
+        let ordering = match (field, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a
+                .as_f64()
+                .partial_cmp(&b.as_f64())
+                .unwrap_or(std::cmp::Ordering::Less),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (a, b) => {
+                return match self.op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    _ => false,
+                }
+            }
+        };
+        match self.op {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+            CompareOp::Glob => unreachable!(),
+        }
+    }
+}
+
+/// Selects game objects in a scene. An object is selected if it passes all the conditions.
+#[derive(Debug, Default, Clone)]
+pub struct SceneQuery {
+    /// Glob on the name of the object
+    pub name: Option<String>,
+    /// Glob on the hierarchy path, made of the names of folders and objects joined by `/`
+    pub path: Option<String>,
+    /// Globs on component symbols, each matching one of the components
+    pub components: Vec<String>,
+    pub predicates: Vec<FieldPredicate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryMatch {
+    pub path: String,
+    pub name: Option<String>,
+    pub components: Vec<&'static str>,
+    pub position: Vec3,
+    /// Quaternion as [x, y, z, w]
+    pub rotation: Vec4,
+    pub scale: Vec3,
+}
+
+fn join_path(parent: &str, name: &Option<String>) -> String {
+    let name = name.as_deref().unwrap_or_default();
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}/{name}")
+    }
+}
+
+impl SceneQuery {
+    pub fn run(&self, scene: &Scene) -> Result<Vec<QueryMatch>> {
+        let mut matches = vec![];
+        self.query_scene(scene, "", &Mat4::identity(), &mut matches)?;
+        Ok(matches)
+    }
+
+    fn query_scene(
+        &self,
+        scene: &Scene,
+        path: &str,
+        world: &Mat4,
+        matches: &mut Vec<QueryMatch>,
+    ) -> Result<()> {
+        for object in &scene.objects {
+            self.query_object(object, path, world, matches)?;
+        }
+        for folder in &scene.folders {
+            self.query_folder(folder, path, world, matches)?;
+        }
+        Ok(())
+    }
+
+    fn query_folder(
+        &self,
+        folder: &Folder,
+        parent_path: &str,
+        world: &Mat4,
+        matches: &mut Vec<QueryMatch>,
+    ) -> Result<()> {
+        let path = join_path(parent_path, &folder.folder.name);
+        let world = &folder.world_matrix(world);
+        for object in &folder.children {
+            self.query_object(object, &path, world, matches)?;
+        }
+        for subfolder in &folder.subfolders {
+            self.query_folder(subfolder, &path, world, matches)?;
+        }
+        if let Some(Ok(subscene)) = &folder.subscene {
+            self.query_scene(subscene, &path, world, matches)?;
+        }
+        Ok(())
+    }
+
+    fn query_object(
+        &self,
+        object: &GameObject,
+        parent_path: &str,
+        parent_world: &Mat4,
+        matches: &mut Vec<QueryMatch>,
+    ) -> Result<()> {
+        let path = join_path(parent_path, &object.object.name);
+        let world = object.world_matrix(parent_world);
+
+        if self.matches(object, &path)? {
+            let scale = vec3(
+                world.column(0).xyz().norm(),
+                world.column(1).xyz().norm(),
+                world.column(2).xyz().norm(),
+            );
+            let rotation = mat3(
+                world[(0, 0)] / scale.x,
+                world[(0, 1)] / scale.y,
+                world[(0, 2)] / scale.z,
+                world[(1, 0)] / scale.x,
+                world[(1, 1)] / scale.y,
+                world[(1, 2)] / scale.z,
+                world[(2, 0)] / scale.x,
+                world[(2, 1)] / scale.y,
+                world[(2, 2)] / scale.z,
+            );
+            matches.push(QueryMatch {
+                path: path.clone(),
+                name: object.object.name.clone(),
+                components: object.components.iter().map(|c| c.symbol()).collect(),
+                position: world.column(3).xyz(),
+                rotation: mat3_to_quat(&rotation).coords,
+                scale,
+            });
+        }
+
+        for child in &object.children {
+            self.query_object(child, &path, &world, matches)?;
+        }
+        Ok(())
+    }
+
+    fn matches(&self, object: &GameObject, path: &str) -> Result<bool> {
+        if let Some(name) = &self.name {
+            if !glob_match(name, object.object.name.as_deref().unwrap_or_default()) {
+                return Ok(false);
+            }
+        }
+        if let Some(pattern) = &self.path {
+            if !glob_match(pattern, path) {
+                return Ok(false);
+            }
+        }
+        for symbol in &self.components {
+            if !object
+                .components
+                .iter()
+                .any(|c| glob_match(symbol, c.symbol()))
+            {
+                return Ok(false);
+            }
+        }
+        if self.predicates.is_empty() {
+            return Ok(true);
+        }
+
+        let components = object
+            .components
+            .iter()
+            .map(|c| Ok((c.symbol(), serde_json::from_str(&c.to_json()?)?)))
+            .collect::<Result<Vec<(&str, Value)>>>()?;
+        Ok(self.predicates.iter().all(|predicate| {
+            components.iter().any(|(symbol, value)| {
+                predicate
+                    .component
+                    .as_ref()
+                    .is_none_or(|pattern| glob_match(pattern, symbol))
+                    && predicate.test(value)
+            })
+        }))
+    }
+}
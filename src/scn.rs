@@ -1,17 +1,20 @@
+use crate::align::{align_up, pad16};
 use crate::file_ext::*;
 use crate::pak::*;
+use crate::pfb::{Pfb, PfbGameObject, PfbGameObjectRef};
 use crate::rsz;
 use crate::rsz::Rsz;
 use crate::user::UserChild;
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
+use nalgebra_glm::Mat4;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
-use std::io::{Cursor, Read, Seek};
-use std::rc::Rc;
+use std::io::{Cursor, Read, Seek, Write};
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct ScnGameObject {
-    #[allow(dead_code)]
     guid: rsz::Guid,
     object_index: u32,
     parent_index: Option<u32>, // could be a game object or a folder
@@ -199,6 +202,79 @@ impl Scn {
         })
     }
 
+    pub fn write<F: Write>(&self, mut file: F) -> Result<()> {
+        let folder_list_offset = 0x40 + self.game_objects.len() as u64 * 0x20;
+        let resource_list_offset = align_up(folder_list_offset + self.folders.len() as u64 * 8, 16);
+        let prefab_list_offset = align_up(
+            resource_list_offset + self.resource_names.len() as u64 * 8,
+            16,
+        );
+        let child_list_offset =
+            align_up(prefab_list_offset + self.prefab_paths.len() as u64 * 8, 16);
+        let mut string_offset = child_list_offset + self.children.len() as u64 * 0x10;
+
+        let mut strings = vec![];
+        let mut string_offsets = vec![];
+        for name in self
+            .resource_names
+            .iter()
+            .chain(&self.prefab_paths)
+            .chain(self.children.iter().map(|child| &child.name))
+        {
+            string_offsets.push(string_offset);
+            let len = strings.len();
+            strings.write_u16str(name)?;
+            string_offset += (strings.len() - len) as u64;
+        }
+        let rsz_offset = align_up(string_offset, 16);
+
+        let mut buf = Cursor::new(vec![]);
+        buf.write_magic(b"SCN\0")?;
+        buf.write_u32(u32::try_from(self.game_objects.len())?)?;
+        buf.write_u32(u32::try_from(self.resource_names.len())?)?;
+        buf.write_u32(u32::try_from(self.folders.len())?)?;
+        buf.write_u32(u32::try_from(self.prefab_paths.len())?)?;
+        buf.write_u32(u32::try_from(self.children.len())?)?;
+        buf.write_u64(folder_list_offset)?;
+        buf.write_u64(resource_list_offset)?;
+        buf.write_u64(prefab_list_offset)?;
+        buf.write_u64(child_list_offset)?;
+        buf.write_u64(rsz_offset)?;
+        for go in &self.game_objects {
+            buf.write_all(&go.guid.bytes)?;
+            buf.write_u32(go.object_index)?;
+            buf.write_u32(go.parent_index.unwrap_or(0xFFFFFFFF))?;
+            buf.write_u32(go.component_count)?;
+            buf.write_u32(go.prefab_index.unwrap_or(0xFFFFFFFF))?;
+        }
+        for folder in &self.folders {
+            buf.write_u32(folder.folder_object_index)?;
+            buf.write_u32(folder.parent_index.unwrap_or(0xFFFFFFFF))?;
+        }
+        pad16(&mut buf)?;
+        let (resource_string_offsets, rest) = string_offsets.split_at(self.resource_names.len());
+        let (prefab_string_offsets, child_string_offsets) = rest.split_at(self.prefab_paths.len());
+        for &offset in resource_string_offsets {
+            buf.write_u64(offset)?;
+        }
+        pad16(&mut buf)?;
+        for &offset in prefab_string_offsets {
+            buf.write_u64(offset)?;
+        }
+        pad16(&mut buf)?;
+        for (child, &offset) in self.children.iter().zip(child_string_offsets) {
+            buf.write_u32(child.hash)?;
+            buf.write_u32(0)?;
+            buf.write_u64(offset)?;
+        }
+        buf.write_all(&strings)?;
+        pad16(&mut buf)?;
+        self.rsz.write(&mut buf)?;
+
+        file.write_all(&buf.into_inner())?;
+        Ok(())
+    }
+
     pub fn dump(&self) {
         println!("Game objects:");
         for n in &self.game_objects {
@@ -264,15 +340,35 @@ impl Scn {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GameObject {
+    /// Zero for objects of prefabs
+    pub guid: rsz::Guid,
     pub object: rsz::GameObject,
     pub components: Vec<rsz::AnyRsz>,
-    pub prefab: Option<Rc<String>>,
+    pub prefab: Option<Arc<String>>,
     pub children: Vec<GameObject>,
+    /// Index of the RSZ root of the object in the file it was read from,
+    /// to carry over the references to it when writing the file back
+    source_index: Option<u32>,
+}
+
+fn name_is(name: &Option<String>, segment: &str) -> bool {
+    name.as_deref().unwrap_or_default() == segment
 }
 
 impl GameObject {
+    pub fn new(guid: rsz::Guid, object: rsz::GameObject) -> GameObject {
+        GameObject {
+            guid,
+            object,
+            components: vec![],
+            prefab: None,
+            children: vec![],
+            source_index: None,
+        }
+    }
+
     pub fn get_component<T: 'static>(&self) -> Result<&T> {
         let mut component: Option<&T> = None;
         for c in &self.components {
@@ -286,13 +382,93 @@ impl GameObject {
         component.context("No component found")
     }
 
-    pub fn for_each_child<F: FnMut(&GameObject) -> Result<bool /*scan_child*/>>(
+    pub fn get_component_mut<T: 'static>(&mut self) -> Result<&mut T> {
+        // Fails the same way as get_component
+        self.get_component::<T>()?;
+        self.components
+            .iter_mut()
+            .find(|c| c.downcast_ref::<T>().is_some())
+            .unwrap()
+            .downcast_mut()
+            .context("Component is shared")
+    }
+
+    pub fn add_component(&mut self, component: rsz::AnyRsz) {
+        self.components.push(component)
+    }
+
+    pub fn remove_component(&mut self, symbol: &str) -> Result<rsz::AnyRsz> {
+        let mut found = self
+            .components
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.symbol() == symbol)
+            .map(|(i, _)| i);
+        let index = found
+            .next()
+            .with_context(|| format!("No component {symbol} found"))?;
+        if found.next().is_some() {
+            bail!("Multiple components {symbol} found")
+        }
+        Ok(self.components.remove(index))
+    }
+
+    /// Fills in what the scene leaves out of a prefab instance:
+    /// components of types the scene doesn't override, and children the scene doesn't place.
+    /// Children are matched by name, and by order among the siblings of the same name.
+    fn apply_prefab(&mut self, prefab: &GameObject) {
+        for component in &prefab.components {
+            if !self
+                .components
+                .iter()
+                .any(|c| c.symbol() == component.symbol())
+            {
+                self.components.push(component.clone())
+            }
+        }
+        // Only the children placed by the scene are matched, each one at most once
+        let placed = self.children.len();
+        let mut same_name_count: HashMap<Option<&str>, usize> = HashMap::new();
+        for prefab_child in &prefab.children {
+            let name = prefab_child.object.name.as_deref();
+            let count = same_name_count.entry(name).or_default();
+            let matched = self.children[..placed]
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| child.object.name.as_deref() == name)
+                .nth(*count)
+                .map(|(i, _)| i);
+            *count += 1;
+            match matched {
+                Some(i) => self.children[i].apply_prefab(prefab_child),
+                None => self.children.push(prefab_child.clone()),
+            }
+        }
+    }
+
+    /// Matrix from the space of the object to the space of its parent.
+    /// Objects without a transform are placed at their parent.
+    pub fn local_matrix(&self) -> Mat4 {
+        self.get_component::<rsz::Transform>()
+            .map_or_else(|_| Mat4::identity(), |transform| transform.local_matrix())
+    }
+
+    /// Matrix from the space of the object to the world space, given the one of its parent
+    pub fn world_matrix(&self, parent_world: &Mat4) -> Mat4 {
+        parent_world * self.local_matrix()
+    }
+
+    /// Visits the descendants with their world matrix, given `world` of this object
+    /// and scanning the children of those for which `f` returns true
+    pub fn for_each_child_world<F: FnMut(&GameObject, &Mat4) -> Result<bool /*scan_child*/>>(
         &self,
+        world: &Mat4,
         f: &mut F,
     ) -> Result<()> {
         for object in &self.children {
-            if f(object)? {
-                object.for_each_child(f)?;
+            let object_world = object.world_matrix(world);
+            if f(object, &object_world)? {
+                object.for_each_child_world(&object_world, f)?;
             }
         }
         Ok(())
@@ -308,22 +484,33 @@ pub struct Folder {
 }
 
 impl Folder {
-    pub fn for_each_object<F: FnMut(&GameObject) -> Result<bool /*scan_child*/>>(
+    /// Matrix from the space of the folder content, including its subscene,
+    /// to the world space, given the one of its parent
+    pub fn world_matrix(&self, parent_world: &Mat4) -> Mat4 {
+        parent_world * self.folder.local_matrix()
+    }
+
+    /// Visits the objects of the folder, its subfolders and its subscene with their world matrix,
+    /// given `parent_world` of the parent folder or scene
+    pub fn for_each_object_world<F: FnMut(&GameObject, &Mat4) -> Result<bool /*scan_child*/>>(
         &self,
+        parent_world: &Mat4,
         f: &mut F,
     ) -> Result<()> {
+        let world = self.world_matrix(parent_world);
         for object in &self.children {
-            if f(object)? {
-                object.for_each_child(f)?;
+            let object_world = object.world_matrix(&world);
+            if f(object, &object_world)? {
+                object.for_each_child_world(&object_world, f)?;
             }
         }
 
         for folders in &self.subfolders {
-            folders.for_each_object(f)?
+            folders.for_each_object_world(&world, f)?
         }
 
         if let Some(Ok(subscene)) = &self.subscene {
-            subscene.for_each_object(f)?
+            subscene.for_each_object_world_from(&world, f)?
         }
 
         Ok(())
@@ -334,63 +521,232 @@ impl Folder {
 pub struct Scene {
     pub objects: Vec<GameObject>,
     pub folders: Vec<Folder>,
+    /// Instances of the scene file that couldn't be read. Not including subscenes
+    pub rsz_report: rsz::RszSkipReport,
+    /// Prefabs that couldn't be instantiated, with the reason. Not including subscenes
+    pub prefab_failures: BTreeMap<String, String>,
+    pub resource_names: Vec<String>,
+    /// CRC of the types in the file, used when writing it back
+    crc_hint: HashMap<u32, u32>,
+    prefabs_expanded: bool,
+    /// References between game objects, for scenes read from a PFB file
+    game_object_refs: Vec<SceneGameObjectRef>,
+}
+
+/// A `PfbGameObjectRef` tied to the objects instead of their RSZ root indices,
+/// so that it follows them through editing
+#[derive(Debug, Clone)]
+struct SceneGameObjectRef {
+    /// Source index of the game object holding the field, by itself or by one of its components
+    holder: u32,
+    /// Symbol of the instance holding the field
+    holder_symbol: &'static str,
+    property_id: u32,
+    array_index: u32,
+    /// Source index of the referenced game object
+    target: u32,
+}
+
+enum SceneNodeMut<'a> {
+    Object(&'a mut GameObject),
+    Folder(&'a mut Folder),
+}
+
+/// Lays out the RSZ roots and the tables of a SCN or PFB file,
+/// with each object followed by its components and then its children
+#[derive(Default)]
+struct SceneWriter {
+    roots: Vec<rsz::AnyRsz>,
+    game_objects: Vec<ScnGameObject>,
+    folders: Vec<ScnFolder>,
+    prefab_paths: Vec<String>,
+    /// Root index of the objects read from a file, by their source index
+    object_indices: HashMap<u32, u32>,
+}
+
+impl SceneWriter {
+    fn put_object(&mut self, object: &GameObject, parent_index: Option<u32>) -> Result<()> {
+        let object_index = u32::try_from(self.roots.len())?;
+        self.roots
+            .push(rsz::AnyRsz::from_node(object.object.clone())?);
+        self.roots.extend(object.components.iter().cloned());
+        let prefab_index = object
+            .prefab
+            .as_ref()
+            .map(|prefab| {
+                let index = match self.prefab_paths.iter().position(|p| **p == **prefab) {
+                    Some(index) => index,
+                    None => {
+                        self.prefab_paths.push(prefab.to_string());
+                        self.prefab_paths.len() - 1
+                    }
+                };
+                u32::try_from(index)
+            })
+            .transpose()?;
+        self.game_objects.push(ScnGameObject {
+            guid: object.guid,
+            object_index,
+            parent_index,
+            component_count: u32::try_from(object.components.len())?,
+            prefab_index,
+        });
+        if let Some(source_index) = object.source_index {
+            self.object_indices.insert(source_index, object_index);
+        }
+        for child in &object.children {
+            self.put_object(child, Some(object_index))?;
+        }
+        Ok(())
+    }
+
+    fn put_folder(&mut self, folder: &Folder, parent_index: Option<u32>) -> Result<()> {
+        let folder_object_index = u32::try_from(self.roots.len())?;
+        self.roots
+            .push(rsz::AnyRsz::from_node(folder.folder.clone())?);
+        self.folders.push(ScnFolder {
+            folder_object_index,
+            parent_index,
+        });
+        for object in &folder.children {
+            self.put_object(object, Some(folder_object_index))?;
+        }
+        for subfolder in &folder.subfolders {
+            self.put_folder(subfolder, Some(folder_object_index))?;
+        }
+        Ok(())
+    }
+}
+
+/// Root objects of the prefabs loaded so far, shared by a scene and its subscenes
+type PrefabCache = HashMap<String, Result<GameObject, String>>;
+
+/// Children of `parent` collected while walking the object list backward
+fn take_orphans<T>(orphans: &mut HashMap<Option<u32>, Vec<T>>, parent: Option<u32>) -> Vec<T> {
+    orphans
+        .remove(&parent)
+        .map_or_else(Vec::new, |mut children: Vec<T>| {
+            children.reverse();
+            children
+        })
+}
+
+/// Takes a game object and the components following it out of the RSZ roots
+fn take_game_object(
+    data: &mut [Option<rsz::AnyRsz>],
+    object_index: u32,
+    component_count: u32,
+) -> Result<(rsz::GameObject, Vec<rsz::AnyRsz>)> {
+    let object: Arc<rsz::GameObject> = data
+        .get_mut(usize::try_from(object_index)?)
+        .context("game object index out of bound")?
+        .take()
+        .context("game object data already taken")?
+        .downcast()
+        .context("GameObject type mismatch")?;
+    let object: rsz::GameObject =
+        Arc::try_unwrap(object).unwrap_or_else(|object| (*object).clone());
+    let components: Vec<rsz::AnyRsz> = (object_index + 1..=object_index + component_count)
+        .map(|i| {
+            data.get_mut(usize::try_from(i)?)
+                .context("component index out of bound")?
+                .take()
+                .context("component data already taken")
+        })
+        .collect::<Result<_>>()?;
+    Ok((object, components))
+}
+
+/// Adds the report to errors from objects missing because the RSZ reading stopped before them
+fn with_unread(error: anyhow::Error, report: &rsz::RszSkipReport) -> anyhow::Error {
+    match report.unread {
+        Some(_) => error.context(format!("The data is partially read:\n{report}")),
+        None => error,
+    }
+}
+
+fn load_prefab(pak: &SyncPakReader, path: &str) -> Result<GameObject> {
+    let index = pak.find_file(path)?;
+    let pfb = Pfb::new(Cursor::new(pak.read_file(index)?))?;
+    let mut roots = Scene::from_pfb(pfb)?.objects;
+    if roots.len() != 1 {
+        bail!("Expected one root object, found {}", roots.len())
+    }
+    Ok(roots.pop().unwrap())
 }
 
 impl Scene {
-    pub fn new<F: Read + Seek>(pak: &mut PakReader<F>, path: &str) -> Result<Scene> {
+    /// Loads the scene and its subscenes, with prefab instances expanded.
+    /// Objects of a prefab are attached under the instancing object,
+    /// with the objects and components placed by the scene taking precedence.
+    pub fn new(pak: &SyncPakReader, path: &str) -> Result<Scene> {
+        Scene::load(pak, path, &mut PrefabCache::new())
+    }
+
+    fn load(pak: &SyncPakReader, path: &str, prefab_cache: &mut PrefabCache) -> Result<Scene> {
         let index = pak.find_file(path)?;
         let content = pak.read_file(index)?;
         let scn = Scn::new(Cursor::new(content))?;
-        let mut data: Vec<Option<rsz::AnyRsz>> =
-            scn.rsz.deserialize(None)?.into_iter().map(Some).collect();
-        let prefabs: Vec<Rc<String>> = scn.prefab_paths.into_iter().map(Rc::new).collect();
+        Scene::assemble(scn, Some((pak, prefab_cache)))
+    }
+
+    /// Reads the scene as it is in the file, for editing and writing it back with `to_scn`.
+    /// Prefab instances are not expanded, and subscenes are not loaded.
+    pub fn from_scn(scn: Scn) -> Result<Scene> {
+        Scene::assemble(scn, None)
+    }
+
+    fn assemble(scn: Scn, mut expand: Option<(&SyncPakReader, &mut PrefabCache)>) -> Result<Scene> {
+        let crc_hint = scn
+            .rsz
+            .type_descriptors
+            .iter()
+            .map(|td| (td.hash, td.crc))
+            .collect();
+        let (data, rsz_report) = scn.rsz.deserialize_lenient(None)?;
+        let mut data: Vec<Option<rsz::AnyRsz>> = data.into_iter().map(Some).collect();
+        let prefabs: Vec<Arc<String>> = scn.prefab_paths.into_iter().map(Arc::new).collect();
 
         let mut orphans: HashMap<Option<u32>, Vec<GameObject>> = HashMap::new();
         let mut orphan_folders: HashMap<Option<u32>, Vec<Folder>> = HashMap::new();
+        let mut prefab_failures = BTreeMap::new();
 
         for go in scn.game_objects.into_iter().rev() {
-            let object: Rc<rsz::GameObject> = data
-                .get_mut(usize::try_from(go.object_index)?)
-                .context("game object index out of bound")?
-                .take()
-                .context("game object data already taken")?
-                .downcast()
-                .context("GameObject type mismatch")?;
-            let object: rsz::GameObject =
-                Rc::try_unwrap(object).map_err(|_| anyhow!("Shared node"))?;
-            let components: Vec<rsz::AnyRsz> = (go.object_index + 1
-                ..=go.object_index + go.component_count)
-                .map(|i| {
-                    data.get_mut(usize::try_from(i)?)
-                        .context("component index out of bound")?
-                        .take()
-                        .context("component data already taken")
-                })
-                .collect::<Result<_>>()?;
-            let prefab: Option<Rc<String>> = go
+            let (object, components) =
+                take_game_object(&mut data, go.object_index, go.component_count)
+                    .map_err(|e| with_unread(e, &rsz_report))?;
+            let prefab: Option<Arc<String>> = go
                 .prefab_index
-                .map(|i| -> Result<Rc<String>> {
+                .map(|i| -> Result<Arc<String>> {
                     Ok(prefabs
                         .get(usize::try_from(i)?)
                         .context("prefab index out of bound")?
                         .clone())
                 })
                 .transpose()?;
-            let children = orphans.remove(&Some(go.object_index)).map_or_else(
-                Vec::new,
-                |mut children: Vec<GameObject>| {
-                    children.reverse();
-                    children
-                },
-            );
-
-            let game_object = GameObject {
+            let children = take_orphans(&mut orphans, Some(go.object_index));
+
+            let mut game_object = GameObject {
+                guid: go.guid,
                 object,
                 components,
                 prefab,
                 children,
+                source_index: Some(go.object_index),
             };
 
+            if let (Some(prefab), Some((pak, prefab_cache))) = (&game_object.prefab, &mut expand) {
+                let instance = prefab_cache
+                    .entry(prefab.to_string())
+                    .or_insert_with(|| load_prefab(pak, prefab).map_err(|e| format!("{e:#}")));
+                match instance {
+                    Ok(instance) => game_object.apply_prefab(instance),
+                    Err(e) => {
+                        prefab_failures.insert(prefab.to_string(), e.clone());
+                    }
+                }
+            }
+
             orphans
                 .entry(go.parent_index)
                 .or_default()
@@ -398,31 +754,24 @@ impl Scene {
         }
 
         for f in scn.folders.into_iter().rev() {
-            let folder: Rc<rsz::Folder> = data
+            let folder: Arc<rsz::Folder> = data
                 .get_mut(usize::try_from(f.folder_object_index)?)
                 .context("folder index out of bound")?
                 .take()
                 .context("folder data already taken")?
                 .downcast()
-                .context("Folder type mismatch")?;
-            let folder: rsz::Folder = Rc::try_unwrap(folder).map_err(|_| anyhow!("Shared node"))?;
-            let subscene = folder
-                .path
-                .as_ref()
-                .and_then(|p| (!p.is_empty()).then(|| Scene::new(pak, p)));
-            let children = orphans.remove(&Some(f.folder_object_index)).map_or_else(
-                Vec::new,
-                |mut children: Vec<GameObject>| {
-                    children.reverse();
-                    children
-                },
-            );
-            let subfolders = orphan_folders
-                .remove(&Some(f.folder_object_index))
-                .map_or_else(Vec::new, |mut children: Vec<Folder>| {
-                    children.reverse();
-                    children
-                });
+                .context("Folder type mismatch")
+                .map_err(|e| with_unread(e, &rsz_report))?;
+            let folder: rsz::Folder =
+                Arc::try_unwrap(folder).unwrap_or_else(|folder| (*folder).clone());
+            let subscene = match (&folder.path, &mut expand) {
+                (Some(p), Some((pak, prefab_cache))) if !p.is_empty() => {
+                    Some(Scene::load(pak, p, prefab_cache))
+                }
+                _ => None,
+            };
+            let children = take_orphans(&mut orphans, Some(f.folder_object_index));
+            let subfolders = take_orphans(&mut orphan_folders, Some(f.folder_object_index));
             let folder = Folder {
                 folder,
                 subscene,
@@ -440,21 +789,8 @@ impl Scene {
             bail!("Left over data")
         }
 
-        let objects =
-            orphans
-                .remove(&None)
-                .map_or_else(Vec::new, |mut children: Vec<GameObject>| {
-                    children.reverse();
-                    children
-                });
-
-        let folders =
-            orphan_folders
-                .remove(&None)
-                .map_or_else(Vec::new, |mut children: Vec<Folder>| {
-                    children.reverse();
-                    children
-                });
+        let objects = take_orphans(&mut orphans, None);
+        let folders = take_orphans(&mut orphan_folders, None);
 
         if !orphans.is_empty() {
             bail!("Found orphan game object")
@@ -464,21 +800,350 @@ impl Scene {
             bail!("Found orphan folder")
         }
 
-        Ok(Scene { objects, folders })
+        Ok(Scene {
+            objects,
+            folders,
+            rsz_report,
+            prefab_failures,
+            resource_names: scn.resource_names,
+            crc_hint,
+            prefabs_expanded: expand.is_some(),
+            game_object_refs: vec![],
+        })
+    }
+
+    /// Reads the objects of a prefab as a scene without folders
+    pub fn from_pfb(pfb: Pfb) -> Result<Scene> {
+        let crc_hint = pfb
+            .rsz
+            .type_descriptors
+            .iter()
+            .map(|td| (td.hash, td.crc))
+            .collect();
+        let (data, rsz_report) = pfb.rsz.deserialize_lenient(None)?;
+        let mut data: Vec<Option<rsz::AnyRsz>> = data.into_iter().map(Some).collect();
+
+        // Game object and symbol of each root, to tie the references to them
+        let mut holders = HashMap::new();
+        for go in &pfb.game_objects {
+            for index in go.object_index..=go.object_index + go.component_count {
+                let symbol = data
+                    .get(usize::try_from(index)?)
+                    .and_then(|d| d.as_ref())
+                    .context("game object index out of bound")?
+                    .symbol();
+                holders.insert(index, (go.object_index, symbol));
+            }
+        }
+        let game_object_refs = pfb
+            .game_object_refs
+            .iter()
+            .map(|r| {
+                let &(holder, holder_symbol) = holders
+                    .get(&r.object_id)
+                    .context("Reference from outside of the game objects")?;
+                if holders.get(&r.target_id).map(|&(object, _)| object) != Some(r.target_id) {
+                    bail!("Reference to something other than a game object")
+                }
+                Ok(SceneGameObjectRef {
+                    holder,
+                    holder_symbol,
+                    property_id: r.property_id,
+                    array_index: r.array_index,
+                    target: r.target_id,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut orphans: HashMap<Option<u32>, Vec<GameObject>> = HashMap::new();
+        for go in pfb.game_objects.into_iter().rev() {
+            let (object, components) =
+                take_game_object(&mut data, go.object_index, go.component_count)
+                    .map_err(|e| with_unread(e, &rsz_report))?;
+            let children = take_orphans(&mut orphans, Some(go.object_index));
+            orphans
+                .entry(go.parent_index)
+                .or_default()
+                .push(GameObject {
+                    guid: rsz::Guid { bytes: [0; 16] },
+                    object,
+                    components,
+                    prefab: None,
+                    children,
+                    source_index: Some(go.object_index),
+                });
+        }
+
+        let objects = take_orphans(&mut orphans, None);
+        if !orphans.is_empty() {
+            bail!("Found orphan game object")
+        }
+
+        Ok(Scene {
+            objects,
+            folders: vec![],
+            rsz_report,
+            prefab_failures: BTreeMap::new(),
+            resource_names: pfb.resource_names,
+            crc_hint,
+            prefabs_expanded: false,
+            game_object_refs,
+        })
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.prefabs_expanded {
+            bail!("The scene is loaded with prefabs expanded. Read it with Scene::from_scn to write it back")
+        }
+        Ok(())
+    }
+
+    /// Builds the SCN file of the scene. Subscenes are separate files, and are not included.
+    /// Types in the original file keep their CRC, while new ones take the latest one
+    /// not newer than `version`.
+    pub fn to_scn(&self, version: u32) -> Result<Scn> {
+        self.check_writable()?;
+        let mut writer = SceneWriter::default();
+        for object in &self.objects {
+            writer.put_object(object, None)?;
+        }
+        for folder in &self.folders {
+            writer.put_folder(folder, None)?;
+        }
+        let rsz = Rsz::serialize(&writer.roots, version, &self.crc_hint)?;
+        Ok(Scn {
+            game_objects: writer.game_objects,
+            folders: writer.folders,
+            resource_names: self.resource_names.clone(),
+            prefab_paths: writer.prefab_paths,
+            children: UserChild::from_extern_slots(&rsz),
+            rsz,
+        })
+    }
+
+    /// Builds the PFB file of the scene, which must have neither folders nor prefab instances.
+    /// References from or to removed game objects are dropped.
+    pub fn to_pfb(&self, version: u32) -> Result<Pfb> {
+        self.check_writable()?;
+        if !self.folders.is_empty() {
+            bail!("Prefabs can't have folders")
+        }
+        let mut writer = SceneWriter::default();
+        for object in &self.objects {
+            writer.put_object(object, None)?;
+        }
+        if !writer.prefab_paths.is_empty() {
+            bail!("Prefabs can't instantiate prefabs")
+        }
+
+        let mut game_object_refs = vec![];
+        for r in &self.game_object_refs {
+            let (Some(&holder), Some(&target_id)) = (
+                writer.object_indices.get(&r.holder),
+                writer.object_indices.get(&r.target),
+            ) else {
+                continue;
+            };
+            let go = writer
+                .game_objects
+                .iter()
+                .find(|go| go.object_index == holder)
+                .unwrap();
+            let Some(offset) = writer.roots
+                [usize::try_from(holder)?..=usize::try_from(holder + go.component_count)?]
+                .iter()
+                .position(|node| node.symbol() == r.holder_symbol)
+            else {
+                continue;
+            };
+            game_object_refs.push(PfbGameObjectRef {
+                object_id: holder + u32::try_from(offset)?,
+                property_id: r.property_id,
+                array_index: r.array_index,
+                target_id,
+            });
+        }
+
+        let rsz = Rsz::serialize(&writer.roots, version, &self.crc_hint)?;
+        Ok(Pfb {
+            game_objects: writer
+                .game_objects
+                .into_iter()
+                .map(|go| PfbGameObject {
+                    object_index: go.object_index,
+                    parent_index: go.parent_index,
+                    component_count: go.component_count,
+                })
+                .collect(),
+            game_object_refs,
+            resource_names: self.resource_names.clone(),
+            children: UserChild::from_extern_slots(&rsz),
+            rsz,
+        })
+    }
+
+    /// Finds an object or a folder by its path of names joined by `/`, as in `SceneQuery`
+    fn node_mut(&mut self, path: &str) -> Result<SceneNodeMut<'_>> {
+        fn find<'a>(
+            objects: &'a mut [GameObject],
+            folders: Option<&'a mut Vec<Folder>>,
+            segments: &[&str],
+            path: &str,
+        ) -> Result<SceneNodeMut<'a>> {
+            let (segment, rest) = segments
+                .split_first()
+                .with_context(|| format!("Empty path {path}"))?;
+            let object_count = objects
+                .iter()
+                .filter(|o| name_is(&o.object.name, segment))
+                .count();
+            let folder_count = folders.as_ref().map_or(0, |folders| {
+                folders
+                    .iter()
+                    .filter(|f| name_is(&f.folder.name, segment))
+                    .count()
+            });
+            let node = match (object_count, folder_count) {
+                (1, 0) => SceneNodeMut::Object(
+                    objects
+                        .iter_mut()
+                        .find(|o| name_is(&o.object.name, segment))
+                        .unwrap(),
+                ),
+                (0, 1) => SceneNodeMut::Folder(
+                    folders
+                        .unwrap()
+                        .iter_mut()
+                        .find(|f| name_is(&f.folder.name, segment))
+                        .unwrap(),
+                ),
+                (0, 0) => bail!("Nothing named {segment} in {path}"),
+                _ => bail!("Several objects or folders named {segment} in {path}"),
+            };
+            if rest.is_empty() {
+                return Ok(node);
+            }
+            match node {
+                SceneNodeMut::Object(object) => find(&mut object.children, None, rest, path),
+                SceneNodeMut::Folder(folder) => find(
+                    &mut folder.children,
+                    Some(&mut folder.subfolders),
+                    rest,
+                    path,
+                ),
+            }
+        }
+        let segments: Vec<&str> = path.split('/').collect();
+        find(&mut self.objects, Some(&mut self.folders), &segments, path)
+    }
+
+    /// Objects directly under the folder or the object at `parent`, or at the root if None
+    fn children_mut(&mut self, parent: Option<&str>) -> Result<&mut Vec<GameObject>> {
+        Ok(match parent {
+            None => &mut self.objects,
+            Some(parent) => match self.node_mut(parent)? {
+                SceneNodeMut::Object(object) => &mut object.children,
+                SceneNodeMut::Folder(folder) => &mut folder.children,
+            },
+        })
+    }
+
+    pub fn object_mut(&mut self, path: &str) -> Result<&mut GameObject> {
+        match self.node_mut(path)? {
+            SceneNodeMut::Object(object) => Ok(object),
+            SceneNodeMut::Folder(_) => bail!("{path} is a folder"),
+        }
+    }
+
+    /// Adds an object under the folder or the object at `parent`, or at the root if None
+    pub fn add_object(&mut self, parent: Option<&str>, object: GameObject) -> Result<()> {
+        self.children_mut(parent)?.push(object);
+        Ok(())
+    }
+
+    pub fn remove_object(&mut self, path: &str) -> Result<GameObject> {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (Some(parent), name),
+            None => (None, path),
+        };
+        let children = self.children_mut(parent)?;
+        let mut found = children
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| name_is(&o.object.name, name))
+            .map(|(i, _)| i);
+        let index = found
+            .next()
+            .with_context(|| format!("No object at {path}"))?;
+        if found.next().is_some() {
+            bail!("Several objects at {path}")
+        }
+        Ok(children.remove(index))
+    }
+
+    /// Moves an object under another folder or object, or at the root if None,
+    /// keeping its local transform
+    pub fn move_object(&mut self, path: &str, new_parent: Option<&str>) -> Result<()> {
+        if let Some(new_parent) = new_parent {
+            if new_parent == path || new_parent.starts_with(&format!("{path}/")) {
+                bail!("Can't move {path} into itself")
+            }
+            // Checks the destination before taking the object out
+            self.node_mut(new_parent)?;
+        }
+        let object = self.remove_object(path)?;
+        self.add_object(new_parent, object)
+    }
+
+    /// Prints the instances skipped and the prefabs not instantiated
+    /// when reading the scene and its subscenes
+    pub fn print_rsz_reports(&self, path: &str) {
+        fn print_folder(folder: &Folder) {
+            if let (Some(Ok(subscene)), Some(path)) = (&folder.subscene, &folder.folder.path) {
+                subscene.print_rsz_reports(path)
+            }
+            for subfolder in &folder.subfolders {
+                print_folder(subfolder)
+            }
+        }
+        if !self.rsz_report.is_empty() {
+            eprint!("Scene {path} is partially read:\n{}", self.rsz_report);
+        }
+        for (prefab, reason) in &self.prefab_failures {
+            eprintln!("Scene {path} failed to instantiate {prefab}: {reason}");
+        }
+        for folder in &self.folders {
+            print_folder(folder)
+        }
+    }
+
+    /// Visits the objects of the scene with their world matrix, composed from the transforms
+    /// of their parents and the offsets of the folders containing them.
+    /// Children are scanned for the objects for which `f` returns true.
+    pub fn for_each_object_world<F: FnMut(&GameObject, &Mat4) -> Result<bool /*scan_child*/>>(
+        &self,
+        f: &mut F,
+    ) -> Result<()> {
+        self.for_each_object_world_from(&Mat4::identity(), f)
     }
 
-    pub fn for_each_object<F: FnMut(&GameObject) -> Result<bool /*scan_child*/>>(
+    /// Like `for_each_object_world`, for a scene placed with the world matrix `world`
+    pub fn for_each_object_world_from<
+        F: FnMut(&GameObject, &Mat4) -> Result<bool /*scan_child*/>,
+    >(
         &self,
+        world: &Mat4,
         f: &mut F,
     ) -> Result<()> {
         for object in &self.objects {
-            if f(object)? {
-                object.for_each_child(f)?;
+            let object_world = object.world_matrix(world);
+            if f(object, &object_world)? {
+                object.for_each_child_world(&object_world, f)?;
             }
         }
 
         for folder in &self.folders {
-            folder.for_each_object(f)?;
+            folder.for_each_object_world(world, f)?;
         }
 
         Ok(())
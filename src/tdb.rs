@@ -4,8 +4,9 @@ use crate::hash::*;
 use anyhow::{bail, Context, Result};
 use bitflags::*;
 use serde::*;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
@@ -315,6 +316,28 @@ fn display_type_flags(flags: TypeFlag) -> String {
     s
 }
 
+fn display_value(value: &ValueInfo) -> String {
+    match value {
+        ValueInfo::String(s) => format!("\"{s}\""),
+        ValueInfo::Bytes(b) => match b.len() {
+            1 => format!("0x{:02X}", b[0]),
+            2 => format!(
+                "0x{:04X}",
+                u16::from_le_bytes(b.as_slice().try_into().unwrap())
+            ),
+            4 => format!(
+                "0x{:08X}",
+                u32::from_le_bytes(b.as_slice().try_into().unwrap())
+            ),
+            8 => format!(
+                "0x{:016X}",
+                u64::from_le_bytes(b.as_slice().try_into().unwrap())
+            ),
+            _ => format!("{b:?}"),
+        },
+    }
+}
+
 fn display_field_attributes(attributes: FieldAttribute) -> String {
     let mut s = String::new();
 
@@ -781,7 +804,7 @@ struct AssemblyInfo {
 }
 
 #[derive(Serialize)]
-struct Tdb {
+pub struct Tdb {
     types: Vec<TypeInfo>,
     intern_strings: Vec<String>,
     assemblies: Vec<AssemblyInfo>,
@@ -2184,28 +2207,8 @@ impl Tdb {
             };
 
         fn print_constant(value: &Option<ValueInfo>, output: &mut File) -> Result<()> {
-            match value {
-                None => (),
-                Some(ValueInfo::String(s)) => write!(output, " = \"{s}\"")?,
-                Some(ValueInfo::Bytes(b)) => match b.len() {
-                    1 => write!(output, " = 0x{:02X}", b[0])?,
-                    2 => write!(
-                        output,
-                        " = 0x{:04X}",
-                        u16::from_le_bytes(b.as_slice().try_into().unwrap())
-                    )?,
-                    4 => write!(
-                        output,
-                        " = 0x{:08X}",
-                        u32::from_le_bytes(b.as_slice().try_into().unwrap())
-                    )?,
-                    8 => write!(
-                        output,
-                        " = 0x{:016X}",
-                        u64::from_le_bytes(b.as_slice().try_into().unwrap())
-                    )?,
-                    _ => write!(output, " = {b:?}")?,
-                },
+            if let Some(value) = value {
+                write!(output, " = {}", display_value(value))?;
             }
             Ok(())
         }
@@ -2424,6 +2427,291 @@ impl Tdb {
     }
 }
 
+/// The members of a type compared by `TdbDiff`, each written as a string
+#[derive(Default)]
+struct TypeOutline {
+    /// Type and offset, by field name
+    fields: BTreeMap<String, String>,
+    /// Parameter names of the overloads by signature, by method name.
+    /// Signatures only have the types, so that renamed parameters don't make new overloads.
+    methods: BTreeMap<String, BTreeMap<String, String>>,
+    /// Values of the literal fields, such as enum variants, by name
+    constants: BTreeMap<String, String>,
+}
+
+impl Tdb {
+    /// Outline of each type by full name and assembly name.
+    /// Types that share both are reported, and only the last one is kept.
+    fn outlines(&self) -> BTreeMap<(&str, &str), TypeOutline> {
+        let type_name = |ti: usize| self.types[ti].full_name.as_str();
+        let mut outlines = BTreeMap::new();
+        for type_info in &self.types {
+            let assembly = self
+                .assemblies
+                .get(type_info.assembly)
+                .map_or("", |assembly| assembly.name.as_str());
+            let mut outline = TypeOutline::default();
+            for field in &type_info.fields {
+                if field.flags.contains(FieldAttribute::LITERAL) {
+                    let value = field.value.as_ref().map_or_else(String::new, display_value);
+                    outline.constants.insert(field.name.clone(), value);
+                } else {
+                    let is_static = if field.flags.contains(FieldAttribute::STATIC) {
+                        "static "
+                    } else {
+                        ""
+                    };
+                    outline.fields.insert(
+                        field.name.clone(),
+                        format!(
+                            "{is_static}{} @ 0x{:X}",
+                            type_name(field.ti),
+                            field.position
+                        ),
+                    );
+                }
+            }
+            for method in &type_info.methods {
+                let param_types: Vec<&str> = method
+                    .params
+                    .iter()
+                    .map(|param| type_name(param.ti))
+                    .collect();
+                let param_names: Vec<&str> = method
+                    .params
+                    .iter()
+                    .map(|param| param.name.as_str())
+                    .collect();
+                outline
+                    .methods
+                    .entry(method.name.clone())
+                    .or_default()
+                    .insert(
+                        format!(
+                            "{} {}({})",
+                            type_name(method.ret.ti),
+                            method.name,
+                            param_types.join(", ")
+                        ),
+                        param_names.join(", "),
+                    );
+            }
+            if outlines
+                .insert((type_info.full_name.as_str(), assembly), outline)
+                .is_some()
+            {
+                eprintln!(
+                    "Multiple types named {} in assembly {assembly}, only the last one is compared",
+                    type_info.full_name
+                );
+            }
+        }
+        outlines
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TdbChange {
+    Added { value: String },
+    Removed { value: String },
+    Changed { old: String, new: String },
+}
+
+#[derive(Debug, Serialize)]
+pub struct TdbMemberDiff {
+    /// `field`, `method`, `parameter_names` or `constant`.
+    /// The name of `parameter_names` is the signature of the overload.
+    pub member: &'static str,
+    pub name: String,
+    #[serde(flatten)]
+    pub change: TdbChange,
+}
+
+/// Differences between the types of two TDBs, matched by full name and assembly.
+/// The assembly is written after the names that several types share, as `name [assembly]`.
+#[derive(Debug, Default, Serialize)]
+pub struct TdbDiff {
+    pub added_types: Vec<String>,
+    pub removed_types: Vec<String>,
+    pub changed_types: BTreeMap<String, Vec<TdbMemberDiff>>,
+}
+
+fn diff_members(
+    member: &'static str,
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    changes: &mut Vec<TdbMemberDiff>,
+) {
+    for (name, old_value) in old {
+        let change = match new.get(name) {
+            Some(new_value) if new_value == old_value => continue,
+            Some(new_value) => TdbChange::Changed {
+                old: old_value.clone(),
+                new: new_value.clone(),
+            },
+            None => TdbChange::Removed {
+                value: old_value.clone(),
+            },
+        };
+        changes.push(TdbMemberDiff {
+            member,
+            name: name.clone(),
+            change,
+        })
+    }
+    for (name, new_value) in new {
+        if !old.contains_key(name) {
+            changes.push(TdbMemberDiff {
+                member,
+                name: name.clone(),
+                change: TdbChange::Added {
+                    value: new_value.clone(),
+                },
+            })
+        }
+    }
+}
+
+/// Overloads are matched by signature. A method with a single overload on both sides
+/// is reported as changed rather than removed and added.
+fn diff_methods(
+    old: &BTreeMap<String, BTreeMap<String, String>>,
+    new: &BTreeMap<String, BTreeMap<String, String>>,
+    changes: &mut Vec<TdbMemberDiff>,
+) {
+    let empty = BTreeMap::new();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let old = old.get(name).unwrap_or(&empty);
+        let new = new.get(name).unwrap_or(&empty);
+        let removed: Vec<&String> = old.keys().filter(|s| !new.contains_key(*s)).collect();
+        let added: Vec<&String> = new.keys().filter(|s| !old.contains_key(*s)).collect();
+        for (signature, old_params) in old {
+            match new.get(signature) {
+                Some(new_params) if new_params != old_params => changes.push(TdbMemberDiff {
+                    member: "parameter_names",
+                    name: signature.clone(),
+                    change: TdbChange::Changed {
+                        old: old_params.clone(),
+                        new: new_params.clone(),
+                    },
+                }),
+                _ => (),
+            }
+        }
+        let mut push = |change| {
+            changes.push(TdbMemberDiff {
+                member: "method",
+                name: name.clone(),
+                change,
+            })
+        };
+        if let ([old], [new]) = (removed.as_slice(), added.as_slice()) {
+            push(TdbChange::Changed {
+                old: (*old).clone(),
+                new: (*new).clone(),
+            });
+            continue;
+        }
+        for value in removed {
+            push(TdbChange::Removed {
+                value: value.clone(),
+            })
+        }
+        for value in added {
+            push(TdbChange::Added {
+                value: value.clone(),
+            })
+        }
+    }
+}
+
+impl TdbDiff {
+    pub fn new(old: &Tdb, new: &Tdb) -> TdbDiff {
+        let old = old.outlines();
+        let new = new.outlines();
+
+        // Names shared by several types in either TDB. They are next to each other in the keys.
+        let shared_names: HashSet<&str> = [&old, &new]
+            .into_iter()
+            .flat_map(|outlines| {
+                outlines
+                    .keys()
+                    .zip(outlines.keys().skip(1))
+                    .filter(|(a, b)| a.0 == b.0)
+                    .map(|(a, _)| a.0)
+            })
+            .collect();
+        let display_name = |&(name, assembly): &(&str, &str)| {
+            if shared_names.contains(name) {
+                format!("{name} [{assembly}]")
+            } else {
+                name.to_owned()
+            }
+        };
+
+        let mut diff = TdbDiff::default();
+        for (key, old_outline) in &old {
+            let Some(new_outline) = new.get(key) else {
+                diff.removed_types.push(display_name(key));
+                continue;
+            };
+            let mut changes = vec![];
+            diff_members(
+                "field",
+                &old_outline.fields,
+                &new_outline.fields,
+                &mut changes,
+            );
+            diff_methods(&old_outline.methods, &new_outline.methods, &mut changes);
+            diff_members(
+                "constant",
+                &old_outline.constants,
+                &new_outline.constants,
+                &mut changes,
+            );
+            if !changes.is_empty() {
+                diff.changed_types.insert(display_name(key), changes);
+            }
+        }
+        diff.added_types = new
+            .keys()
+            .filter(|key| !old.contains_key(*key))
+            .map(display_name)
+            .collect();
+        diff
+    }
+}
+
+impl Display for TdbDiff {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for name in &self.added_types {
+            writeln!(f, "+ type {name}")?;
+        }
+        for name in &self.removed_types {
+            writeln!(f, "- type {name}")?;
+        }
+        for (name, changes) in &self.changed_types {
+            writeln!(f, "~ type {name}")?;
+            for c in changes {
+                match &c.change {
+                    TdbChange::Added { value } => {
+                        writeln!(f, "    + {} {}: {}", c.member, c.name, value)?
+                    }
+                    TdbChange::Removed { value } => {
+                        writeln!(f, "    - {} {}: {}", c.member, c.name, value)?
+                    }
+                    TdbChange::Changed { old, new } => {
+                        writeln!(f, "    ~ {} {}: {} -> {}", c.member, c.name, old, new)?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn print<F: Read + Seek>(file: F, base_address: u64, options: crate::TdbOptions) -> Result<()> {
     if options.json.is_none() && options.json_split.is_none() && options.cs.is_none() {
         eprintln!("Please specify at least one of --json, --json-split, --map, --cs");
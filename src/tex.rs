@@ -544,6 +544,14 @@ impl Tex {
         })
     }
 
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn mipmap_count(&self) -> usize {
+        self.textures.first().map_or(0, |mipmaps| mipmaps.len())
+    }
+
     pub fn to_rgba(&self, index: usize, mipmap: usize) -> anyhow::Result<RgbaImage> {
         self.to_rgba_swizzle(index, mipmap, "rgba")
     }
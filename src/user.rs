@@ -1,7 +1,8 @@
+use crate::align::{align_up, pad16};
 use crate::file_ext::*;
 use crate::rsz::Rsz;
 use anyhow::{bail, Context, Result};
-use std::io::{Read, Seek};
+use std::io::{Cursor, Read, Seek, Write};
 
 #[derive(Debug)]
 pub struct UserChild {
@@ -9,6 +10,21 @@ pub struct UserChild {
     pub name: String,
 }
 
+impl UserChild {
+    /// The USER files referenced by the RSZ data, in the order of their slots
+    pub fn from_extern_slots(rsz: &Rsz) -> Vec<UserChild> {
+        let mut slots: Vec<_> = rsz.extern_slots.iter().collect();
+        slots.sort_by_key(|&(&index, _)| index);
+        slots
+            .into_iter()
+            .map(|(_, slot)| UserChild {
+                hash: slot.hash,
+                name: slot.path.clone(),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct User {
     pub resource_names: Vec<String>,
@@ -86,4 +102,54 @@ impl User {
             rsz,
         })
     }
+
+    pub fn write<F: Write>(&self, mut file: F) -> Result<()> {
+        let resource_list_offset = align_up(0x28, 16);
+        let child_list_offset = align_up(
+            resource_list_offset + self.resource_names.len() as u64 * 8,
+            16,
+        );
+        let mut string_offset = child_list_offset + self.children.len() as u64 * 0x10;
+
+        let mut strings = vec![];
+        let mut string_offsets = vec![];
+        for name in self
+            .resource_names
+            .iter()
+            .chain(self.children.iter().map(|child| &child.name))
+        {
+            string_offsets.push(string_offset);
+            let len = strings.len();
+            strings.write_u16str(name)?;
+            string_offset += (strings.len() - len) as u64;
+        }
+        let rsz_offset = align_up(string_offset, 16);
+
+        let mut buf = Cursor::new(vec![]);
+        buf.write_magic(b"USR\0")?;
+        buf.write_u32(u32::try_from(self.resource_names.len())?)?;
+        buf.write_u32(u32::try_from(self.children.len())?)?;
+        buf.write_u32(0)?;
+        buf.write_u64(resource_list_offset)?;
+        buf.write_u64(child_list_offset)?;
+        buf.write_u64(rsz_offset)?;
+        pad16(&mut buf)?;
+        let (resource_string_offsets, child_string_offsets) =
+            string_offsets.split_at(self.resource_names.len());
+        for &offset in resource_string_offsets {
+            buf.write_u64(offset)?;
+        }
+        pad16(&mut buf)?;
+        for (child, &offset) in self.children.iter().zip(child_string_offsets) {
+            buf.write_u32(child.hash)?;
+            buf.write_u32(0)?;
+            buf.write_u64(offset)?;
+        }
+        buf.write_all(&strings)?;
+        pad16(&mut buf)?;
+        self.rsz.write(&mut buf)?;
+
+        file.write_all(&buf.into_inner())?;
+        Ok(())
+    }
 }
@@ -1,24 +1,30 @@
 use crate::file_ext::*;
 use anyhow::{bail, Result};
 use nalgebra_glm::*;
+use serde::*;
 use std::io::{Read, Seek};
 
+#[derive(Serialize)]
 pub struct TextureRef {
     pub id: u64,
     pub path: String,
 }
 
+#[derive(Serialize)]
 pub struct Spriter {
     pub id: u64,
     pub p0: Vec2,
     pub p1: Vec2,
+    pub texture_index: u32,
     pub anchors: Option<Vec<Vec2>>,
 }
 
+#[derive(Serialize)]
 pub struct SpriterGroup {
     pub spriters: Vec<Spriter>,
 }
 
+#[derive(Serialize)]
 pub struct Uvs {
     pub textures: Vec<TextureRef>,
     pub spriter_groups: Vec<SpriterGroup>,
@@ -126,6 +132,7 @@ impl Uvs {
                                 id,
                                 p0: vec2(x0, y0),
                                 p1: vec2(x1, y1),
+                                texture_index,
                                 anchors,
                             })
                         })